@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN start_offset_ms INTEGER;
+ALTER TABLE songs ADD COLUMN end_offset_ms INTEGER;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN index_album_artist_fallback TEXT NOT NULL DEFAULT "artist";
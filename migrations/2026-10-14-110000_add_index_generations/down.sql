@@ -0,0 +1,3 @@
+DROP TABLE index_changes;
+DROP TABLE index_fingerprints;
+DROP TABLE index_generations;
@@ -0,0 +1,19 @@
+CREATE TABLE index_generations (
+	id INTEGER PRIMARY KEY NOT NULL,
+	date INTEGER NOT NULL
+);
+
+CREATE TABLE index_fingerprints (
+	path TEXT PRIMARY KEY NOT NULL,
+	fingerprint BIGINT NOT NULL
+);
+
+CREATE TABLE index_changes (
+	id INTEGER PRIMARY KEY NOT NULL,
+	generation INTEGER NOT NULL,
+	path TEXT NOT NULL,
+	previous_fingerprint BIGINT,
+	fingerprint BIGINT,
+	FOREIGN KEY(generation) REFERENCES index_generations(id) ON DELETE CASCADE,
+	UNIQUE(generation, path) ON CONFLICT REPLACE
+);
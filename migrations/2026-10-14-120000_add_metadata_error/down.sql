@@ -0,0 +1,39 @@
+CREATE TEMPORARY TABLE songs_backup(id, path, parent, track_number, disc_number, title, artist, album_artist, year, album, artwork, duration, lyricist, composer, genre, label, start_offset_ms, end_offset_ms);
+INSERT INTO songs_backup SELECT id, path, parent, track_number, disc_number, title, artist, album_artist, year, album, artwork, duration, lyricist, composer, genre, label, start_offset_ms, end_offset_ms FROM songs;
+DROP TABLE songs;
+CREATE TABLE songs (
+	id INTEGER PRIMARY KEY NOT NULL,
+	path TEXT NOT NULL,
+	parent TEXT NOT NULL,
+	track_number INTEGER,
+	disc_number INTEGER,
+	title TEXT,
+	artist TEXT,
+	album_artist TEXT,
+	year INTEGER,
+	album TEXT,
+	artwork TEXT,
+	duration INTEGER,
+	lyricist TEXT,
+	composer TEXT,
+	genre TEXT,
+	label TEXT,
+	start_offset_ms INTEGER,
+	end_offset_ms INTEGER,
+	UNIQUE(path) ON CONFLICT REPLACE
+);
+INSERT INTO songs SELECT * FROM songs_backup;
+DROP TABLE songs_backup;
+
+CREATE TEMPORARY TABLE misc_settings_backup(id, auth_secret, index_sleep_duration_seconds, index_album_art_pattern, index_album_artist_fallback);
+INSERT INTO misc_settings_backup SELECT id, auth_secret, index_sleep_duration_seconds, index_album_art_pattern, index_album_artist_fallback FROM misc_settings;
+DROP TABLE misc_settings;
+CREATE TABLE misc_settings (
+	   id INTEGER PRIMARY KEY NOT NULL CHECK(id = 0),
+	   auth_secret BLOB NOT NULL DEFAULT (randomblob(32)),
+	   index_sleep_duration_seconds INTEGER NOT NULL,
+	   index_album_art_pattern TEXT NOT NULL,
+	   index_album_artist_fallback TEXT NOT NULL DEFAULT "artist"
+);
+INSERT INTO misc_settings SELECT * FROM misc_settings_backup;
+DROP TABLE misc_settings_backup;
@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN metadata_error BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE misc_settings ADD COLUMN index_keep_unreadable_songs BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN index_path_pattern TEXT NOT NULL DEFAULT "";
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN index_exact_durations BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE songs ADD COLUMN duration_decoded BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,7 @@
+CREATE TABLE audit_log (
+	id INTEGER PRIMARY KEY NOT NULL,
+	date INTEGER NOT NULL,
+	actor TEXT,
+	action TEXT NOT NULL,
+	details TEXT NOT NULL
+);
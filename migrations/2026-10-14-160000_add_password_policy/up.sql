@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings ADD COLUMN password_min_length INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE misc_settings ADD COLUMN password_require_complexity BOOLEAN NOT NULL DEFAULT 0;
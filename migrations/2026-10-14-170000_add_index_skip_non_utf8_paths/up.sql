@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN index_skip_non_utf8_paths BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,18 @@
+CREATE TEMPORARY TABLE misc_settings_backup(id, auth_secret, index_sleep_duration_seconds, index_album_art_pattern, index_album_artist_fallback, index_keep_unreadable_songs, index_path_pattern, index_exact_durations, password_min_length, password_require_complexity, index_skip_non_utf8_paths);
+INSERT INTO misc_settings_backup SELECT id, auth_secret, index_sleep_duration_seconds, index_album_art_pattern, index_album_artist_fallback, index_keep_unreadable_songs, index_path_pattern, index_exact_durations, password_min_length, password_require_complexity, index_skip_non_utf8_paths FROM misc_settings;
+DROP TABLE misc_settings;
+CREATE TABLE misc_settings (
+	   id INTEGER PRIMARY KEY NOT NULL CHECK(id = 0),
+	   auth_secret BLOB NOT NULL DEFAULT (randomblob(32)),
+	   index_sleep_duration_seconds INTEGER NOT NULL,
+	   index_album_art_pattern TEXT NOT NULL,
+	   index_album_artist_fallback TEXT NOT NULL DEFAULT "artist",
+	   index_keep_unreadable_songs BOOLEAN NOT NULL DEFAULT 0,
+	   index_path_pattern TEXT NOT NULL DEFAULT "",
+	   index_exact_durations BOOLEAN NOT NULL DEFAULT 0,
+	   password_min_length INTEGER NOT NULL DEFAULT 0,
+	   password_require_complexity BOOLEAN NOT NULL DEFAULT 0,
+	   index_skip_non_utf8_paths BOOLEAN NOT NULL DEFAULT 0
+);
+INSERT INTO misc_settings SELECT * FROM misc_settings_backup;
+DROP TABLE misc_settings_backup;
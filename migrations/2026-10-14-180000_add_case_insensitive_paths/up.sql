@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN case_insensitive_paths BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN index_folder_name_albums BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+DROP TABLE queue_songs;
+DROP TABLE queues;
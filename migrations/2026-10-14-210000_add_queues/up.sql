@@ -0,0 +1,17 @@
+CREATE TABLE queues (
+	id INTEGER PRIMARY KEY NOT NULL,
+	owner INTEGER NOT NULL,
+	current_index INTEGER NOT NULL,
+	position_ms INTEGER NOT NULL,
+	FOREIGN KEY(owner) REFERENCES users(id) ON DELETE CASCADE,
+	UNIQUE(owner)
+);
+
+CREATE TABLE queue_songs (
+	id INTEGER PRIMARY KEY NOT NULL,
+	queue INTEGER NOT NULL,
+	path TEXT NOT NULL,
+	ordering INTEGER NOT NULL,
+	FOREIGN KEY(queue) REFERENCES queues(id) ON DELETE CASCADE,
+	UNIQUE(queue, ordering)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings ADD COLUMN index_min_year INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE misc_settings ADD COLUMN index_max_year INTEGER NOT NULL DEFAULT 0;
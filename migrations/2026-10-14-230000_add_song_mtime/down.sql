@@ -0,0 +1,30 @@
+CREATE TEMPORARY TABLE songs_backup(id, path, parent, track_number, disc_number, title, artist, album_artist, year, album, artwork, duration, lyricist, composer, genre, label, start_offset_ms, end_offset_ms, metadata_error, track_total, disc_total, duration_decoded);
+INSERT INTO songs_backup SELECT id, path, parent, track_number, disc_number, title, artist, album_artist, year, album, artwork, duration, lyricist, composer, genre, label, start_offset_ms, end_offset_ms, metadata_error, track_total, disc_total, duration_decoded FROM songs;
+DROP TABLE songs;
+CREATE TABLE songs (
+	id INTEGER PRIMARY KEY NOT NULL,
+	path TEXT NOT NULL,
+	parent TEXT NOT NULL,
+	track_number INTEGER,
+	disc_number INTEGER,
+	title TEXT,
+	artist TEXT,
+	album_artist TEXT,
+	year INTEGER,
+	album TEXT,
+	artwork TEXT,
+	duration INTEGER,
+	lyricist TEXT,
+	composer TEXT,
+	genre TEXT,
+	label TEXT,
+	start_offset_ms INTEGER,
+	end_offset_ms INTEGER,
+	metadata_error BOOLEAN NOT NULL DEFAULT 0,
+	track_total INTEGER,
+	disc_total INTEGER,
+	duration_decoded BOOLEAN NOT NULL DEFAULT 0,
+	UNIQUE(path) ON CONFLICT REPLACE
+);
+INSERT INTO songs SELECT * FROM songs_backup;
+DROP TABLE songs_backup;
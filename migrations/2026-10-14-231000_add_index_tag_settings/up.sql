@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN index_tag_settings TEXT;
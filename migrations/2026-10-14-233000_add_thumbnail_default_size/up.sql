@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN thumbnail_default_size TEXT NOT NULL DEFAULT "small";
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN index_artwork_preference TEXT NOT NULL DEFAULT "folder";
@@ -0,0 +1,9 @@
+CREATE TABLE device_preferences (
+	id INTEGER PRIMARY KEY NOT NULL,
+	owner INTEGER NOT NULL,
+	device TEXT NOT NULL,
+	web_theme_base TEXT,
+	web_theme_accent TEXT,
+	FOREIGN KEY(owner) REFERENCES users(id) ON DELETE CASCADE,
+	UNIQUE(owner, device)
+);
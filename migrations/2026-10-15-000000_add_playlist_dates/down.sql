@@ -0,0 +1,12 @@
+CREATE TEMPORARY TABLE playlists_backup(id, owner, name);
+INSERT INTO playlists_backup SELECT id, owner, name FROM playlists;
+DROP TABLE playlists;
+CREATE TABLE playlists (
+	id INTEGER PRIMARY KEY NOT NULL,
+	owner INTEGER NOT NULL,
+	name TEXT NOT NULL,
+	FOREIGN KEY(owner) REFERENCES users(id) ON DELETE CASCADE,
+	UNIQUE(owner, name) ON CONFLICT REPLACE
+);
+INSERT INTO playlists SELECT * FROM playlists_backup;
+DROP TABLE playlists_backup;
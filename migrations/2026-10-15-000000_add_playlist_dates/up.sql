@@ -0,0 +1,3 @@
+ALTER TABLE playlists ADD COLUMN created_at INTEGER;
+ALTER TABLE playlists ADD COLUMN updated_at INTEGER DEFAULT 0 NOT NULL;
+UPDATE playlists SET updated_at = CAST(strftime('%s', 'now') AS INTEGER);
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN index_compilation_artist_names TEXT NOT NULL DEFAULT "Various Artists";
@@ -0,0 +1 @@
+ALTER TABLE songs ADD COLUMN audio_path TEXT;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN transcode_targets TEXT NOT NULL DEFAULT "mp3:128,mp3:192,mp3:320,opus:96,opus:128";
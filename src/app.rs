@@ -4,6 +4,7 @@ use std::path::PathBuf;
 use crate::db::{self, DB};
 use crate::paths::Paths;
 
+pub mod audit;
 pub mod config;
 pub mod ddns;
 pub mod index;
@@ -11,6 +12,7 @@ pub mod lastfm;
 pub mod playlist;
 pub mod settings;
 pub mod thumbnail;
+pub mod transcode;
 pub mod user;
 pub mod vfs;
 
@@ -32,17 +34,17 @@ pub enum Error {
 #[derive(Clone)]
 pub struct App {
 	pub port: u16,
-	pub auth_secret: settings::AuthSecret,
 	pub web_dir_path: PathBuf,
 	pub swagger_dir_path: PathBuf,
-	pub db: DB,
 	pub index: index::Index,
+	pub audit_manager: audit::Manager,
 	pub config_manager: config::Manager,
 	pub ddns_manager: ddns::Manager,
 	pub lastfm_manager: lastfm::Manager,
 	pub playlist_manager: playlist::Manager,
 	pub settings_manager: settings::Manager,
 	pub thumbnail_manager: thumbnail::Manager,
+	pub transcode_manager: transcode::Manager,
 	pub user_manager: user::Manager,
 	pub vfs_manager: vfs::Manager,
 }
@@ -59,11 +61,12 @@ impl App {
 		fs::create_dir_all(&thumbnails_dir_path)
 			.map_err(|e| Error::Io(thumbnails_dir_path.clone(), e))?;
 
+		let audit_manager = audit::Manager::new(db.clone());
 		let vfs_manager = vfs::Manager::new(db.clone());
 		let settings_manager = settings::Manager::new(db.clone());
 		let auth_secret = settings_manager.get_auth_secret()?;
 		let ddns_manager = ddns::Manager::new(db.clone());
-		let user_manager = user::Manager::new(db.clone(), auth_secret);
+		let user_manager = user::Manager::new(db.clone(), auth_secret, settings_manager.clone());
 		let index = index::Index::new(db.clone(), vfs_manager.clone(), settings_manager.clone());
 		let config_manager = config::Manager::new(
 			settings_manager.clone(),
@@ -73,6 +76,7 @@ impl App {
 		);
 		let playlist_manager = playlist::Manager::new(db.clone(), vfs_manager.clone());
 		let thumbnail_manager = thumbnail::Manager::new(thumbnails_dir_path);
+		let transcode_manager = transcode::Manager::new();
 		let lastfm_manager = lastfm::Manager::new(index.clone(), user_manager.clone());
 
 		if let Some(config_path) = paths.config_file_path {
@@ -80,23 +84,21 @@ impl App {
 			config_manager.apply(&config)?;
 		}
 
-		let auth_secret = settings_manager.get_auth_secret()?;
-
 		Ok(Self {
 			port,
-			auth_secret,
 			web_dir_path: paths.web_dir_path,
 			swagger_dir_path: paths.swagger_dir_path,
 			index,
+			audit_manager,
 			config_manager,
 			ddns_manager,
 			lastfm_manager,
 			playlist_manager,
 			settings_manager,
 			thumbnail_manager,
+			transcode_manager,
 			user_manager,
 			vfs_manager,
-			db,
 		})
 	}
 }
@@ -0,0 +1,174 @@
+use diesel::prelude::*;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::db::{self, audit_log, DB};
+
+const MAX_ENTRIES: i64 = 10_000; // Older entries are deleted as new ones are recorded
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error(transparent)]
+	DatabaseConnection(#[from] db::Error),
+	#[error(transparent)]
+	Database(#[from] diesel::result::Error),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+	ApplyConfig,
+	CancelScan,
+	CleanIndex,
+	CreateUser,
+	DeleteUser,
+	RefreshArtwork,
+	TriggerIndex,
+	UpdateDDNSConfig,
+	UpdateMountDirs,
+	UpdateSettings,
+	UpdateUser,
+}
+
+impl Action {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Action::ApplyConfig => "apply_config",
+			Action::CancelScan => "cancel_scan",
+			Action::CleanIndex => "clean_index",
+			Action::CreateUser => "create_user",
+			Action::DeleteUser => "delete_user",
+			Action::RefreshArtwork => "refresh_artwork",
+			Action::TriggerIndex => "trigger_index",
+			Action::UpdateDDNSConfig => "update_ddns_config",
+			Action::UpdateMountDirs => "update_mount_dirs",
+			Action::UpdateSettings => "update_settings",
+			Action::UpdateUser => "update_user",
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Queryable, Serialize, Deserialize)]
+pub struct Entry {
+	#[serde(skip_serializing, skip_deserializing)]
+	id: i32,
+	pub date: i32,
+	// Missing when the action was performed before any user existed
+	pub actor: Option<String>,
+	pub action: String,
+	pub details: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = audit_log)]
+struct NewEntry<'a> {
+	date: i32,
+	actor: Option<&'a str>,
+	action: &'a str,
+	details: &'a str,
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	db: DB,
+	max_entries: i64,
+}
+
+impl Manager {
+	pub fn new(db: DB) -> Self {
+		Self {
+			db,
+			max_entries: MAX_ENTRIES,
+		}
+	}
+
+	// Failing to record an entry does not fail the action being audited
+	pub fn record(&self, actor: Option<&str>, action: Action, details: &str) {
+		if let Err(e) = self.try_record(actor, action, details) {
+			error!("Error while recording audit log entry: {}", e);
+		}
+	}
+
+	fn try_record(&self, actor: Option<&str>, action: Action, details: &str) -> Result<(), Error> {
+		let date = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs() as i32)
+			.unwrap_or_default();
+		let mut connection = self.db.connect()?;
+		diesel::insert_into(audit_log::table)
+			.values(NewEntry {
+				date,
+				actor,
+				action: action.as_str(),
+				details,
+			})
+			.execute(&mut connection)?;
+
+		let oldest_kept: Option<i32> = audit_log::table
+			.select(audit_log::id)
+			.order(audit_log::id.desc())
+			.offset(self.max_entries - 1)
+			.first(&mut connection)
+			.optional()?;
+		if let Some(oldest_kept) = oldest_kept {
+			diesel::delete(audit_log::table.filter(audit_log::id.lt(oldest_kept)))
+				.execute(&mut connection)?;
+		}
+
+		Ok(())
+	}
+
+	// Returns the most recent entries first. SQLite treats negative limits as no limit, so the
+	// limit is clamped to the number of entries which can exist.
+	pub fn list(&self, limit: i64) -> Result<Vec<Entry>, Error> {
+		let mut connection = self.db.connect()?;
+		let entries = audit_log::table
+			.order(audit_log::id.desc())
+			.limit(limit.clamp(0, self.max_entries))
+			.load(&mut connection)?;
+		Ok(entries)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	#[test]
+	fn lists_most_recent_entries_first() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		ctx.audit_manager
+			.record(Some("Walter"), Action::CreateUser, "Jesse");
+		ctx.audit_manager
+			.record(Some("Walter"), Action::DeleteUser, "Jesse");
+		ctx.audit_manager.record(None, Action::TriggerIndex, "");
+
+		let entries = ctx.audit_manager.list(2).unwrap();
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0].actor, None);
+		assert_eq!(entries[0].action, "trigger_index");
+		assert_eq!(entries[1].actor, Some("Walter".to_owned()));
+		assert_eq!(entries[1].action, "delete_user");
+		assert_eq!(entries[1].details, "Jesse");
+
+		assert!(ctx.audit_manager.list(-1).unwrap().is_empty());
+	}
+
+	#[test]
+	fn deletes_oldest_entries() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let audit_manager = Manager {
+			db: ctx.db.clone(),
+			max_entries: 2,
+		};
+		audit_manager.record(Some("Walter"), Action::CreateUser, "Jesse");
+		audit_manager.record(Some("Walter"), Action::CreateUser, "Skyler");
+		audit_manager.record(Some("Walter"), Action::CreateUser, "Hank");
+
+		let entries = audit_manager.list(10).unwrap();
+		let details: Vec<&str> = entries.iter().map(|e| e.details.as_str()).collect();
+		assert_eq!(details, vec!["Hank", "Skyler"]);
+	}
+}
@@ -120,6 +120,21 @@ mod test {
 		let new_config = Config {
 			settings: Some(settings::NewSettings {
 				album_art_pattern: Some("🖼️\\.jpg".into()),
+				album_artist_fallback: Some("various_artists".into()),
+				keep_unreadable_songs: Some(true),
+				path_pattern: Some("{artist}/{album}/{title}".into()),
+				exact_durations: Some(true),
+				skip_non_utf8_paths: Some(true),
+				password_min_length: Some(8),
+				password_require_complexity: Some(true),
+				case_insensitive_paths: Some(true),
+				folder_name_albums: Some(true),
+				min_year: Some(1900),
+				max_year: Some(2100),
+				thumbnail_default_size: Some("large".into()),
+				artwork_preference: Some("embedded".into()),
+				compilation_artist_names: Some(vec!["Various Artists".into(), "VA".into()]),
+				transcode_targets: Some(vec!["mp3:320".into()]),
 				reindex_every_n_seconds: Some(100),
 			}),
 			..Default::default()
@@ -132,12 +147,99 @@ mod test {
 			settings.index_album_art_pattern,
 			new_settings.album_art_pattern.unwrap()
 		);
+		assert_eq!(
+			settings.index_album_artist_fallback,
+			new_settings.album_artist_fallback.unwrap()
+		);
+		assert_eq!(
+			settings.index_keep_unreadable_songs,
+			new_settings.keep_unreadable_songs.unwrap()
+		);
+		assert_eq!(
+			settings.index_path_pattern,
+			new_settings.path_pattern.unwrap()
+		);
+		assert_eq!(
+			settings.index_exact_durations,
+			new_settings.exact_durations.unwrap()
+		);
+		assert_eq!(
+			settings.index_skip_non_utf8_paths,
+			new_settings.skip_non_utf8_paths.unwrap()
+		);
+		assert_eq!(
+			settings.password_min_length,
+			new_settings.password_min_length.unwrap()
+		);
+		assert_eq!(
+			settings.password_require_complexity,
+			new_settings.password_require_complexity.unwrap()
+		);
+		assert_eq!(
+			settings.case_insensitive_paths,
+			new_settings.case_insensitive_paths.unwrap()
+		);
+		assert_eq!(
+			settings.index_folder_name_albums,
+			new_settings.folder_name_albums.unwrap()
+		);
+		assert_eq!(settings.index_min_year, new_settings.min_year.unwrap());
+		assert_eq!(settings.index_max_year, new_settings.max_year.unwrap());
+		assert_eq!(
+			settings.thumbnail_default_size,
+			new_settings.thumbnail_default_size.unwrap()
+		);
+		assert_eq!(
+			settings.index_artwork_preference,
+			new_settings.artwork_preference.unwrap()
+		);
+		assert_eq!(
+			settings.compilation_artist_names(),
+			new_settings.compilation_artist_names.unwrap()
+		);
+		assert_eq!(
+			settings.transcode_targets(),
+			new_settings.transcode_targets.unwrap()
+		);
 		assert_eq!(
 			settings.index_sleep_duration_seconds,
 			new_settings.reindex_every_n_seconds.unwrap()
 		);
 	}
 
+	#[test]
+	fn apply_rejects_negative_reindex_interval() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let new_config = Config {
+			settings: Some(settings::NewSettings {
+				reindex_every_n_seconds: Some(-1),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		assert!(matches!(
+			ctx.config_manager.apply(&new_config),
+			Err(Error::Settings(settings::Error::IndexSleepDurationInvalid))
+		));
+	}
+
+	#[test]
+	fn reindex_interval_of_zero_disables_periodic_updates() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let new_config = Config {
+			settings: Some(settings::NewSettings {
+				reindex_every_n_seconds: Some(0),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		ctx.config_manager.apply(&new_config).unwrap();
+		assert_eq!(
+			ctx.settings_manager.get_index_sleep_duration().unwrap(),
+			None
+		);
+	}
+
 	#[test]
 	fn apply_saves_mount_points() {
 		let ctx = test::ContextBuilder::new(test_name!()).build();
@@ -1,19 +1,30 @@
 use log::error;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
 use std::time::Duration;
 
 use crate::app::{settings, vfs};
 use crate::db::DB;
 
+mod cue;
 mod metadata;
+mod path_pattern;
 mod query;
+mod sync;
 #[cfg(test)]
 mod test;
 mod types;
 mod update;
 
+pub use self::cue::{is_track_path as is_cue_track_path, Segment as CueSegment};
+pub use self::path_pattern::PathPattern;
 pub use self::query::*;
+pub use self::sync::{GenerationInfo, SyncChanges};
 pub use self::types::*;
+pub use self::update::{CleanReport, Error as UpdateError, ScanRecord, ScanStatus};
+
+const VARIOUS_ARTISTS: &str = "Various Artists";
 
 #[derive(Clone)]
 pub struct Index {
@@ -21,11 +32,14 @@ pub struct Index {
 	vfs_manager: vfs::Manager,
 	settings_manager: settings::Manager,
 	pending_reindex: Arc<(Mutex<bool>, Condvar)>,
+	scan_progress: Arc<update::ScanProgress>,
+	scan_history: Arc<Mutex<VecDeque<ScanRecord>>>,
+	initial_scan_completed: Arc<AtomicBool>,
 }
 
 impl Index {
 	pub fn new(db: DB, vfs_manager: vfs::Manager, settings_manager: settings::Manager) -> Self {
-		let index = Self {
+		let mut index = Self {
 			db,
 			vfs_manager,
 			settings_manager,
@@ -35,7 +49,15 @@ impl Index {
 				Mutex::new(false),
 				Condvar::new(),
 			)),
+			scan_progress: Arc::default(),
+			scan_history: Arc::default(),
+			initial_scan_completed: Arc::default(),
 		};
+		let generation = index.get_generation().unwrap_or_else(|e| {
+			error!("Could not read index generation: {}", e);
+			None
+		});
+		index.initial_scan_completed = Arc::new(AtomicBool::new(generation.is_some()));
 
 		let commands_index = index.clone();
 		std::thread::spawn(move || {
@@ -45,11 +67,22 @@ impl Index {
 		index
 	}
 
-	pub fn trigger_reindex(&self) {
+	// Distinguishes a collection which is empty from one which was never indexed. Every
+	// completed update records a generation, so this survives restarts. Only the database is
+	// read when the index is created, so this is cheap enough to call on every request.
+	pub fn is_initial_scan_completed(&self) -> bool {
+		self.initial_scan_completed.load(Ordering::Relaxed)
+	}
+
+	// Returns whether an update was already running or queued. Triggers received while an
+	// update is running are merged into a single follow-up update.
+	pub fn trigger_reindex(&self) -> bool {
 		let (lock, cvar) = &*self.pending_reindex;
 		let mut pending_reindex = lock.lock().unwrap();
+		let already_running = *pending_reindex || self.get_scan_status().running;
 		*pending_reindex = true;
 		cvar.notify_one();
+		already_running
 	}
 
 	pub fn begin_periodic_updates(&self) {
@@ -67,8 +100,8 @@ impl Index {
 				while !*pending {
 					pending = cvar.wait(pending).unwrap();
 				}
-				*pending = false;
 			}
+			// The pending flag is cleared by the update itself, once it is running
 			if let Err(e) = self.update() {
 				error!("Error while updating index: {}", e);
 			}
@@ -76,16 +109,21 @@ impl Index {
 	}
 
 	fn automatic_reindex(&self) {
+		self.trigger_reindex();
 		loop {
-			self.trigger_reindex();
-			let sleep_duration = self
-				.settings_manager
-				.get_index_sleep_duration()
-				.unwrap_or_else(|e| {
+			match self.settings_manager.get_index_sleep_duration() {
+				Ok(Some(sleep_duration)) => {
+					std::thread::sleep(sleep_duration);
+					self.trigger_reindex();
+				}
+				// Periodic updates are disabled, check again later in case the setting changes
+				Ok(None) => std::thread::sleep(Duration::from_secs(60)),
+				Err(e) => {
 					error!("Could not retrieve index sleep duration: {}", e);
-					Duration::from_secs(1800)
-				});
-			std::thread::sleep(sleep_duration);
+					std::thread::sleep(Duration::from_secs(1800));
+					self.trigger_reindex();
+				}
+			}
 		}
 	}
 }
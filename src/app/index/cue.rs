@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("Filesystem error for `{0}`: `{1}`")]
+	Io(PathBuf, std::io::Error),
+}
+
+const CUE_TRACK_SEPARATOR: char = '#';
+const FRAMES_PER_SECOND: u32 = 75;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CueSheet {
+	pub title: Option<String>,
+	pub performer: Option<String>,
+	pub songwriter: Option<String>,
+	pub genre: Option<String>,
+	pub year: Option<i32>,
+	pub files: Vec<CueFile>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CueFile {
+	pub name: String,
+	pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CueTrack {
+	pub number: u32,
+	pub title: Option<String>,
+	pub performer: Option<String>,
+	pub songwriter: Option<String>,
+	pub start_ms: Option<u32>,
+}
+
+// Portion of an audio file covered by a single cue sheet track
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+	pub audio_path: PathBuf,
+	pub start_ms: u32,
+	pub end_ms: Option<u32>,
+}
+
+pub fn is_cue_sheet(path: &Path) -> bool {
+	path.extension()
+		.and_then(|e| e.to_str())
+		.map(|e| e.eq_ignore_ascii_case("cue"))
+		.unwrap_or(false)
+}
+
+pub fn read(path: &Path) -> Result<CueSheet, Error> {
+	let bytes = fs::read(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+	let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+	Ok(parse(&String::from_utf8_lossy(bytes)))
+}
+
+pub fn parse(content: &str) -> CueSheet {
+	let mut sheet = CueSheet::default();
+	for line in content.lines() {
+		let line = line.trim();
+		let (command, arguments) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+		let arguments = arguments.trim();
+		let track = sheet.files.last_mut().and_then(|f| f.tracks.last_mut());
+		crate::utils::match_ignore_case! {
+			match command {
+				"FILE" => sheet.files.push(CueFile {
+					name: parse_file_name(arguments),
+					tracks: Vec::new(),
+				}),
+				"TRACK" => {
+					let number = arguments.split_whitespace().next().and_then(|n| n.parse().ok());
+					if let (Some(file), Some(number)) = (sheet.files.last_mut(), number) {
+						file.tracks.push(CueTrack {
+							number,
+							..Default::default()
+						});
+					}
+				},
+				"INDEX" => {
+					let mut words = arguments.split_whitespace();
+					if words.next() == Some("01") {
+						if let Some(track) = track {
+							track.start_ms = words.next().and_then(parse_timestamp);
+						}
+					}
+				},
+				"TITLE" => match track {
+					Some(t) => t.title = Some(unquote(arguments)),
+					None => sheet.title = Some(unquote(arguments)),
+				},
+				"PERFORMER" => match track {
+					Some(t) => t.performer = Some(unquote(arguments)),
+					None => sheet.performer = Some(unquote(arguments)),
+				},
+				"SONGWRITER" => match track {
+					Some(t) => t.songwriter = Some(unquote(arguments)),
+					None => sheet.songwriter = Some(unquote(arguments)),
+				},
+				"REM" => {
+					let (key, value) = arguments.split_once(char::is_whitespace).unwrap_or((arguments, ""));
+					let value = unquote(value.trim());
+					crate::utils::match_ignore_case! {
+						match key {
+							"GENRE" => sheet.genre = Some(value),
+							"DATE" => sheet.year = value.get(0..4).and_then(|y| y.parse().ok()),
+							_ => (),
+						}
+					}
+				},
+				_ => (),
+			}
+		}
+	}
+
+	for file in &mut sheet.files {
+		file.tracks.retain(|t| t.start_ms.is_some());
+	}
+
+	sheet
+}
+
+// Cue sheet tracks are indexed under a path made of the cue sheet path and the track number.
+// For example, `Album.cue#03` for the third track described in `Album.cue`.
+pub fn track_path(cue_path: &Path, track_number: u32) -> PathBuf {
+	let mut path = cue_path.as_os_str().to_owned();
+	path.push(format!("{}{:02}", CUE_TRACK_SEPARATOR, track_number));
+	PathBuf::from(path)
+}
+
+// Whether a path was made by `track_path`. The cue sheet itself is not read.
+pub fn is_track_path(path: &Path) -> bool {
+	let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+		return false;
+	};
+	let Some((cue_name, track_number)) = file_name.rsplit_once(CUE_TRACK_SEPARATOR) else {
+		return false;
+	};
+	track_number.parse::<u32>().is_ok() && is_cue_sheet(Path::new(cue_name))
+}
+
+fn parse_file_name(arguments: &str) -> String {
+	if let Some(quoted) = arguments.strip_prefix('"') {
+		return quoted.split('"').next().unwrap_or_default().to_owned();
+	}
+	// Unquoted file names cannot contain spaces and are followed by the file type
+	arguments
+		.split_whitespace()
+		.next()
+		.unwrap_or_default()
+		.to_owned()
+}
+
+fn unquote(value: &str) -> String {
+	value
+		.strip_prefix('"')
+		.and_then(|v| v.strip_suffix('"'))
+		.unwrap_or(value)
+		.to_owned()
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<u32> {
+	let mut parts = timestamp.split(':').map(|p| p.parse::<u32>().ok());
+	let minutes = parts.next()??;
+	let seconds = parts.next()??;
+	let frames = parts.next()??;
+	if parts.next().is_some() {
+		return None;
+	}
+	Some((minutes * 60 + seconds) * 1000 + frames * 1000 / FRAMES_PER_SECOND)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	const SAMPLE_CUE_SHEET: &str = r#"
+REM GENRE "Doom Metal"
+REM DATE 2016
+PERFORMER "Khemmis"
+TITLE "Hunted"
+FILE "Khemmis - Hunted.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "Above The Water"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Candlelight"
+    PERFORMER "Khemmis feat. Someone"
+    INDEX 00 06:20:10
+    INDEX 01 06:22:37
+"#;
+
+	#[test]
+	fn can_parse_cue_sheet() {
+		let sheet = parse(SAMPLE_CUE_SHEET);
+		assert_eq!(
+			sheet,
+			CueSheet {
+				title: Some("Hunted".to_owned()),
+				performer: Some("Khemmis".to_owned()),
+				songwriter: None,
+				genre: Some("Doom Metal".to_owned()),
+				year: Some(2016),
+				files: vec![CueFile {
+					name: "Khemmis - Hunted.flac".to_owned(),
+					tracks: vec![
+						CueTrack {
+							number: 1,
+							title: Some("Above The Water".to_owned()),
+							performer: None,
+							songwriter: None,
+							start_ms: Some(0),
+						},
+						CueTrack {
+							number: 2,
+							title: Some("Candlelight".to_owned()),
+							performer: Some("Khemmis feat. Someone".to_owned()),
+							songwriter: None,
+							start_ms: Some(382_493),
+						},
+					],
+				}],
+			}
+		);
+	}
+
+	#[test]
+	fn ignores_tracks_without_start_index() {
+		let sheet = parse("FILE album.wav WAVE\nTRACK 01 AUDIO\nTITLE \"Nowhere\"\n");
+		assert_eq!(sheet.files[0].name, "album.wav");
+		assert!(sheet.files[0].tracks.is_empty());
+	}
+
+	#[test]
+	fn can_recognize_track_path() {
+		let cue_path: PathBuf = ["test-data", "cue", "sample.cue"].iter().collect();
+		let path = track_path(&cue_path, 2);
+		assert_eq!(path, cue_path.with_file_name("sample.cue#02"));
+		assert!(is_track_path(&path));
+		assert!(!is_track_path(&cue_path));
+		assert!(!is_track_path(&cue_path.with_file_name("sample.flac")));
+		assert!(!is_track_path(&cue_path.with_file_name("sample.flac#02")));
+		assert!(!is_track_path(
+			&cue_path.with_file_name("sample.cue#side-a")
+		));
+	}
+}
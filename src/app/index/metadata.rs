@@ -1,13 +1,19 @@
 use id3::TagLike;
 use lewton::inside_ogg::OggStreamReader;
 use log::error;
-use regex::Regex;
 use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 
 use crate::utils;
 use crate::utils::AudioFormat;
 
+// ID3 pictures with this MIME type hold the location of an image file instead of image data
+const ID3_LINKED_PICTURE_MIME_TYPE: &str = "-->";
+
+const OPUS_SAMPLE_RATE: u64 = 48000; // Opus granule positions always count samples at 48kHz
+const MAX_OGG_PAGE_SIZE: u64 = 65307; // Bytes, including the page header
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
 	#[error(transparent)]
@@ -16,9 +22,13 @@ pub enum Error {
 	Id3(#[from] id3::Error),
 	#[error("Filesystem error for `{0}`: `{1}`")]
 	Io(PathBuf, std::io::Error),
+	#[error("Could not find the audio properties of `{0}`")]
+	AudioPropertiesNotFound(PathBuf),
 	#[error(transparent)]
 	Metaflac(#[from] metaflac::Error),
 	#[error(transparent)]
+	Mp3Duration(#[from] mp3_duration::MP3DurationError),
+	#[error(transparent)]
 	Mp4aMeta(#[from] mp4ameta::Error),
 	#[error(transparent)]
 	Opus(#[from] opus_headers::ParseError),
@@ -28,10 +38,50 @@ pub enum Error {
 	VorbisCommentNotFoundInFlacFile,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+// Gains are in dB, peaks are relative to full scale
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplayGain {
+	pub track_gain: Option<f32>,
+	pub track_peak: Option<f32>,
+	pub album_gain: Option<f32>,
+	pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+	// Ignores keys which are not ReplayGain tags
+	fn read(&mut self, key: &str, value: &str) {
+		utils::match_ignore_case! {
+			match key {
+				"REPLAYGAIN_TRACK_GAIN" => self.track_gain = parse_replay_gain(value),
+				"REPLAYGAIN_TRACK_PEAK" => self.track_peak = parse_replay_gain(value),
+				"REPLAYGAIN_ALBUM_GAIN" => self.album_gain = parse_replay_gain(value),
+				"REPLAYGAIN_ALBUM_PEAK" => self.album_peak = parse_replay_gain(value),
+				_ => (),
+			}
+		}
+	}
+}
+
+// Accepts values with or without a unit suffix, eg. `-6.5 dB`
+fn parse_replay_gain(value: &str) -> Option<f32> {
+	let value = value.trim();
+	let has_unit = value.len() >= 2
+		&& value
+			.get(value.len() - 2..)
+			.is_some_and(|u| u.eq_ignore_ascii_case("db"));
+	let value = match has_unit {
+		true => &value[..value.len() - 2],
+		false => value,
+	};
+	value.trim().parse::<f32>().ok().filter(|v| v.is_finite())
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct SongTags {
 	pub disc_number: Option<u32>,
+	pub disc_total: Option<u32>,
 	pub track_number: Option<u32>,
+	pub track_total: Option<u32>,
 	pub title: Option<String>,
 	pub duration: Option<u32>,
 	pub artist: Option<String>,
@@ -39,10 +89,13 @@ pub struct SongTags {
 	pub album: Option<String>,
 	pub year: Option<i32>,
 	pub has_artwork: bool,
+	// Image file named by the tags instead of being embedded, relative to the song's directory
+	pub artwork_reference: Option<String>,
 	pub lyricist: Option<String>,
 	pub composer: Option<String>,
 	pub genre: Option<String>,
 	pub label: Option<String>,
+	pub replay_gain: ReplayGain,
 }
 
 impl From<id3::Tag> for SongTags {
@@ -53,21 +106,35 @@ impl From<id3::Tag> for SongTags {
 		let title = tag.title().map(|s| s.to_string());
 		let duration = tag.duration();
 		let disc_number = tag.disc();
+		let disc_total = tag.total_discs();
 		let track_number = tag.track();
+		let track_total = tag.total_tracks();
 		let year = tag
 			.year()
 			.or_else(|| tag.date_released().map(|d| d.year))
 			.or_else(|| tag.original_date_released().map(|d| d.year))
 			.or_else(|| tag.date_recorded().map(|d| d.year));
-		let has_artwork = tag.pictures().count() > 0;
+		let has_artwork = tag
+			.pictures()
+			.any(|p| p.mime_type != ID3_LINKED_PICTURE_MIME_TYPE);
+		let artwork_reference = tag
+			.pictures()
+			.find(|p| p.mime_type == ID3_LINKED_PICTURE_MIME_TYPE)
+			.and_then(|p| String::from_utf8(p.data.clone()).ok());
 		let lyricist = tag.get_text("TEXT");
 		let composer = tag.get_text("TCOM");
 		let genre = tag.genre().map(|s| s.to_string());
 		let label = tag.get_text("TPUB");
+		let mut replay_gain = ReplayGain::default();
+		for text in tag.extended_texts() {
+			replay_gain.read(&text.description, &text.value);
+		}
 
 		SongTags {
 			disc_number,
+			disc_total,
 			track_number,
+			track_total,
 			title,
 			duration,
 			artist,
@@ -75,15 +142,18 @@ impl From<id3::Tag> for SongTags {
 			album,
 			year,
 			has_artwork,
+			artwork_reference,
 			lyricist,
 			composer,
 			genre,
 			label,
+			replay_gain,
 		}
 	}
 }
 
-pub fn read(path: &Path) -> Option<SongTags> {
+// Returns `Ok(None)` for files which are not in a supported audio format
+pub fn read(path: &Path) -> Result<Option<SongTags>, Error> {
 	let data = match utils::get_audio_format(path) {
 		Some(AudioFormat::AIFF) => read_aiff(path),
 		Some(AudioFormat::FLAC) => read_flac(path),
@@ -93,17 +163,96 @@ pub fn read(path: &Path) -> Option<SongTags> {
 		Some(AudioFormat::WAVE) => read_wave(path),
 		Some(AudioFormat::APE) | Some(AudioFormat::MPC) => read_ape(path),
 		Some(AudioFormat::MP4) | Some(AudioFormat::M4B) => read_mp4(path),
-		None => return None,
+		None => return Ok(None),
+	};
+	data.map(Some)
+}
+
+// Measures the duration of files whose tags do not describe it from their audio data. MP3 and
+// Vorbis files are read in full, which is much slower than reading tags.
+pub fn decode_duration(path: &Path) -> Option<u32> {
+	let duration = match utils::get_audio_format(path) {
+		Some(AudioFormat::AIFF) => decode_aiff_duration(path),
+		Some(AudioFormat::MP3) => decode_mp3_duration(path),
+		Some(AudioFormat::OGG) => decode_vorbis_duration(path),
+		Some(AudioFormat::OPUS) => decode_opus_duration(path),
+		Some(AudioFormat::WAVE) => decode_wave_duration(path),
+		_ => return None,
 	};
-	match data {
+	match duration {
 		Ok(d) => Some(d),
 		Err(e) => {
-			error!("Error while reading file metadata for '{:?}': {}", path, e);
+			error!("Error while decoding duration of '{:?}': {}", path, e);
 			None
 		}
 	}
 }
 
+// Lists every tag field in a file as key-value pairs, including the ones Polaris does not index.
+// Returns `Ok(None)` for files which are not in a supported audio format.
+pub fn read_raw_tags(path: &Path) -> Result<Option<Vec<(String, String)>>, Error> {
+	let read_id3 = |tag: Result<id3::Tag, id3::Error>| -> Result<Vec<_>, id3::Error> {
+		let tag = tag.or_else(|error| {
+			if let Some(tag) = error.partial_tag {
+				Ok(tag)
+			} else {
+				Err(error)
+			}
+		})?;
+		Ok(tag
+			.frames()
+			.map(|f| (f.id().to_owned(), f.content().to_string()))
+			.collect())
+	};
+	let tags: Vec<(String, String)> = match utils::get_audio_format(path) {
+		Some(AudioFormat::AIFF) => read_id3(id3::Tag::read_from_aiff_path(path))?,
+		Some(AudioFormat::MP3) => read_id3(id3::Tag::read_from_path(path))?,
+		Some(AudioFormat::WAVE) => read_id3(id3::Tag::read_from_wav_path(path))?,
+		Some(AudioFormat::FLAC) => {
+			let tag = metaflac::Tag::read_from_path(path)?;
+			let vorbis = tag
+				.vorbis_comments()
+				.ok_or(Error::VorbisCommentNotFoundInFlacFile)?;
+			let mut tags: Vec<(String, String)> = vorbis
+				.comments
+				.iter()
+				.flat_map(|(k, values)| values.iter().map(|v| (k.clone(), v.clone())))
+				.collect();
+			tags.sort();
+			tags
+		}
+		Some(AudioFormat::OGG) => {
+			let file = fs::File::open(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+			OggStreamReader::new(file)?.comment_hdr.comment_list
+		}
+		Some(AudioFormat::OPUS) => {
+			let comments = opus_headers::parse_from_path(path)?.comments.user_comments;
+			let mut tags: Vec<(String, String)> = comments.into_iter().collect();
+			tags.sort();
+			tags
+		}
+		Some(AudioFormat::APE) | Some(AudioFormat::MPC) => ape::read_from_path(path)?
+			.iter()
+			.map(|item| {
+				let value = read_ape_string(item).unwrap_or_else(|| "<binary>".to_owned());
+				(item.key.clone(), value)
+			})
+			.collect(),
+		Some(AudioFormat::MP4) | Some(AudioFormat::M4B) => mp4ameta::Tag::read_from_path(path)?
+			.data()
+			.map(|(ident, data)| {
+				let value = data
+					.string()
+					.map(|s| s.to_owned())
+					.unwrap_or_else(|| format!("{:?}", data));
+				(ident.to_string(), value)
+			})
+			.collect(),
+		None => return Ok(None),
+	};
+	Ok(Some(tags))
+}
+
 trait FrameContent {
 	/// Returns the value stored, if any, in the Frame.
 	/// Say "TCOM" returns composer field.
@@ -140,6 +289,92 @@ fn read_mp3(path: &Path) -> Result<SongTags, Error> {
 	Ok(song_tags)
 }
 
+// Unlike when reading tags, frames preceding the first corrupted one still count towards the
+// duration
+fn decode_mp3_duration(path: &Path) -> Result<u32, Error> {
+	match mp3_duration::from_path(path) {
+		Ok(duration) => Ok(duration.as_secs() as u32),
+		Err(e) if !e.at_duration.is_zero() => Ok(e.at_duration.as_secs() as u32),
+		Err(e) => Err(e.into()),
+	}
+}
+
+fn decode_aiff_duration(path: &Path) -> Result<u32, Error> {
+	let mut file = fs::File::open(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+	let mut comm = [0; 18];
+	match find_iff_chunk(&mut file, b"COMM", true) {
+		Ok(Some(_)) => file
+			.read_exact(&mut comm)
+			.map_err(|e| Error::Io(path.to_owned(), e))?,
+		Ok(None) => return Err(Error::AudioPropertiesNotFound(path.to_owned())),
+		Err(e) => return Err(Error::Io(path.to_owned(), e)),
+	}
+	let num_frames = u32::from_be_bytes([comm[2], comm[3], comm[4], comm[5]]);
+	let sample_rate = parse_extended_float(&comm[8..18]);
+	if sample_rate < 1.0 {
+		return Err(Error::AudioPropertiesNotFound(path.to_owned()));
+	}
+	Ok((f64::from(num_frames) / sample_rate) as u32)
+}
+
+fn decode_wave_duration(path: &Path) -> Result<u32, Error> {
+	let mut file = fs::File::open(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+	let mut fmt = [0; 16];
+	match find_iff_chunk(&mut file, b"fmt ", false) {
+		Ok(Some(_)) => file
+			.read_exact(&mut fmt)
+			.map_err(|e| Error::Io(path.to_owned(), e))?,
+		Ok(None) => return Err(Error::AudioPropertiesNotFound(path.to_owned())),
+		Err(e) => return Err(Error::Io(path.to_owned(), e)),
+	}
+	let byte_rate = u32::from_le_bytes([fmt[8], fmt[9], fmt[10], fmt[11]]);
+	let data_size = match find_iff_chunk(&mut file, b"data", false) {
+		Ok(Some(size)) => size,
+		Ok(None) => return Err(Error::AudioPropertiesNotFound(path.to_owned())),
+		Err(e) => return Err(Error::Io(path.to_owned(), e)),
+	};
+	if byte_rate == 0 {
+		return Err(Error::AudioPropertiesNotFound(path.to_owned()));
+	}
+	Ok(data_size / byte_rate)
+}
+
+// Walks the chunks of a RIFF (little endian) or AIFF (big endian) file from the start, and
+// returns the size of the requested one. The file is left positioned at the chunk's contents.
+fn find_iff_chunk(file: &mut fs::File, id: &[u8; 4], big_endian: bool) -> io::Result<Option<u32>> {
+	file.seek(SeekFrom::Start(12))?;
+	let mut header = [0; 8];
+	loop {
+		match file.read_exact(&mut header) {
+			Ok(()) => (),
+			Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+			Err(e) => return Err(e),
+		}
+		let size = [header[4], header[5], header[6], header[7]];
+		let size = match big_endian {
+			true => u32::from_be_bytes(size),
+			false => u32::from_le_bytes(size),
+		};
+		if &header[0..4] == id {
+			return Ok(Some(size));
+		}
+		// Chunks are padded to an even number of bytes
+		file.seek(SeekFrom::Current(i64::from(size) + i64::from(size % 2)))?;
+	}
+}
+
+// AIFF sample rates are stored as 80-bit IEEE 754 extended precision numbers
+fn parse_extended_float(bytes: &[u8]) -> f64 {
+	let exponent = i32::from(u16::from_be_bytes([bytes[0], bytes[1]]) & 0x7fff);
+	let mut mantissa = [0; 8];
+	mantissa.copy_from_slice(&bytes[2..10]);
+	let mantissa = u64::from_be_bytes(mantissa);
+	if exponent == 0 && mantissa == 0 {
+		return 0.0;
+	}
+	mantissa as f64 * 2f64.powi(exponent - 16383 - 63)
+}
+
 fn read_aiff(path: &Path) -> Result<SongTags, Error> {
 	let tag = id3::Tag::read_from_aiff_path(path).or_else(|error| {
 		if let Some(tag) = error.partial_tag {
@@ -176,17 +411,22 @@ fn read_ape_i32(item: &ape::Item) -> Option<i32> {
 	}
 }
 
-fn read_ape_x_of_y(item: &ape::Item) -> Option<u32> {
+// Parses values like `3/12` into their number and total. Missing or malformed parts are `None`.
+fn parse_x_of_y(value: &str) -> (Option<u32>, Option<u32>) {
+	let (number, total) = match value.split_once('/') {
+		Some((number, total)) => (number, Some(total)),
+		None => (value, None),
+	};
+	(
+		number.trim().parse().ok(),
+		total.and_then(|t| t.trim().parse().ok()),
+	)
+}
+
+fn read_ape_x_of_y(item: &ape::Item) -> (Option<u32>, Option<u32>) {
 	match item.value {
-		ape::ItemValue::Text(ref s) => {
-			let format = Regex::new(r#"^\d+"#).unwrap();
-			if let Some(m) = format.find(s) {
-				s[m.start()..m.end()].parse().ok()
-			} else {
-				None
-			}
-		}
-		_ => None,
+		ape::ItemValue::Text(ref s) => parse_x_of_y(s),
+		_ => (None, None),
 	}
 }
 
@@ -197,12 +437,18 @@ fn read_ape(path: &Path) -> Result<SongTags, Error> {
 	let album_artist = tag.item("Album artist").and_then(read_ape_string);
 	let title = tag.item("Title").and_then(read_ape_string);
 	let year = tag.item("Year").and_then(read_ape_i32);
-	let disc_number = tag.item("Disc").and_then(read_ape_x_of_y);
-	let track_number = tag.item("Track").and_then(read_ape_x_of_y);
+	let (disc_number, disc_total) = tag.item("Disc").map(read_ape_x_of_y).unwrap_or_default();
+	let (track_number, track_total) = tag.item("Track").map(read_ape_x_of_y).unwrap_or_default();
 	let lyricist = tag.item("LYRICIST").and_then(read_ape_string);
 	let composer = tag.item("COMPOSER").and_then(read_ape_string);
 	let genre = tag.item("GENRE").and_then(read_ape_string);
 	let label = tag.item("PUBLISHER").and_then(read_ape_string);
+	let mut replay_gain = ReplayGain::default();
+	for item in tag.iter() {
+		if let Some(value) = read_ape_string(item) {
+			replay_gain.read(&item.key, &value);
+		}
+	}
 	Ok(SongTags {
 		artist,
 		album_artist,
@@ -210,16 +456,29 @@ fn read_ape(path: &Path) -> Result<SongTags, Error> {
 		title,
 		duration: None,
 		disc_number,
+		disc_total,
 		track_number,
+		track_total,
 		year,
 		has_artwork: false,
+		artwork_reference: None,
 		lyricist,
 		composer,
 		genre,
 		label,
+		replay_gain,
 	})
 }
 
+fn decode_vorbis_duration(path: &Path) -> Result<u32, Error> {
+	let file = fs::File::open(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+	let mut source = OggStreamReader::new(file)?;
+	while source.read_dec_packet()?.is_some() {}
+	let sample_rate = u64::from(source.ident_hdr.audio_sample_rate).max(1);
+	let num_samples = source.get_last_absgp().unwrap_or_default();
+	Ok((num_samples / sample_rate) as u32)
+}
+
 fn read_vorbis(path: &Path) -> Result<SongTags, Error> {
 	let file = fs::File::open(path).map_err(|e| Error::Io(path.to_owned(), e))?;
 	let source = OggStreamReader::new(file)?;
@@ -231,13 +490,17 @@ fn read_vorbis(path: &Path) -> Result<SongTags, Error> {
 		title: None,
 		duration: None,
 		disc_number: None,
+		disc_total: None,
 		track_number: None,
+		track_total: None,
 		year: None,
 		has_artwork: false,
+		artwork_reference: None,
 		lyricist: None,
 		composer: None,
 		genre: None,
 		label: None,
+		replay_gain: ReplayGain::default(),
 	};
 
 	for (key, value) in source.comment_hdr.comment_list {
@@ -247,14 +510,26 @@ fn read_vorbis(path: &Path) -> Result<SongTags, Error> {
 				"ALBUM" => tags.album = Some(value),
 				"ARTIST" => tags.artist = Some(value),
 				"ALBUMARTIST" => tags.album_artist = Some(value),
-				"TRACKNUMBER" => tags.track_number = value.parse::<u32>().ok(),
-				"DISCNUMBER" => tags.disc_number = value.parse::<u32>().ok(),
+				"TRACKNUMBER" => {
+					let (number, total) = parse_x_of_y(&value);
+					tags.track_number = number;
+					tags.track_total = total.or(tags.track_total);
+				},
+				"TRACKTOTAL" => tags.track_total = value.trim().parse::<u32>().ok(),
+				"TOTALTRACKS" => tags.track_total = value.trim().parse::<u32>().ok(),
+				"DISCNUMBER" => {
+					let (number, total) = parse_x_of_y(&value);
+					tags.disc_number = number;
+					tags.disc_total = total.or(tags.disc_total);
+				},
+				"DISCTOTAL" => tags.disc_total = value.trim().parse::<u32>().ok(),
+				"TOTALDISCS" => tags.disc_total = value.trim().parse::<u32>().ok(),
 				"DATE" => tags.year = value.parse::<i32>().ok(),
 				"LYRICIST" => tags.lyricist = Some(value),
 				"COMPOSER" => tags.composer = Some(value),
 				"GENRE" => tags.genre = Some(value),
 				"PUBLISHER" => tags.label = Some(value),
-				_ => (),
+				_ => tags.replay_gain.read(&key, &value),
 			}
 		}
 	}
@@ -262,6 +537,39 @@ fn read_vorbis(path: &Path) -> Result<SongTags, Error> {
 	Ok(tags)
 }
 
+// The granule position of the last Ogg page is the number of samples in the stream, including
+// the ones skipped at the start of playback
+fn decode_opus_duration(path: &Path) -> Result<u32, Error> {
+	let headers = opus_headers::parse_from_path(path)?;
+	let num_samples = read_last_granule_position(path)
+		.map_err(|e| Error::Io(path.to_owned(), e))?
+		.ok_or_else(|| Error::AudioPropertiesNotFound(path.to_owned()))?;
+	let num_samples = num_samples.saturating_sub(u64::from(headers.id.pre_skip));
+	Ok((num_samples / OPUS_SAMPLE_RATE) as u32)
+}
+
+fn read_last_granule_position(path: &Path) -> io::Result<Option<u64>> {
+	let mut file = fs::File::open(path)?;
+	let length = file.metadata()?.len();
+	file.seek(SeekFrom::Start(length.saturating_sub(MAX_OGG_PAGE_SIZE)))?;
+	let mut tail = Vec::new();
+	file.read_to_end(&mut tail)?;
+	let mut end = tail.len();
+	// Pages which do not complete any packet have a granule position of -1
+	while let Some(start) = tail[..end].windows(4).rposition(|w| w == b"OggS") {
+		if let Some(granule) = tail.get(start + 6..start + 14) {
+			let mut bytes = [0; 8];
+			bytes.copy_from_slice(granule);
+			let granule = i64::from_le_bytes(bytes);
+			if granule >= 0 {
+				return Ok(Some(granule as u64));
+			}
+		}
+		end = start;
+	}
+	Ok(None)
+}
+
 fn read_opus(path: &Path) -> Result<SongTags, Error> {
 	let headers = opus_headers::parse_from_path(path)?;
 
@@ -272,13 +580,17 @@ fn read_opus(path: &Path) -> Result<SongTags, Error> {
 		title: None,
 		duration: None,
 		disc_number: None,
+		disc_total: None,
 		track_number: None,
+		track_total: None,
 		year: None,
 		has_artwork: false,
+		artwork_reference: None,
 		lyricist: None,
 		composer: None,
 		genre: None,
 		label: None,
+		replay_gain: ReplayGain::default(),
 	};
 
 	for (key, value) in headers.comments.user_comments {
@@ -288,14 +600,26 @@ fn read_opus(path: &Path) -> Result<SongTags, Error> {
 				"ALBUM" => tags.album = Some(value),
 				"ARTIST" => tags.artist = Some(value),
 				"ALBUMARTIST" => tags.album_artist = Some(value),
-				"TRACKNUMBER" => tags.track_number = value.parse::<u32>().ok(),
-				"DISCNUMBER" => tags.disc_number = value.parse::<u32>().ok(),
+				"TRACKNUMBER" => {
+					let (number, total) = parse_x_of_y(&value);
+					tags.track_number = number;
+					tags.track_total = total.or(tags.track_total);
+				},
+				"TRACKTOTAL" => tags.track_total = value.trim().parse::<u32>().ok(),
+				"TOTALTRACKS" => tags.track_total = value.trim().parse::<u32>().ok(),
+				"DISCNUMBER" => {
+					let (number, total) = parse_x_of_y(&value);
+					tags.disc_number = number;
+					tags.disc_total = total.or(tags.disc_total);
+				},
+				"DISCTOTAL" => tags.disc_total = value.trim().parse::<u32>().ok(),
+				"TOTALDISCS" => tags.disc_total = value.trim().parse::<u32>().ok(),
 				"DATE" => tags.year = value.parse::<i32>().ok(),
 				"LYRICIST" => tags.lyricist = Some(value),
 				"COMPOSER" => tags.composer = Some(value),
 				"GENRE" => tags.genre = Some(value),
 				"PUBLISHER" => tags.label = Some(value),
-				_ => (),
+				_ => tags.replay_gain.read(&key, &value),
 			}
 		}
 	}
@@ -308,9 +632,24 @@ fn read_flac(path: &Path) -> Result<SongTags, Error> {
 	let vorbis = tag
 		.vorbis_comments()
 		.ok_or(Error::VorbisCommentNotFoundInFlacFile)?;
-	let disc_number = vorbis
+	let (disc_number, disc_total) = vorbis
 		.get("DISCNUMBER")
-		.and_then(|d| d[0].parse::<u32>().ok());
+		.map(|d| parse_x_of_y(&d[0]))
+		.unwrap_or_default();
+	let disc_total = disc_total.or_else(|| {
+		["DISCTOTAL", "TOTALDISCS"]
+			.iter()
+			.find_map(|k| vorbis.get(k).and_then(|d| d[0].trim().parse::<u32>().ok()))
+	});
+	let (track_number, track_total) = vorbis
+		.get("TRACKNUMBER")
+		.map(|d| parse_x_of_y(&d[0]))
+		.unwrap_or_default();
+	let track_total = track_total.or_else(|| {
+		["TRACKTOTAL", "TOTALTRACKS"]
+			.iter()
+			.find_map(|k| vorbis.get(k).and_then(|d| d[0].trim().parse::<u32>().ok()))
+	});
 	let year = vorbis.get("DATE").and_then(|d| d[0].parse::<i32>().ok());
 	let mut streaminfo = tag.get_blocks(metaflac::BlockType::StreamInfo);
 	let duration = match streaminfo.next() {
@@ -318,6 +657,12 @@ fn read_flac(path: &Path) -> Result<SongTags, Error> {
 		_ => None,
 	};
 	let has_artwork = tag.pictures().count() > 0;
+	let mut replay_gain = ReplayGain::default();
+	for (key, values) in &vorbis.comments {
+		if let Some(value) = values.first() {
+			replay_gain.read(key, value);
+		}
+	}
 
 	Ok(SongTags {
 		artist: vorbis.artist().map(|v| v[0].clone()),
@@ -326,19 +671,29 @@ fn read_flac(path: &Path) -> Result<SongTags, Error> {
 		title: vorbis.title().map(|v| v[0].clone()),
 		duration,
 		disc_number,
-		track_number: vorbis.track(),
+		disc_total,
+		track_number,
+		track_total,
 		year,
 		has_artwork,
+		artwork_reference: None,
 		lyricist: vorbis.get("LYRICIST").map(|v| v[0].clone()),
 		composer: vorbis.get("COMPOSER").map(|v| v[0].clone()),
 		genre: vorbis.get("GENRE").map(|v| v[0].clone()),
 		label: vorbis.get("PUBLISHER").map(|v| v[0].clone()),
+		replay_gain,
 	})
 }
 
 fn read_mp4(path: &Path) -> Result<SongTags, Error> {
 	let mut tag = mp4ameta::Tag::read_from_path(path)?;
 	let label_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "Label");
+	let mut replay_gain = ReplayGain::default();
+	for (ident, data) in tag.data() {
+		if let (mp4ameta::DataIdent::Freeform { name, .. }, Some(value)) = (ident, data.string()) {
+			replay_gain.read(name, value);
+		}
+	}
 
 	Ok(SongTags {
 		artist: tag.take_artist(),
@@ -347,13 +702,17 @@ fn read_mp4(path: &Path) -> Result<SongTags, Error> {
 		title: tag.take_title(),
 		duration: tag.duration().map(|v| v.as_secs() as u32),
 		disc_number: tag.disc_number().map(|d| d as u32),
+		disc_total: tag.total_discs().map(|d| d as u32),
 		track_number: tag.track_number().map(|d| d as u32),
+		track_total: tag.total_tracks().map(|d| d as u32),
 		year: tag.year().and_then(|v| v.parse::<i32>().ok()),
 		has_artwork: tag.artwork().is_some(),
+		artwork_reference: None,
 		lyricist: tag.take_lyricist(),
 		composer: tag.take_composer(),
 		genre: tag.take_genre(),
 		label: tag.take_strings_of(&label_ident).next(),
+		replay_gain,
 	})
 }
 
@@ -361,7 +720,9 @@ fn read_mp4(path: &Path) -> Result<SongTags, Error> {
 fn reads_file_metadata() {
 	let sample_tags = SongTags {
 		disc_number: Some(3),
+		disc_total: Some(4),
 		track_number: Some(1),
+		track_total: Some(2),
 		title: Some("TEST TITLE".into()),
 		artist: Some("TEST ARTIST".into()),
 		album_artist: Some("TEST ALBUM ARTIST".into()),
@@ -369,10 +730,12 @@ fn reads_file_metadata() {
 		duration: None,
 		year: Some(2016),
 		has_artwork: false,
+		artwork_reference: None,
 		lyricist: Some("TEST LYRICIST".into()),
 		composer: Some("TEST COMPOSER".into()),
 		genre: Some("TEST GENRE".into()),
 		label: Some("TEST LABEL".into()),
+		replay_gain: ReplayGain::default(),
 	};
 	let flac_sample_tag = SongTags {
 		duration: Some(0),
@@ -382,69 +745,199 @@ fn reads_file_metadata() {
 		duration: Some(0),
 		..sample_tags.clone()
 	};
+	// These samples are not tagged with track and disc totals
 	let m4a_sample_tag = SongTags {
 		duration: Some(0),
+		disc_total: None,
+		track_total: None,
+		..sample_tags.clone()
+	};
+	let opus_sample_tag = SongTags {
+		disc_total: None,
+		track_total: None,
 		..sample_tags.clone()
 	};
 	assert_eq!(
-		read(Path::new("test-data/formats/sample.aif")).unwrap(),
+		read(Path::new("test-data/formats/sample.aif"))
+			.unwrap()
+			.unwrap(),
 		sample_tags
 	);
 	assert_eq!(
-		read(Path::new("test-data/formats/sample.mp3")).unwrap(),
+		read(Path::new("test-data/formats/sample.mp3"))
+			.unwrap()
+			.unwrap(),
 		mp3_sample_tag
 	);
 	assert_eq!(
-		read(Path::new("test-data/formats/sample.ogg")).unwrap(),
+		read(Path::new("test-data/formats/sample.ogg"))
+			.unwrap()
+			.unwrap(),
 		sample_tags
 	);
 	assert_eq!(
-		read(Path::new("test-data/formats/sample.flac")).unwrap(),
+		read(Path::new("test-data/formats/sample.flac"))
+			.unwrap()
+			.unwrap(),
 		flac_sample_tag
 	);
 	assert_eq!(
-		read(Path::new("test-data/formats/sample.m4a")).unwrap(),
+		read(Path::new("test-data/formats/sample.m4a"))
+			.unwrap()
+			.unwrap(),
 		m4a_sample_tag
 	);
 	assert_eq!(
-		read(Path::new("test-data/formats/sample.opus")).unwrap(),
-		sample_tags
+		read(Path::new("test-data/formats/sample.opus"))
+			.unwrap()
+			.unwrap(),
+		opus_sample_tag
 	);
 	assert_eq!(
-		read(Path::new("test-data/formats/sample.ape")).unwrap(),
+		read(Path::new("test-data/formats/sample.ape"))
+			.unwrap()
+			.unwrap(),
 		sample_tags
 	);
 	assert_eq!(
-		read(Path::new("test-data/formats/sample.wav")).unwrap(),
+		read(Path::new("test-data/formats/sample.wav"))
+			.unwrap()
+			.unwrap(),
 		sample_tags
 	);
 }
 
+#[test]
+fn reads_raw_tags() {
+	let raw_tags = |name: &str| -> Vec<(String, String)> {
+		read_raw_tags(Path::new("test-data/formats").join(name).as_path())
+			.unwrap()
+			.unwrap()
+	};
+	let has_tag = |tags: &[(String, String)], key: &str, value: &str| {
+		tags.iter().any(|(k, v)| k == key && v == value)
+	};
+	assert!(has_tag(&raw_tags("sample.mp3"), "TIT2", "TEST TITLE"));
+	assert!(has_tag(&raw_tags("sample.flac"), "TITLE", "TEST TITLE"));
+	assert!(has_tag(&raw_tags("sample.ogg"), "TITLE", "TEST TITLE"));
+	assert!(has_tag(&raw_tags("sample.ape"), "Title", "TEST TITLE"));
+	assert!(!raw_tags("sample.m4a").is_empty());
+	assert_eq!(
+		read_raw_tags(Path::new(
+			"test-data/small-collection/Khemmis/Hunted/Folder.jpg"
+		))
+		.unwrap(),
+		None
+	);
+}
+
 #[test]
 fn reads_embedded_artwork() {
 	assert!(
 		read(Path::new("test-data/artwork/sample.aif"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
 	assert!(
 		read(Path::new("test-data/artwork/sample.mp3"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
 	assert!(
 		read(Path::new("test-data/artwork/sample.flac"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
 	assert!(
 		read(Path::new("test-data/artwork/sample.m4a"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
 	assert!(
 		read(Path::new("test-data/artwork/sample.wav"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
 }
+
+#[test]
+fn decodes_missing_durations() {
+	assert_eq!(
+		decode_duration(Path::new("test-data/formats/sample.aif")),
+		Some(2)
+	);
+	assert_eq!(
+		decode_duration(Path::new("test-data/formats/sample.mp3")),
+		Some(0)
+	);
+	assert_eq!(
+		decode_duration(Path::new("test-data/formats/sample.ogg")),
+		Some(0)
+	);
+	assert_eq!(
+		decode_duration(Path::new("test-data/formats/sample.opus")),
+		Some(30)
+	);
+	assert_eq!(
+		decode_duration(Path::new("test-data/formats/sample.flac")),
+		None
+	);
+}
+
+#[test]
+fn parses_replay_gain() {
+	assert_eq!(parse_replay_gain("-6.5 dB"), Some(-6.5));
+	assert_eq!(parse_replay_gain("+3.20 DB"), Some(3.2));
+	assert_eq!(parse_replay_gain("-6.5dB"), Some(-6.5));
+	assert_eq!(parse_replay_gain(" 0.988525 "), Some(0.988525));
+	assert_eq!(parse_replay_gain("loud"), None);
+	assert_eq!(parse_replay_gain("dB"), None);
+	assert_eq!(parse_replay_gain("NaN"), None);
+	assert_eq!(parse_replay_gain(""), None);
+}
+
+#[test]
+fn reads_replay_gain_tags() {
+	let song_path = crate::test::prepare_test_directory(crate::test_name!()).join("song.mp3");
+	std::fs::copy("test-data/formats/sample.mp3", &song_path).unwrap();
+	let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+	tag.add_frame(id3::frame::ExtendedText {
+		description: "REPLAYGAIN_TRACK_GAIN".to_owned(),
+		value: "-6.5 dB".to_owned(),
+	});
+	tag.add_frame(id3::frame::ExtendedText {
+		description: "replaygain_album_peak".to_owned(),
+		value: "0.95".to_owned(),
+	});
+	tag.add_frame(id3::frame::ExtendedText {
+		description: "REPLAYGAIN_ALBUM_GAIN".to_owned(),
+		value: "invalid".to_owned(),
+	});
+	tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+
+	assert_eq!(
+		read(&song_path).unwrap().unwrap().replay_gain,
+		ReplayGain {
+			track_gain: Some(-6.5),
+			track_peak: None,
+			album_gain: None,
+			album_peak: Some(0.95),
+		}
+	);
+}
+
+#[test]
+fn parses_number_and_total() {
+	assert_eq!(parse_x_of_y("3"), (Some(3), None));
+	assert_eq!(parse_x_of_y("3/12"), (Some(3), Some(12)));
+	assert_eq!(parse_x_of_y(" 03 / 12 "), (Some(3), Some(12)));
+	assert_eq!(parse_x_of_y("3/"), (Some(3), None));
+	assert_eq!(parse_x_of_y("3/twelve"), (Some(3), None));
+	assert_eq!(parse_x_of_y("three"), (None, None));
+	assert_eq!(parse_x_of_y(""), (None, None));
+}
@@ -0,0 +1,134 @@
+use regex::Regex;
+use std::path::Path;
+
+use crate::app::index::metadata::SongTags;
+
+const PLACEHOLDERS: [&str; 8] = [
+	"artist",
+	"album_artist",
+	"album",
+	"title",
+	"track",
+	"disc",
+	"year",
+	"genre",
+];
+
+// Infers song tags from file paths, using patterns like `{artist}/{album}/{track} - {title}`.
+// Patterns are matched against the end of the path, without the file extension.
+#[derive(Clone, Debug)]
+pub struct PathPattern {
+	regex: Regex,
+}
+
+impl PathPattern {
+	pub fn new(pattern: &str) -> Option<Self> {
+		let mut expression = String::from("(?:^|/)");
+		let mut remaining = pattern;
+		while let Some(start) = remaining.find('{') {
+			let (literal, rest) = remaining.split_at(start);
+			let end = rest.find('}')?;
+			let name = &rest[1..end];
+			if !PLACEHOLDERS.contains(&name) || expression.contains(&format!("(?P<{}>", name)) {
+				return None;
+			}
+			expression.push_str(&regex::escape(literal));
+			match name {
+				"track" | "disc" | "year" => expression.push_str(&format!(r"(?P<{}>\d+)", name)),
+				_ => expression.push_str(&format!("(?P<{}>[^/]+?)", name)),
+			}
+			remaining = &rest[end + 1..];
+		}
+		if remaining.contains('}') {
+			return None;
+		}
+		expression.push_str(&regex::escape(remaining));
+		expression.push('$');
+		Regex::new(&expression).ok().map(|regex| Self { regex })
+	}
+
+	// Fills in tags which are missing from `tags`, leaving existing values untouched
+	pub fn apply(&self, path: &Path, tags: &mut SongTags) {
+		let path = path
+			.with_extension("")
+			.components()
+			.map(|c| c.as_os_str().to_string_lossy().into_owned())
+			.collect::<Vec<_>>()
+			.join("/");
+		let Some(captures) = self.regex.captures(&path) else {
+			return;
+		};
+		let text = |name: &str| captures.name(name).map(|m| m.as_str().trim().to_owned());
+		let number = |name: &str| -> Option<u32> {
+			captures.name(name).and_then(|m| m.as_str().parse().ok())
+		};
+
+		tags.artist = tags.artist.take().or_else(|| text("artist"));
+		tags.album_artist = tags.album_artist.take().or_else(|| text("album_artist"));
+		tags.album = tags.album.take().or_else(|| text("album"));
+		tags.title = tags.title.take().or_else(|| text("title"));
+		tags.genre = tags.genre.take().or_else(|| text("genre"));
+		tags.track_number = tags.track_number.or_else(|| number("track"));
+		tags.disc_number = tags.disc_number.or_else(|| number("disc"));
+		tags.year = tags.year.or_else(|| number("year").map(|y| y as i32));
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	#[test]
+	fn rejects_invalid_patterns() {
+		assert!(PathPattern::new("{artist}/{albm}").is_none());
+		assert!(PathPattern::new("{artist}/{album").is_none());
+		assert!(PathPattern::new("{artist}/album}").is_none());
+		assert!(PathPattern::new("{title}/{title}").is_none());
+	}
+
+	#[test]
+	fn fills_missing_tags() {
+		let pattern = PathPattern::new("{artist}/{album}/{track} - {title}").unwrap();
+		let path: PathBuf = ["Music", "Khemmis", "Hunted", "05 - Hunted.mp3"]
+			.iter()
+			.collect();
+		let mut tags = SongTags::default();
+		pattern.apply(&path, &mut tags);
+		assert_eq!(
+			tags,
+			SongTags {
+				artist: Some("Khemmis".to_owned()),
+				album: Some("Hunted".to_owned()),
+				track_number: Some(5),
+				title: Some("Hunted".to_owned()),
+				..Default::default()
+			}
+		);
+	}
+
+	#[test]
+	fn does_not_override_existing_tags() {
+		let pattern = PathPattern::new("{artist}/{album}/{track} - {title}").unwrap();
+		let path: PathBuf = ["Khemmis", "Hunted", "05 - Hunted.mp3"].iter().collect();
+		let mut tags = SongTags {
+			title: Some("Three Gates".to_owned()),
+			track_number: Some(3),
+			..Default::default()
+		};
+		pattern.apply(&path, &mut tags);
+		assert_eq!(tags.artist, Some("Khemmis".to_owned()));
+		assert_eq!(tags.title, Some("Three Gates".to_owned()));
+		assert_eq!(tags.track_number, Some(3));
+	}
+
+	#[test]
+	fn ignores_paths_not_matching_pattern() {
+		let pattern = PathPattern::new("{artist}/{album}/{track} - {title}").unwrap();
+		let path: PathBuf = ["Khemmis", "Hunted", "Hunted.mp3"].iter().collect();
+		let mut tags = SongTags::default();
+		pattern.apply(&path, &mut tags);
+		assert_eq!(tags, SongTags::default());
+	}
+}
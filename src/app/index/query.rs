@@ -1,7 +1,14 @@
 use diesel::dsl::sql;
 use diesel::prelude::*;
 use diesel::sql_types;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
 
 use super::*;
 use crate::db::{self, directories, songs};
@@ -12,8 +19,12 @@ pub enum QueryError {
 	Database(#[from] diesel::result::Error),
 	#[error(transparent)]
 	DatabaseConnection(#[from] db::Error),
+	#[error("Album was not found: `{0}`")]
+	AlbumNotFound(PathBuf),
 	#[error("Song was not found: `{0}`")]
 	SongNotFound(PathBuf),
+	#[error("Could not read tags from `{0}`: `{1}`")]
+	TagsUnreadable(PathBuf, metadata::Error),
 	#[error(transparent)]
 	Vfs(#[from] vfs::Error),
 }
@@ -23,6 +34,99 @@ sql_function!(
 	fn random() -> Integer;
 );
 
+sql_function!(
+	fn trim(x: sql_types::Nullable<sql_types::Text>) -> sql_types::Nullable<sql_types::Text>;
+);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SongSort {
+	// Albums by year, then songs by disc and track number
+	#[default]
+	Album,
+	Title,
+	// Most recent first
+	Year,
+	// Order of the songs in the collection, ie. by path
+	Path,
+	// Disc number, then track number
+	Track,
+	// Songs from the most recently added directories first
+	DateAdded,
+}
+
+// Song fields whose distinct values can be listed with `Index::get_field_values`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SongField {
+	Artist,
+	Album,
+	Genre,
+	Label,
+	Year,
+}
+
+// Genres count each value of multi-genre tags
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldValue {
+	pub value: String,
+	pub num_songs: usize,
+}
+
+// Distinct values across the songs of an album, sorted alphabetically
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlbumCredits {
+	pub performers: Vec<String>,
+	pub composers: Vec<String>,
+	pub lyricists: Vec<String>,
+	pub labels: Vec<String>,
+}
+
+// Everything needed to display an album in a single query. Similar albums are the other
+// albums by the same artist.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlbumPage {
+	pub album: Directory,
+	pub songs: Vec<Song>,
+	pub credits: AlbumCredits,
+	pub similar_albums: Vec<Directory>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Label {
+	pub albums: Vec<Directory>,
+	pub artists: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Year {
+	pub year: i32,
+	pub num_albums: usize,
+}
+
+// Decades are identified by their first year, eg. 1990 for 1990 to 1999
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decade {
+	pub decade: i32,
+	pub num_albums: usize,
+}
+
+// Artists whose name starts with something other than a letter are grouped under `#`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtistBucket {
+	pub letter: char,
+	pub num_artists: usize,
+}
+
+// Artists exclude `Various Artists`, genres count each value of multi-genre tags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Statistics {
+	pub num_songs: usize,
+	pub num_albums: usize,
+	pub num_artists: usize,
+	pub num_genres: usize,
+	// In seconds
+	pub total_duration: i64,
+}
+
 impl Index {
 	pub fn browse<P>(&self, virtual_path: P) -> Result<Vec<CollectionFile>, QueryError>
 	where
@@ -67,6 +171,37 @@ impl Index {
 	}
 
 	pub fn flatten<P>(&self, virtual_path: P) -> Result<Vec<Song>, QueryError>
+	where
+		P: AsRef<Path>,
+	{
+		let (songs, _) = self.flatten_page(virtual_path, None, i64::MAX)?;
+		Ok(songs)
+	}
+
+	pub fn flatten_sorted<P>(
+		&self,
+		virtual_path: P,
+		sort: SongSort,
+	) -> Result<Vec<Song>, QueryError>
+	where
+		P: AsRef<Path>,
+	{
+		let mut songs = self.flatten(virtual_path)?;
+		if sort != SongSort::Path {
+			self.sort_songs(&mut songs, sort)?;
+		}
+		Ok(songs)
+	}
+
+	// Reads up to `count` of the songs returned by `flatten`, in the same order, starting after the
+	// song whose real path is `after`. Alongside the songs, returns the value of `after` to read
+	// the next page with, or `None` when there are no songs left.
+	pub fn flatten_page<P>(
+		&self,
+		virtual_path: P,
+		after: Option<String>,
+		count: i64,
+	) -> Result<(Vec<Song>, Option<String>), QueryError>
 	where
 		P: AsRef<Path>,
 	{
@@ -74,23 +209,27 @@ impl Index {
 		let vfs = self.vfs_manager.get_vfs()?;
 		let mut connection = self.db.connect()?;
 
-		let real_songs: Vec<Song> = if virtual_path.as_ref().parent().is_some() {
+		let mut query = songs.order(path).limit(count).into_boxed();
+		if virtual_path.as_ref().parent().is_some() {
 			let real_path = vfs.virtual_to_real(virtual_path)?;
 			let song_path_filter = {
 				let mut path_buf = real_path;
 				path_buf.push("%");
 				path_buf.as_path().to_string_lossy().into_owned()
 			};
-			songs
-				.filter(path.like(&song_path_filter))
-				.order(path)
-				.load(&mut connection)?
-		} else {
-			songs.order(path).load(&mut connection)?
-		};
+			query = query.filter(path.like(song_path_filter));
+		}
+		if let Some(after) = after {
+			query = query.filter(path.gt(after));
+		}
+		let real_songs: Vec<Song> = query.load(&mut connection)?;
 
+		let next = match real_songs.last() {
+			Some(last) if real_songs.len() as i64 == count => Some(last.path.clone()),
+			_ => None,
+		};
 		let virtual_songs = real_songs.into_iter().filter_map(|s| s.virtualize(&vfs));
-		Ok(virtual_songs.collect::<Vec<_>>())
+		Ok((virtual_songs.collect::<Vec<_>>(), next))
 	}
 
 	pub fn get_random_albums(&self, count: i64) -> Result<Vec<Directory>, QueryError> {
@@ -108,12 +247,326 @@ impl Index {
 		Ok(virtual_directories.collect::<Vec<_>>())
 	}
 
-	pub fn get_recent_albums(&self, count: i64) -> Result<Vec<Directory>, QueryError> {
+	// Compilation artists are excluded from artist listings
+	fn get_compilation_artist_names(&self) -> Vec<String> {
+		self.settings_manager
+			.get_index_compilation_artist_names()
+			.unwrap_or_else(|_| vec![VARIOUS_ARTISTS.to_owned()])
+	}
+
+	// Artists credited on songs, either as performing artist or album artist.
+	// Passing the same seed returns the same artists, as long as the collection is unchanged.
+	pub fn get_random_artists(
+		&self,
+		count: usize,
+		seed: Option<u64>,
+	) -> Result<Vec<String>, QueryError> {
+		let mut connection = self.db.connect()?;
+		let artists: Vec<Option<String>> = songs::table
+			.select(songs::artist)
+			.distinct()
+			.load(&mut connection)?;
+		let album_artists: Vec<Option<String>> = songs::table
+			.select(songs::album_artist)
+			.distinct()
+			.load(&mut connection)?;
+		let compilation_artists = self.get_compilation_artist_names();
+		let candidates = artists
+			.into_iter()
+			.chain(album_artists)
+			.flatten()
+			.filter(|a| !is_compilation_artist(&compilation_artists, a));
+		Ok(pick_random(candidates, count, seed))
+	}
+
+	// Distinct artists and album artists, sorted alphabetically regardless of case
+	pub fn get_artists(&self) -> Result<Vec<String>, QueryError> {
+		let mut connection = self.db.connect()?;
+		let artists: Vec<Option<String>> = songs::table
+			.select(songs::artist)
+			.distinct()
+			.load(&mut connection)?;
+		let album_artists: Vec<Option<String>> = songs::table
+			.select(songs::album_artist)
+			.distinct()
+			.load(&mut connection)?;
+		let compilation_artists = self.get_compilation_artist_names();
+		let artists: BTreeSet<String> = artists
+			.into_iter()
+			.chain(album_artists)
+			.flatten()
+			.filter(|a| !is_compilation_artist(&compilation_artists, a))
+			.collect();
+		let mut artists: Vec<String> = artists.into_iter().collect();
+		artists.sort_by_cached_key(|a| (artist_collation_key(a), a.clone()));
+		Ok(artists)
+	}
+
+	// Buckets follow the order of `get_artists`, so their counts can be used as offsets into it
+	pub fn get_artist_buckets(&self) -> Result<Vec<ArtistBucket>, QueryError> {
+		let mut buckets: Vec<ArtistBucket> = Vec::new();
+		for artist in self.get_artists()? {
+			let letter = artist_bucket(&artist);
+			match buckets.last_mut() {
+				Some(bucket) if bucket.letter == letter => bucket.num_artists += 1,
+				_ => buckets.push(ArtistBucket {
+					letter,
+					num_artists: 1,
+				}),
+			}
+		}
+		Ok(buckets)
+	}
+
+	// Text values are sorted like artists, years are sorted chronologically. Artists are the same
+	// as in `get_artists`, songs count towards both their artist and album artist.
+	pub fn get_field_values(&self, field: SongField) -> Result<Vec<FieldValue>, QueryError> {
+		use diesel::dsl::count_star;
+		let mut connection = self.db.connect()?;
+
+		let values: Vec<(Option<String>, i64)> = match field {
+			SongField::Artist => {
+				let credits: Vec<(Option<String>, Option<String>, i64)> = songs::table
+					.group_by((songs::artist, songs::album_artist))
+					.select((songs::artist, songs::album_artist, count_star()))
+					.load(&mut connection)?;
+				let compilation_artists = self.get_compilation_artist_names();
+				credits
+					.into_iter()
+					.flat_map(|(artist, album_artist, num_songs)| {
+						let album_artist = album_artist.filter(|a| Some(a) != artist.as_ref());
+						[(artist, num_songs), (album_artist, num_songs)]
+					})
+					.filter(|(a, _)| {
+						a.as_ref()
+							.is_some_and(|a| !is_compilation_artist(&compilation_artists, a))
+					})
+					.collect()
+			}
+			SongField::Album => songs::table
+				.group_by(songs::album)
+				.select((songs::album, count_star()))
+				.load(&mut connection)?,
+			SongField::Genre => songs::table
+				.group_by(songs::genre)
+				.select((songs::genre, count_star()))
+				.load(&mut connection)?,
+			SongField::Label => songs::table
+				.group_by(songs::label)
+				.select((songs::label, count_star()))
+				.load(&mut connection)?,
+			SongField::Year => {
+				let years: Vec<(Option<i32>, i64)> = songs::table
+					.filter(songs::year.is_not_null())
+					.group_by(songs::year)
+					.select((songs::year, count_star()))
+					.order(songs::year)
+					.load(&mut connection)?;
+				return Ok(years
+					.into_iter()
+					.filter_map(|(y, n)| {
+						y.map(|y| FieldValue {
+							value: y.to_string(),
+							num_songs: n as usize,
+						})
+					})
+					.collect());
+			}
+		};
+
+		let mut counts: HashMap<String, usize> = HashMap::new();
+		for (value, num_songs) in values {
+			let Some(value) = value else {
+				continue;
+			};
+			let parts: Vec<&str> = match field {
+				SongField::Genre => value.split('\0').collect(),
+				_ => vec![value.as_str()],
+			};
+			for part in parts.into_iter().map(str::trim).filter(|p| !p.is_empty()) {
+				*counts.entry(part.to_owned()).or_default() += num_songs as usize;
+			}
+		}
+
+		let mut values: Vec<FieldValue> = counts
+			.into_iter()
+			.map(|(value, num_songs)| FieldValue { value, num_songs })
+			.collect();
+		values.sort_by_cached_key(|v| (artist_collation_key(&v.value), v.value.clone()));
+		Ok(values)
+	}
+
+	// Passing the same seed returns the same genres, as long as the collection is unchanged
+	pub fn get_random_genres(
+		&self,
+		count: usize,
+		seed: Option<u64>,
+	) -> Result<Vec<String>, QueryError> {
+		let mut connection = self.db.connect()?;
+		let genres: Vec<Option<String>> = songs::table
+			.select(songs::genre)
+			.distinct()
+			.load(&mut connection)?;
+		// Songs with several genres store them separated by null characters
+		let genres = genres
+			.iter()
+			.flatten()
+			.flat_map(|g| g.split('\0'))
+			.map(str::to_owned);
+		Ok(pick_random(genres, count, seed))
+	}
+
+	pub fn get_statistics(&self) -> Result<Statistics, QueryError> {
+		let mut connection = self.db.connect()?;
+		let num_songs: i64 = songs::table.count().get_result(&mut connection)?;
+		let num_albums: i64 = directories::table
+			.filter(directories::album.is_not_null())
+			.count()
+			.get_result(&mut connection)?;
+		let total_duration: Option<i64> = songs::table
+			.select(diesel::dsl::sum(songs::duration))
+			.first(&mut connection)?;
+
+		let artists: Vec<(Option<String>, Option<String>)> = songs::table
+			.select((songs::artist, songs::album_artist))
+			.distinct()
+			.load(&mut connection)?;
+		let compilation_artists = self.get_compilation_artist_names();
+		let artists: HashSet<String> = artists
+			.into_iter()
+			.flat_map(|(a, b)| [a, b])
+			.flatten()
+			.filter(|a| !is_compilation_artist(&compilation_artists, a))
+			.collect();
+
+		let genres: Vec<Option<String>> = songs::table
+			.select(songs::genre)
+			.distinct()
+			.load(&mut connection)?;
+		let genres: HashSet<&str> = genres
+			.iter()
+			.flatten()
+			.flat_map(|g| g.split('\0'))
+			.collect();
+
+		Ok(Statistics {
+			num_songs: num_songs as usize,
+			num_albums: num_albums as usize,
+			num_artists: artists.len(),
+			num_genres: genres.len(),
+			total_duration: total_duration.unwrap_or_default(),
+		})
+	}
+
+	pub fn get_years(&self) -> Result<Vec<Year>, QueryError> {
+		use self::directories::dsl::*;
+		let mut connection = self.db.connect()?;
+		let years: Vec<(Option<i32>, i64)> = directories
+			.filter(album.is_not_null())
+			.filter(year.is_not_null())
+			.group_by(year)
+			.select((year, diesel::dsl::count_star()))
+			.order(year)
+			.load(&mut connection)?;
+		Ok(years
+			.into_iter()
+			.filter_map(|(y, n)| {
+				y.map(|y| Year {
+					year: y,
+					num_albums: n as usize,
+				})
+			})
+			.collect())
+	}
+
+	pub fn get_decades(&self) -> Result<Vec<Decade>, QueryError> {
+		let mut decades: Vec<Decade> = Vec::new();
+		for y in self.get_years()? {
+			let decade = y.year.div_euclid(10) * 10;
+			match decades.last_mut() {
+				Some(d) if d.decade == decade => d.num_albums += y.num_albums,
+				_ => decades.push(Decade {
+					decade,
+					num_albums: y.num_albums,
+				}),
+			}
+		}
+		Ok(decades)
+	}
+
+	pub fn get_year_albums(&self, album_year: i32) -> Result<Vec<Directory>, QueryError> {
 		use self::directories::dsl::*;
 		let vfs = self.vfs_manager.get_vfs()?;
 		let mut connection = self.db.connect()?;
 		let real_directories: Vec<Directory> = directories
 			.filter(album.is_not_null())
+			.filter(year.eq(album_year))
+			.order(sql::<sql_types::Bool>("album COLLATE NOCASE ASC"))
+			.load(&mut connection)?;
+		let virtual_directories = real_directories
+			.into_iter()
+			.filter_map(|d| d.virtualize(&vfs));
+		Ok(virtual_directories.collect())
+	}
+
+	pub fn get_labels(&self) -> Result<Vec<String>, QueryError> {
+		let mut connection = self.db.connect()?;
+		let labels: Vec<Option<String>> = songs::table
+			.select(songs::label)
+			.distinct()
+			.load(&mut connection)?;
+		let labels: BTreeSet<String> = labels
+			.into_iter()
+			.flatten()
+			.map(|l| l.trim().to_owned())
+			.filter(|l| !l.is_empty())
+			.collect();
+		Ok(labels.into_iter().collect())
+	}
+
+	// Albums and artists with at least one song released on a label. Labels are compared
+	// without surrounding whitespace, like they are listed by `get_labels`.
+	pub fn get_label(&self, name: &str) -> Result<Label, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let label_songs = songs::table.filter(trim(songs::label).eq(name.trim()));
+		let real_directories: Vec<Directory> = directories::table
+			.filter(directories::album.is_not_null())
+			.filter(directories::path.eq_any(label_songs.select(songs::parent)))
+			.order((directories::year, directories::album))
+			.load(&mut connection)?;
+		let artists: Vec<Option<String>> = label_songs
+			.select(songs::artist)
+			.distinct()
+			.load(&mut connection)?;
+		let artists: BTreeSet<String> = artists.into_iter().flatten().collect();
+
+		Ok(Label {
+			albums: real_directories
+				.into_iter()
+				.filter_map(|d| d.virtualize(&vfs))
+				.collect(),
+			artists: artists.into_iter().collect(),
+		})
+	}
+
+	// Singles are album directories containing only one song
+	pub fn get_recent_albums(
+		&self,
+		count: i64,
+		exclude_singles: bool,
+	) -> Result<Vec<Directory>, QueryError> {
+		use self::directories::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let mut query = directories.filter(album.is_not_null()).into_boxed();
+		if exclude_singles {
+			query = query.filter(sql::<sql_types::Bool>(
+				"(SELECT COUNT(*) FROM songs WHERE songs.parent = directories.path) > 1",
+			));
+		}
+		let real_directories: Vec<Directory> = query
 			.order(date_added.desc())
 			.limit(count)
 			.load(&mut connection)?;
@@ -166,17 +619,249 @@ impl Index {
 		Ok(output)
 	}
 
+	// Real paths of all images (or audio files with embedded artwork) used as artwork
+	pub fn get_artwork_paths(&self) -> Result<Vec<PathBuf>, QueryError> {
+		let mut connection = self.db.connect()?;
+		let mut artworks: Vec<String> = songs::table
+			.select(songs::artwork)
+			.filter(songs::artwork.is_not_null())
+			.distinct()
+			.load::<Option<String>>(&mut connection)?
+			.into_iter()
+			.flatten()
+			.collect();
+		artworks.extend(
+			directories::table
+				.select(directories::artwork)
+				.filter(directories::artwork.is_not_null())
+				.distinct()
+				.load::<Option<String>>(&mut connection)?
+				.into_iter()
+				.flatten(),
+		);
+		artworks.sort();
+		artworks.dedup();
+		Ok(artworks.into_iter().map(PathBuf::from).collect())
+	}
+
+	// Finds the album stored in a directory. When the directory itself is not an album (eg. it
+	// only contains disc sub-directories), the album shared by all its descendants is returned.
+	pub fn get_album_at(&self, virtual_path: &Path) -> Result<Directory, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let real_path = vfs.virtual_to_real(virtual_path)?;
+		let real_path_string = real_path.as_path().to_string_lossy().into_owned();
+		let descendants_filter = {
+			let mut path_buf = real_path.clone();
+			path_buf.push("%");
+			path_buf.as_path().to_string_lossy().into_owned()
+		};
+
+		use self::directories::dsl::*;
+		let candidates: Vec<Directory> = directories
+			.filter(album.is_not_null())
+			.filter(
+				path.eq(&real_path_string)
+					.or(path.like(&descendants_filter)),
+			)
+			.order(path)
+			.load(&mut connection)?;
+
+		let album_directory = match candidates.iter().position(|d| d.path == real_path_string) {
+			Some(index) => candidates.into_iter().nth(index),
+			None => {
+				let is_single_album = candidates
+					.windows(2)
+					.all(|w| w[0].album == w[1].album && w[0].artist == w[1].artist);
+				match is_single_album {
+					true => candidates.into_iter().next(),
+					false => None,
+				}
+			}
+		};
+
+		album_directory
+			.and_then(|d| d.virtualize(&vfs))
+			.ok_or(QueryError::AlbumNotFound(real_path))
+	}
+
+	pub fn get_album_credits(&self, virtual_path: &Path) -> Result<AlbumCredits, QueryError> {
+		let album = self.get_album_at(virtual_path)?;
+		let songs = self.flatten(Path::new(&album.path))?;
+		Ok(collect_credits(&songs))
+	}
+
+	pub fn get_album_page(&self, virtual_path: &Path) -> Result<AlbumPage, QueryError> {
+		let album = self.get_album_at(virtual_path)?;
+		let songs = self.flatten(Path::new(&album.path))?;
+		let credits = collect_credits(&songs);
+
+		let similar_albums = match &album.artist {
+			Some(artist) => {
+				let vfs = self.vfs_manager.get_vfs()?;
+				let mut connection = self.db.connect()?;
+				let real_directories: Vec<Directory> = directories::table
+					.filter(directories::album.is_not_null())
+					.filter(directories::artist.eq(artist))
+					.order((directories::year, directories::album))
+					.load(&mut connection)?;
+				real_directories
+					.into_iter()
+					.filter_map(|d| d.virtualize(&vfs))
+					.filter(|d| d.path != album.path)
+					.collect()
+			}
+			None => Vec::new(),
+		};
+
+		Ok(AlbumPage {
+			album,
+			songs,
+			credits,
+			similar_albums,
+		})
+	}
+
+	// Songs credited to an artist, either as the performing artist or as the album artist
+	pub fn get_artist_songs(&self, name: &str, sort: SongSort) -> Result<Vec<Song>, QueryError> {
+		use self::songs::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let mut real_songs: Vec<Song> = songs
+			.filter(artist.eq(name).or(album_artist.eq(name)))
+			.load(&mut connection)?;
+
+		self.sort_songs(&mut real_songs, sort)?;
+
+		let virtual_songs = real_songs.into_iter().filter_map(|s| s.virtualize(&vfs));
+		Ok(virtual_songs.collect())
+	}
+
+	// Other artists ranked by how many genres they share with `name`, most similar first
+	pub fn get_similar_artists(&self, name: &str, count: usize) -> Result<Vec<String>, QueryError> {
+		let mut connection = self.db.connect()?;
+		let credits: Vec<(Option<String>, Option<String>, Option<String>)> = songs::table
+			.filter(songs::genre.is_not_null())
+			.select((songs::artist, songs::album_artist, songs::genre))
+			.distinct()
+			.load(&mut connection)?;
+
+		let mut artist_genres: HashMap<String, HashSet<String>> = HashMap::new();
+		for (artist, album_artist, genre) in credits {
+			let Some(genre) = genre else {
+				continue;
+			};
+			let genres = genre.split('\0').map(str::trim).filter(|g| !g.is_empty());
+			for artist in [artist, album_artist].into_iter().flatten() {
+				artist_genres
+					.entry(artist)
+					.or_default()
+					.extend(genres.clone().map(str::to_owned));
+			}
+		}
+
+		let compilation_artists = self.get_compilation_artist_names();
+		Ok(rank_similar_artists(
+			&artist_genres,
+			&compilation_artists,
+			name,
+			count,
+		))
+	}
+
+	// Songs tagged with any of the given genres. Tags with multiple genres are stored
+	// with the genres separated by null characters.
+	pub fn get_genre_songs(
+		&self,
+		genres: &[String],
+		sort: SongSort,
+	) -> Result<Vec<Song>, QueryError> {
+		use self::songs::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let genres: Vec<&str> = genres
+			.iter()
+			.map(|g| g.trim())
+			.filter(|g| !g.is_empty())
+			.collect();
+		if genres.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		// Candidates are narrowed down in SQL, then each genre is compared exactly, without
+		// surrounding whitespace, like genres are listed by `get_field_values`. SQLite text
+		// functions stop at null characters, so genres are searched as blobs.
+		let mut query = songs.into_boxed();
+		for g in &genres {
+			query = query.or_filter(
+				sql::<sql_types::Bool>("instr(CAST(genre AS BLOB), CAST(")
+					.bind::<sql_types::Text, _>(g.to_string())
+					.sql(" AS BLOB)) > 0"),
+			);
+		}
+		let mut real_songs: Vec<Song> = query.load(&mut connection)?;
+		real_songs.retain(|s| {
+			s.genre
+				.as_deref()
+				.is_some_and(|g| g.split('\0').map(str::trim).any(|g| genres.contains(&g)))
+		});
+		self.sort_songs(&mut real_songs, sort)?;
+
+		let virtual_songs = real_songs.into_iter().filter_map(|s| s.virtualize(&vfs));
+		Ok(virtual_songs.collect())
+	}
+
+	// Reads tags straight from the file rather than from the index
+	pub fn get_raw_tags(&self, virtual_path: &Path) -> Result<Vec<(String, String)>, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let real_path = vfs.virtual_to_real(virtual_path)?;
+		let audio_path = match cue::is_track_path(&real_path) {
+			true => self
+				.get_song(virtual_path)?
+				.cue_segment()
+				.map(|s| s.audio_path)
+				.unwrap_or_else(|| real_path.clone()),
+			false => real_path.clone(),
+		};
+		if !audio_path.is_file() {
+			return Err(QueryError::SongNotFound(real_path));
+		}
+		match metadata::read_raw_tags(&audio_path) {
+			Ok(Some(tags)) => Ok(tags),
+			Ok(None) => Err(QueryError::SongNotFound(real_path)),
+			Err(e) => Err(QueryError::TagsUnreadable(real_path, e)),
+		}
+	}
+
 	pub fn get_song(&self, virtual_path: &Path) -> Result<Song, QueryError> {
 		let vfs = self.vfs_manager.get_vfs()?;
 		let mut connection = self.db.connect()?;
 
 		let real_path = vfs.virtual_to_real(virtual_path)?;
-		let real_path_string = real_path.as_path().to_string_lossy();
+		let real_path_string = real_path.as_path().to_string_lossy().into_owned();
+		let case_insensitive = self
+			.settings_manager
+			.get_case_insensitive_paths()
+			.unwrap_or_default();
 
 		use self::songs::dsl::*;
-		let real_song: Song = songs
-			.filter(path.eq(real_path_string))
-			.get_result(&mut connection)?;
+		let query = match case_insensitive {
+			true => songs
+				.filter(
+					sql::<sql_types::Bool>("path = ")
+						.bind::<sql_types::Text, _>(real_path_string)
+						.sql(" COLLATE NOCASE"),
+				)
+				.into_boxed(),
+			false => songs.filter(path.eq(real_path_string)).into_boxed(),
+		};
+		let real_song: Song = query.first(&mut connection).map_err(|e| match e {
+			diesel::result::Error::NotFound => QueryError::SongNotFound(real_path.clone()),
+			e => e.into(),
+		})?;
 
 		match real_song.virtualize(&vfs) {
 			Some(s) => Ok(s),
@@ -184,3 +869,212 @@ impl Index {
 		}
 	}
 }
+
+fn pick_random<I>(candidates: I, count: usize, seed: Option<u64>) -> Vec<String>
+where
+	I: Iterator<Item = String>,
+{
+	// Sorted and de-duplicated so seeded picks do not depend on database order
+	let candidates: BTreeSet<String> = candidates
+		.map(|c| c.trim().to_owned())
+		.filter(|c| !c.is_empty())
+		.collect();
+	let mut candidates: Vec<String> = candidates.into_iter().collect();
+	let mut rng = match seed {
+		Some(seed) => StdRng::seed_from_u64(seed),
+		None => StdRng::from_entropy(),
+	};
+	candidates.shuffle(&mut rng);
+	candidates.truncate(count);
+	candidates
+}
+
+// Artists sharing no genre with `name` are left out, ties are sorted like `get_artists`
+fn rank_similar_artists(
+	artist_genres: &HashMap<String, HashSet<String>>,
+	compilation_artists: &[String],
+	name: &str,
+	count: usize,
+) -> Vec<String> {
+	let Some(genres) = artist_genres.get(name) else {
+		return Vec::new();
+	};
+	let mut similar: Vec<(usize, &String)> = artist_genres
+		.iter()
+		.filter(|(artist, _)| artist.as_str() != name)
+		.filter(|(artist, _)| !is_compilation_artist(compilation_artists, artist))
+		.map(|(artist, other)| (genres.intersection(other).count(), artist))
+		.filter(|(shared, _)| *shared > 0)
+		.collect();
+	similar.sort_by_cached_key(|(shared, artist)| {
+		(
+			std::cmp::Reverse(*shared),
+			artist_collation_key(artist),
+			artist.to_string(),
+		)
+	});
+	similar
+		.into_iter()
+		.take(count)
+		.map(|(_, artist)| artist.clone())
+		.collect()
+}
+
+fn is_compilation_artist(compilation_artists: &[String], name: &str) -> bool {
+	let name = name.to_lowercase();
+	compilation_artists.iter().any(|c| c.to_lowercase() == name)
+}
+
+fn collect_credits(songs: &[Song]) -> AlbumCredits {
+	let collect = |field: fn(&Song) -> &Option<String>| -> Vec<String> {
+		songs
+			.iter()
+			.filter_map(|s| field(s).as_deref())
+			.map(str::trim)
+			.filter(|v| !v.is_empty())
+			.map(str::to_owned)
+			.collect::<BTreeSet<_>>()
+			.into_iter()
+			.collect()
+	};
+	AlbumCredits {
+		performers: collect(|s| &s.artist),
+		composers: collect(|s| &s.composer),
+		lyricists: collect(|s| &s.lyricist),
+		labels: collect(|s| &s.label),
+	}
+}
+
+// Sorts artists regardless of case and diacritics, with names not starting with a letter first
+fn artist_collation_key(name: &str) -> (bool, String) {
+	let folded: String = name
+		.nfd()
+		.filter(|c| !is_combining_mark(*c))
+		.flat_map(char::to_lowercase)
+		.collect();
+	let starts_with_letter = folded.chars().next().is_some_and(char::is_alphabetic);
+	(starts_with_letter, folded)
+}
+
+fn artist_bucket(name: &str) -> char {
+	let (starts_with_letter, folded) = artist_collation_key(name);
+	match folded.chars().next() {
+		Some(c) if starts_with_letter => c.to_uppercase().next().unwrap_or(c),
+		_ => '#',
+	}
+}
+
+impl Index {
+	fn sort_songs(&self, songs: &mut [Song], sort: SongSort) -> Result<(), QueryError> {
+		let dates_added = match sort {
+			SongSort::DateAdded => self.read_dates_added()?,
+			_ => HashMap::new(),
+		};
+		sort_songs(songs, sort, &dates_added);
+		Ok(())
+	}
+
+	// Keyed by the real path of each directory, like the `parent` of songs
+	fn read_dates_added(&self) -> Result<HashMap<String, i32>, QueryError> {
+		let mut connection = self.db.connect()?;
+		let dates_added: Vec<(String, i32)> = directories::table
+			.select((directories::path, directories::date_added))
+			.load(&mut connection)?;
+		Ok(dates_added.into_iter().collect())
+	}
+}
+
+// Songs whose directory is missing from `dates_added` are sorted last by `SongSort::DateAdded`
+fn sort_songs(songs: &mut [Song], sort: SongSort, dates_added: &HashMap<String, i32>) {
+	let album_order = |s: &Song| {
+		(
+			s.year,
+			s.album.clone(),
+			s.disc_number,
+			s.track_number,
+			s.path.clone(),
+		)
+	};
+	match sort {
+		SongSort::Album => songs.sort_by_cached_key(album_order),
+		SongSort::Title => songs
+			.sort_by_cached_key(|s| (s.title.as_ref().map(|t| t.to_lowercase()), s.path.clone())),
+		SongSort::Year => songs.sort_by_cached_key(|s| (std::cmp::Reverse(s.year), album_order(s))),
+		SongSort::Path => songs.sort_by(|a, b| a.path.cmp(&b.path)),
+		SongSort::Track => {
+			songs.sort_by_cached_key(|s| (s.disc_number, s.track_number, s.path.clone()))
+		}
+		SongSort::DateAdded => songs.sort_by_cached_key(|s| {
+			let date_added = dates_added.get(&s.parent).copied();
+			(std::cmp::Reverse(date_added), s.path.clone())
+		}),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn artist_collation_ignores_case_and_diacritics() {
+		let mut artists = vec!["Zeal", "ásmegin", "Amon Amarth", "Bathory"];
+		artists.sort_by_cached_key(|a| artist_collation_key(a));
+		assert_eq!(artists, vec!["Amon Amarth", "ásmegin", "Bathory", "Zeal"]);
+	}
+
+	#[test]
+	fn artists_not_starting_with_letter_are_bucketed_first() {
+		let mut artists = ["Bathory", "2Pac", "_Dig", "Ásmegin", "!!!"];
+		artists.sort_by_cached_key(|a| artist_collation_key(a));
+		let buckets: Vec<char> = artists.iter().map(|a| artist_bucket(a)).collect();
+		assert_eq!(buckets, vec!['#', '#', '#', 'A', 'B']);
+	}
+
+	#[test]
+	fn compilation_artists_are_matched_regardless_of_case() {
+		let compilation_artists = vec![
+			"Various Artists".to_owned(),
+			"Verschiedene Interpreten".to_owned(),
+		];
+		assert!(is_compilation_artist(
+			&compilation_artists,
+			"various artists"
+		));
+		assert!(is_compilation_artist(
+			&compilation_artists,
+			"VERSCHIEDENE INTERPRETEN"
+		));
+		assert!(!is_compilation_artist(&compilation_artists, "Various"));
+		assert!(!is_compilation_artist(&[], "Various Artists"));
+	}
+
+	#[test]
+	fn similar_artists_are_ranked_by_shared_genres() {
+		let artist_genres: HashMap<String, HashSet<String>> = [
+			(
+				"Bathory",
+				vec!["Black Metal", "Viking Metal", "Thrash Metal"],
+			),
+			("Enslaved", vec!["Black Metal", "Viking Metal"]),
+			("Amon Amarth", vec!["Viking Metal"]),
+			("Kreator", vec!["Thrash Metal"]),
+			("Various Artists", vec!["Black Metal", "Viking Metal"]),
+			("Tobokegao", vec!["Chiptune"]),
+		]
+		.into_iter()
+		.map(|(a, g)| (a.to_owned(), g.into_iter().map(str::to_owned).collect()))
+		.collect();
+
+		let compilation_artists = vec!["various artists".to_owned()];
+		let rank = |name: &str, count: usize| {
+			rank_similar_artists(&artist_genres, &compilation_artists, name, count)
+		};
+		assert_eq!(
+			rank("Bathory", 10),
+			vec!["Enslaved", "Amon Amarth", "Kreator"]
+		);
+		assert_eq!(rank("Bathory", 2), vec!["Enslaved", "Amon Amarth"]);
+		assert!(rank("Tobokegao", 10).is_empty());
+		assert!(rank("Burzum", 10).is_empty());
+	}
+}
@@ -0,0 +1,376 @@
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::*;
+use crate::db::{index_changes, index_fingerprints, index_generations, songs};
+
+const INSERT_BUFFER_SIZE: usize = 1000; // Insertions in each query
+const MAX_GENERATIONS: i64 = 8; // Number of generations whose changes are kept
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncChanges {
+	pub generation: Option<i32>,
+	// Set when the requested generation is unknown or too old to diff against,
+	// in which case every song is listed as added.
+	pub full_sync: bool,
+	pub added: Vec<String>,
+	pub removed: Vec<String>,
+	pub changed: Vec<String>,
+}
+
+// Fields are `None` until the first index update completes
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationInfo {
+	pub generation: Option<i32>,
+	pub date: Option<i32>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = index_changes)]
+struct NewChange {
+	generation: i32,
+	path: String,
+	// `None` when the song was added in this generation
+	previous_fingerprint: Option<i64>,
+	// `None` when the song was removed in this generation
+	fingerprint: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = index_fingerprints)]
+struct NewFingerprint {
+	path: String,
+	fingerprint: i64,
+}
+
+type Fingerprints = HashMap<String, i64>;
+
+impl Index {
+	// Records a new generation of the collection, unless it is identical to the latest one.
+	// Only the songs which changed since the latest generation are stored with it.
+	pub(super) fn record_generation(&self) -> Result<(), QueryError> {
+		let mut connection = self.db.connect()?;
+
+		let songs: Vec<Song> = songs::table.load(&mut connection)?;
+		let current: Fingerprints = songs
+			.into_iter()
+			.map(|s| {
+				let fingerprint = fingerprint(&s);
+				(s.path, fingerprint)
+			})
+			.collect();
+		let previous = self.read_fingerprints(&mut connection)?;
+
+		let latest = self.get_latest_generation(&mut connection)?;
+		if latest.is_some() && previous == current {
+			self.initial_scan_completed.store(true, Ordering::Relaxed);
+			return Ok(());
+		}
+
+		let date = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs() as i32)
+			.unwrap_or_default();
+
+		connection.transaction::<_, diesel::result::Error, _>(|connection| {
+			diesel::insert_into(index_generations::table)
+				.values(index_generations::date.eq(date))
+				.execute(&mut *connection)?;
+			let generation: i32 = index_generations::table
+				.select(diesel::dsl::max(index_generations::id))
+				.first::<Option<i32>>(&mut *connection)?
+				.unwrap_or_default();
+
+			let updated: Vec<NewChange> = current
+				.iter()
+				.filter(|(path, fingerprint)| previous.get(*path) != Some(*fingerprint))
+				.map(|(path, fingerprint)| NewChange {
+					generation,
+					path: path.clone(),
+					previous_fingerprint: previous.get(path).copied(),
+					fingerprint: Some(*fingerprint),
+				})
+				.collect();
+			let removed: Vec<NewChange> = previous
+				.iter()
+				.filter(|(path, _)| !current.contains_key(*path))
+				.map(|(path, fingerprint)| NewChange {
+					generation,
+					path: path.clone(),
+					previous_fingerprint: Some(*fingerprint),
+					fingerprint: None,
+				})
+				.collect();
+
+			for chunk in removed.chunks(INSERT_BUFFER_SIZE) {
+				let paths = chunk.iter().map(|c| &c.path);
+				diesel::delete(index_fingerprints::table)
+					.filter(index_fingerprints::path.eq_any(paths))
+					.execute(&mut *connection)?;
+			}
+			let fingerprints: Vec<NewFingerprint> = updated
+				.iter()
+				.filter_map(|c| {
+					Some(NewFingerprint {
+						path: c.path.clone(),
+						fingerprint: c.fingerprint?,
+					})
+				})
+				.collect();
+			for chunk in fingerprints.chunks(INSERT_BUFFER_SIZE) {
+				diesel::replace_into(index_fingerprints::table)
+					.values(chunk)
+					.execute(&mut *connection)?;
+			}
+
+			for chunk in updated.chunks(INSERT_BUFFER_SIZE) {
+				diesel::insert_into(index_changes::table)
+					.values(chunk)
+					.execute(&mut *connection)?;
+			}
+			for chunk in removed.chunks(INSERT_BUFFER_SIZE) {
+				diesel::insert_into(index_changes::table)
+					.values(chunk)
+					.execute(&mut *connection)?;
+			}
+
+			let expired: Vec<i32> = index_generations::table
+				.select(index_generations::id)
+				.order(index_generations::id.desc())
+				.offset(MAX_GENERATIONS)
+				.limit(i64::MAX)
+				.load(&mut *connection)?;
+			diesel::delete(index_generations::table.filter(index_generations::id.eq_any(expired)))
+				.execute(&mut *connection)?;
+
+			Ok(())
+		})?;
+
+		self.initial_scan_completed.store(true, Ordering::Relaxed);
+		Ok(())
+	}
+
+	// Composes the changes recorded by every generation after `since`
+	pub fn get_changes_since(&self, since: Option<i32>) -> Result<SyncChanges, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let generation = self.get_latest_generation(&mut connection)?;
+
+		let is_known_generation = match since {
+			Some(since) => index_generations::table
+				.find(since)
+				.select(index_generations::id)
+				.first::<i32>(&mut connection)
+				.optional()?
+				.is_some(),
+			None => false,
+		};
+
+		let virtualize = |paths: Vec<String>| -> Vec<String> {
+			let mut paths: Vec<String> = paths
+				.into_iter()
+				.filter_map(|p| vfs.real_to_virtual(Path::new(&p)).ok())
+				.map(|p| p.to_string_lossy().into_owned())
+				.collect();
+			paths.sort();
+			paths
+		};
+
+		let since = match since {
+			Some(since) if is_known_generation => since,
+			_ => {
+				let added = self.read_fingerprints(&mut connection)?.into_keys();
+				return Ok(SyncChanges {
+					generation,
+					full_sync: true,
+					added: virtualize(added.collect()),
+					..Default::default()
+				});
+			}
+		};
+
+		let changes: Vec<(String, Option<i64>, Option<i64>)> = index_changes::table
+			.filter(index_changes::generation.gt(since))
+			.order(index_changes::generation.asc())
+			.select((
+				index_changes::path,
+				index_changes::previous_fingerprint,
+				index_changes::fingerprint,
+			))
+			.load(&mut connection)?;
+
+		// Fingerprint of each song when `since` was recorded, and as of the latest generation
+		let mut composed: HashMap<String, (Option<i64>, Option<i64>)> = HashMap::new();
+		for (path, previous_fingerprint, fingerprint) in changes {
+			composed
+				.entry(path)
+				.and_modify(|(_, f)| *f = fingerprint)
+				.or_insert((previous_fingerprint, fingerprint));
+		}
+
+		let mut added = Vec::new();
+		let mut removed = Vec::new();
+		let mut changed = Vec::new();
+		for (path, fingerprints) in composed {
+			match fingerprints {
+				(None, Some(_)) => added.push(path),
+				(Some(_), None) => removed.push(path),
+				(Some(before), Some(after)) if before != after => changed.push(path),
+				_ => (),
+			}
+		}
+
+		Ok(SyncChanges {
+			generation,
+			full_sync: false,
+			added: virtualize(added),
+			removed: virtualize(removed),
+			changed: virtualize(changed),
+		})
+	}
+
+	// Returns `None` until the first index update completes
+	pub fn get_generation(&self) -> Result<Option<i32>, QueryError> {
+		let mut connection = self.db.connect()?;
+		self.get_latest_generation(&mut connection)
+	}
+
+	pub fn get_generation_info(&self) -> Result<GenerationInfo, QueryError> {
+		let mut connection = self.db.connect()?;
+		let latest: Option<(i32, i32)> = index_generations::table
+			.select((index_generations::id, index_generations::date))
+			.order(index_generations::id.desc())
+			.first(&mut connection)
+			.optional()?;
+		Ok(match latest {
+			Some((generation, date)) => GenerationInfo {
+				generation: Some(generation),
+				date: Some(date),
+			},
+			None => GenerationInfo::default(),
+		})
+	}
+
+	fn get_latest_generation(
+		&self,
+		connection: &mut SqliteConnection,
+	) -> Result<Option<i32>, QueryError> {
+		Ok(index_generations::table
+			.select(diesel::dsl::max(index_generations::id))
+			.first(connection)?)
+	}
+
+	// Fingerprints of the songs as of the latest generation
+	fn read_fingerprints(
+		&self,
+		connection: &mut SqliteConnection,
+	) -> Result<Fingerprints, QueryError> {
+		let entries: Vec<(String, i64)> = index_fingerprints::table
+			.select((index_fingerprints::path, index_fingerprints::fingerprint))
+			.load(connection)?;
+		Ok(entries.into_iter().collect())
+	}
+}
+
+// Fingerprints are persisted and compared across server versions, so they are computed with
+// FNV-1a over an explicit list of the song fields clients can see, rather than with `Hash`.
+fn fingerprint(song: &Song) -> i64 {
+	let mut hasher = Fnv::new();
+	hasher.write_str(Some(&song.path));
+	hasher.write_int(song.track_number);
+	hasher.write_int(song.disc_number);
+	hasher.write_str(song.title.as_deref());
+	hasher.write_str(song.artist.as_deref());
+	hasher.write_str(song.album_artist.as_deref());
+	hasher.write_int(song.year);
+	hasher.write_str(song.album.as_deref());
+	hasher.write_str(song.artwork.as_deref());
+	hasher.write_int(song.duration);
+	hasher.write_str(song.lyricist.as_deref());
+	hasher.write_str(song.composer.as_deref());
+	hasher.write_str(song.genre.as_deref());
+	hasher.write_str(song.label.as_deref());
+	hasher.write_int(song.start_offset_ms);
+	hasher.write_int(song.end_offset_ms);
+	hasher.write(&[song.metadata_error as u8]);
+	hasher.write_int(song.track_total);
+	hasher.write_int(song.disc_total);
+	hasher.write_float(song.track_gain);
+	hasher.write_float(song.track_peak);
+	hasher.write_float(song.album_gain);
+	hasher.write_float(song.album_peak);
+	hasher.write(&[song.duration_decoded as u8]);
+	hasher.0 as i64
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+	const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+	const PRIME: u64 = 0x0100_0000_01b3;
+
+	fn new() -> Self {
+		Self(Self::OFFSET_BASIS)
+	}
+
+	fn write(&mut self, bytes: &[u8]) {
+		for byte in bytes {
+			self.0 ^= *byte as u64;
+			self.0 = self.0.wrapping_mul(Self::PRIME);
+		}
+	}
+
+	// Values are prefixed with whether they are set, and strings with their length, so that
+	// adjacent fields cannot be confused with each other
+	fn write_str(&mut self, value: Option<&str>) {
+		match value {
+			Some(value) => {
+				self.write(&[1]);
+				self.write(&(value.len() as u64).to_le_bytes());
+				self.write(value.as_bytes());
+			}
+			None => self.write(&[0]),
+		}
+	}
+
+	fn write_int(&mut self, value: Option<i32>) {
+		match value {
+			Some(value) => {
+				self.write(&[1]);
+				self.write(&value.to_le_bytes());
+			}
+			None => self.write(&[0]),
+		}
+	}
+
+	fn write_float(&mut self, value: Option<f32>) {
+		match value {
+			Some(value) => {
+				self.write(&[1]);
+				self.write(&value.to_bits().to_le_bytes());
+			}
+			None => self.write(&[0]),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn fnv_matches_reference_values() {
+		let mut hasher = Fnv::new();
+		hasher.write(b"");
+		assert_eq!(hasher.0, 0xcbf2_9ce4_8422_2325);
+		let mut hasher = Fnv::new();
+		hasher.write(b"foobar");
+		assert_eq!(hasher.0, 0x8594_4171_f739_67e8);
+	}
+}
@@ -66,6 +66,187 @@ fn update_removes_missing_content() {
 	}
 }
 
+#[test]
+fn clean_removes_missing_content() {
+	let builder = test::ContextBuilder::new(test_name!());
+
+	let original_collection_dir: PathBuf = ["test-data", "small-collection"].iter().collect();
+	let test_collection_dir: PathBuf = builder.test_directory.join("small-collection");
+
+	let copy_options = fs_extra::dir::CopyOptions::new();
+	fs_extra::dir::copy(
+		original_collection_dir,
+		&builder.test_directory,
+		&copy_options,
+	)
+	.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, test_collection_dir.to_str().unwrap())
+		.build();
+
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.clean().unwrap(), CleanReport::default());
+	let generation = ctx.index.get_generation().unwrap();
+
+	let khemmis_directory = test_collection_dir.join("Khemmis");
+	std::fs::remove_dir_all(khemmis_directory).unwrap();
+	assert_eq!(
+		ctx.index.clean().unwrap(),
+		CleanReport {
+			directories_removed: 2,
+			songs_removed: 5,
+		}
+	);
+
+	let mut connection = ctx.db.connect().unwrap();
+	let all_songs: Vec<Song> = songs::table.load(&mut connection).unwrap();
+	assert_eq!(all_songs.len(), 8);
+	assert!(ctx.index.get_generation().unwrap() > generation);
+}
+
+#[test]
+fn clean_is_rejected_during_update() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	assert!(ctx.index.scan_progress.begin());
+	assert!(matches!(
+		ctx.index.clean(),
+		Err(update::Error::ScanInProgress)
+	));
+	ctx.index.scan_progress.end();
+	assert_eq!(ctx.index.clean().unwrap(), CleanReport::default());
+	assert!(!ctx.index.get_scan_status().running);
+}
+
+#[test]
+fn update_reuses_unmodified_songs() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	let song_path = collection_dir.join("song.mp3");
+	std::fs::copy("test-data/formats/sample.mp3", &song_path).unwrap();
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	let song_virtual_path: PathBuf = [TEST_MOUNT_NAME, "song.mp3"].iter().collect();
+
+	let edit_title = |title: &str, mtime: std::time::SystemTime| {
+		let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+		tag.set_title(title);
+		tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+		let file = std::fs::File::options()
+			.write(true)
+			.open(&song_path)
+			.unwrap();
+		file.set_modified(mtime).unwrap();
+	};
+	let title = || ctx.index.get_song(&song_virtual_path).unwrap().title;
+
+	ctx.index.update().unwrap();
+	assert_eq!(title(), Some("TEST TITLE".to_owned()));
+	let mtime = std::fs::metadata(&song_path).unwrap().modified().unwrap();
+
+	// Tags are not read again when the file modification time is unchanged
+	edit_title("Edited", mtime);
+	ctx.index.update().unwrap();
+	assert_eq!(title(), Some("TEST TITLE".to_owned()));
+	assert_eq!(ctx.index.get_scan_status().songs_found, 1);
+
+	// Nor after a restart
+	let restarted_index = Index::new(
+		ctx.db.clone(),
+		ctx.vfs_manager.clone(),
+		ctx.settings_manager.clone(),
+	);
+	restarted_index.update().unwrap();
+	assert_eq!(title(), Some("TEST TITLE".to_owned()));
+
+	edit_title("Edited", mtime + std::time::Duration::from_secs(1));
+	ctx.index.update().unwrap();
+	assert_eq!(title(), Some("Edited".to_owned()));
+
+	// Changing settings which affect tags causes all songs to be read again
+	edit_title("Edited Again", mtime + std::time::Duration::from_secs(1));
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			exact_durations: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+	assert_eq!(title(), Some("Edited Again".to_owned()));
+}
+
+#[test]
+fn update_rereads_directories_with_new_songs() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("Loose Tracks");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	let song_path = collection_dir.join("01.mp3");
+	std::fs::copy("test-data/formats/sample.mp3", &song_path).unwrap();
+	let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+	tag.remove_album();
+	tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			folder_name_albums: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	let song_virtual_path: PathBuf = [TEST_MOUNT_NAME, "01.mp3"].iter().collect();
+	let album = || ctx.index.get_song(&song_virtual_path).unwrap().album;
+
+	ctx.index.update().unwrap();
+	assert_eq!(album(), Some("Loose Tracks".to_owned()));
+
+	// The unmodified song no longer belongs to an album named after its directory
+	std::fs::copy(
+		"test-data/formats/sample.mp3",
+		collection_dir.join("02.mp3"),
+	)
+	.unwrap();
+	ctx.index.update().unwrap();
+	assert_eq!(album(), None);
+}
+
+#[test]
+fn records_decoded_durations() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/formats")
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			exact_durations: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	let song = |name: &str| {
+		songs
+			.iter()
+			.find(|s| Path::new(&s.path).file_name() == Some(name.as_ref()))
+			.unwrap()
+	};
+	assert_eq!(song("sample.opus").duration, Some(30));
+	assert!(song("sample.opus").duration_decoded);
+	assert_eq!(song("sample.aif").duration, Some(2));
+	assert!(song("sample.aif").duration_decoded);
+	assert!(!song("sample.flac").duration_decoded);
+}
+
 #[test]
 fn can_browse_top_level() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -117,6 +298,31 @@ fn can_flatten_root() {
 	assert_eq!(songs[0].title, Some("Above The Water".to_owned()));
 }
 
+#[test]
+fn flatten_pages_follow_flatten_order() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+	let expected = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+
+	let mut songs = Vec::new();
+	let mut after = None;
+	loop {
+		let (page, next) = ctx
+			.index
+			.flatten_page(Path::new(TEST_MOUNT_NAME), after, 5)
+			.unwrap();
+		assert!(page.len() <= 5);
+		songs.extend(page);
+		match next {
+			Some(next) => after = Some(next),
+			None => break,
+		}
+	}
+	assert_eq!(songs, expected);
+}
+
 #[test]
 fn can_flatten_directory() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -139,6 +345,82 @@ fn can_flatten_directory_with_shared_prefix() {
 	assert_eq!(songs.len(), 7);
 }
 
+#[test]
+fn can_flatten_with_sort() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let root = Path::new(TEST_MOUNT_NAME);
+	let songs = ctx.index.flatten_sorted(root, SongSort::Path).unwrap();
+	assert_eq!(songs, ctx.index.flatten(root).unwrap());
+
+	let songs = ctx.index.flatten_sorted(root, SongSort::Track).unwrap();
+	assert_eq!(songs.len(), 13);
+	assert!(songs
+		.windows(2)
+		.all(|w| (w[0].disc_number, w[0].track_number) <= (w[1].disc_number, w[1].track_number)));
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis"].iter().collect();
+	let titles: Vec<String> = ctx
+		.index
+		.flatten_sorted(path, SongSort::Title)
+		.unwrap()
+		.into_iter()
+		.filter_map(|s| s.title)
+		.collect();
+	assert_eq!(
+		titles,
+		vec![
+			"Above The Water",
+			"Beyond The Door",
+			"Candlelight",
+			"Hunted",
+			"Three Gates"
+		]
+	);
+}
+
+#[test]
+fn can_flatten_by_date_added() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	// Songs follow the date their directory was added, regardless of file modification times
+	let mark_hunted_added_at = |hunted: i32, others: i32| {
+		let mut connection = ctx.db.connect().unwrap();
+		diesel::update(directories::table)
+			.set(directories::date_added.eq(others))
+			.execute(&mut connection)
+			.unwrap();
+		diesel::update(directories::table.filter(directories::path.like("%Hunted%")))
+			.set(directories::date_added.eq(hunted))
+			.execute(&mut connection)
+			.unwrap();
+	};
+	let albums = || -> Vec<Option<String>> {
+		ctx.index
+			.flatten_sorted(Path::new(TEST_MOUNT_NAME), SongSort::DateAdded)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.album)
+			.collect()
+	};
+	let is_hunted = |a: &Option<String>| a.as_deref() == Some("Hunted");
+
+	mark_hunted_added_at(1, 0);
+	let newest_first = albums();
+	assert_eq!(newest_first.len(), 13);
+	assert!(newest_first[..5].iter().all(is_hunted));
+
+	mark_hunted_added_at(0, 1);
+	let newest_first = albums();
+	assert!(newest_first[8..].iter().all(is_hunted));
+}
+
 #[test]
 fn can_get_random_albums() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -149,17 +431,498 @@ fn can_get_random_albums() {
 	assert_eq!(albums.len(), 1);
 }
 
+#[test]
+fn triggers_are_merged_into_running_update() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+
+	assert!(!ctx.index.trigger_reindex());
+	assert!(ctx.index.trigger_reindex());
+}
+
+#[test]
+fn only_one_update_runs_at_a_time() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+
+	assert!(ctx.index.scan_progress.begin());
+	assert!(matches!(
+		ctx.index.update(),
+		Err(UpdateError::ScanInProgress)
+	));
+	assert!(ctx.index.trigger_reindex());
+
+	ctx.index.scan_progress.end();
+	ctx.index.update().unwrap();
+}
+
+#[test]
+fn can_get_artists() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let artists = ctx.index.get_artists().unwrap();
+	assert_eq!(artists, vec!["Khemmis".to_owned(), "Tobokegao".to_owned()]);
+}
+
+#[test]
+fn compilation_artists_are_excluded_from_artists() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			compilation_artist_names: Some(vec!["KHEMMIS".to_owned()]),
+			..Default::default()
+		})
+		.unwrap();
+
+	let artists = ctx.index.get_artists().unwrap();
+	assert_eq!(artists, vec!["Tobokegao".to_owned()]);
+	assert_eq!(ctx.index.get_statistics().unwrap().num_artists, 1);
+}
+
+#[test]
+fn can_get_similar_artists() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	// Khemmis and Tobokegao have no genre in common
+	assert!(ctx
+		.index
+		.get_similar_artists("Khemmis", 10)
+		.unwrap()
+		.is_empty());
+	assert!(ctx
+		.index
+		.get_similar_artists("Burzum", 10)
+		.unwrap()
+		.is_empty());
+}
+
+#[test]
+fn can_get_field_values() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let values = |field: SongField| -> Vec<(String, usize)> {
+		ctx.index
+			.get_field_values(field)
+			.unwrap()
+			.into_iter()
+			.map(|v| (v.value, v.num_songs))
+			.collect()
+	};
+
+	assert_eq!(
+		values(SongField::Artist),
+		vec![("Khemmis".to_owned(), 5), ("Tobokegao".to_owned(), 8)]
+	);
+	assert_eq!(
+		values(SongField::Album),
+		vec![
+			("Hunted".to_owned(), 5),
+			("Picnic".to_owned(), 7),
+			("Picnic (Remixes)".to_owned(), 1)
+		]
+	);
+	assert_eq!(
+		values(SongField::Genre),
+		vec![
+			("Chiptune".to_owned(), 7),
+			("Doom Metal".to_owned(), 5),
+			("Electronic".to_owned(), 8),
+			("Metal".to_owned(), 5)
+		]
+	);
+	assert_eq!(values(SongField::Year), vec![("2016".to_owned(), 13)]);
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			compilation_artist_names: Some(vec!["KHEMMIS".to_owned()]),
+			..Default::default()
+		})
+		.unwrap();
+	assert_eq!(values(SongField::Artist), vec![("Tobokegao".to_owned(), 8)]);
+}
+
+#[test]
+fn artist_buckets_match_artist_order() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let buckets = ctx.index.get_artist_buckets().unwrap();
+	assert_eq!(
+		buckets,
+		vec![
+			ArtistBucket {
+				letter: 'K',
+				num_artists: 1
+			},
+			ArtistBucket {
+				letter: 'T',
+				num_artists: 1
+			},
+		]
+	);
+	let total: usize = buckets.iter().map(|b| b.num_artists).sum();
+	assert_eq!(total, ctx.index.get_artists().unwrap().len());
+}
+
+#[test]
+fn can_get_random_artists() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let mut artists = ctx.index.get_random_artists(10, None).unwrap();
+	artists.sort();
+	assert_eq!(artists, vec!["Khemmis".to_owned(), "Tobokegao".to_owned()]);
+
+	let first_pick = ctx.index.get_random_artists(1, Some(42)).unwrap();
+	assert_eq!(first_pick.len(), 1);
+	assert_eq!(
+		ctx.index.get_random_artists(1, Some(42)).unwrap(),
+		first_pick
+	);
+}
+
+#[test]
+fn can_get_random_genres() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let genres = ctx.index.get_random_genres(10, Some(7)).unwrap();
+	let mut sorted_genres = genres.clone();
+	sorted_genres.sort();
+	assert_eq!(
+		sorted_genres,
+		vec!["Chiptune", "Doom Metal", "Electronic", "Metal"]
+	);
+	assert_eq!(ctx.index.get_random_genres(10, Some(7)).unwrap(), genres);
+}
+
 #[test]
 fn can_get_recent_albums() {
 	let ctx = test::ContextBuilder::new(test_name!())
 		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
 		.build();
 	ctx.index.update().unwrap();
-	let albums = ctx.index.get_recent_albums(2).unwrap();
+	let albums = ctx.index.get_recent_albums(2, false).unwrap();
 	assert_eq!(albums.len(), 2);
 	assert!(albums[0].date_added >= albums[1].date_added);
 }
 
+#[test]
+fn album_listings_include_duration() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let recent_albums = ctx.index.get_recent_albums(10, false).unwrap();
+	let random_albums = ctx.index.get_random_albums(10).unwrap();
+	assert_eq!(recent_albums.len(), 3);
+	assert_eq!(random_albums.len(), 3);
+	for album in recent_albums.iter().chain(random_albums.iter()) {
+		let songs = ctx.index.flatten(PathBuf::from(&album.path)).unwrap();
+		let expected: i64 = songs.iter().filter_map(|s| s.duration).map(i64::from).sum();
+		assert_eq!(album.duration, Some(expected));
+	}
+}
+
+#[test]
+fn can_exclude_singles_from_recent_albums() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let albums = ctx.index.get_recent_albums(10, false).unwrap();
+	assert_eq!(albums.len(), 3);
+
+	let albums = ctx.index.get_recent_albums(10, true).unwrap();
+	let mut names: Vec<_> = albums.into_iter().filter_map(|a| a.album).collect();
+	names.sort();
+	assert_eq!(names, vec!["Hunted".to_owned(), "Picnic".to_owned()]);
+}
+
+#[test]
+fn can_get_album_credits() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	let album_dir = collection_dir.join("album");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	std::fs::copy("test-data/formats/sample.mp3", album_dir.join("01.mp3")).unwrap();
+	let song_path = album_dir.join("02.mp3");
+	std::fs::copy("test-data/formats/sample.mp3", &song_path).unwrap();
+	let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+	tag.set_text("TCOM", "Another Composer");
+	tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let album_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "album"].iter().collect();
+	let credits = ctx.index.get_album_credits(&album_virtual_dir).unwrap();
+	assert_eq!(
+		credits,
+		AlbumCredits {
+			performers: vec!["TEST ARTIST".to_owned()],
+			composers: vec!["Another Composer".to_owned(), "TEST COMPOSER".to_owned()],
+			lyricists: vec!["TEST LYRICIST".to_owned()],
+			labels: vec!["TEST LABEL".to_owned()],
+		}
+	);
+}
+
+#[test]
+fn indexes_replay_gain() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	let song_path = collection_dir.join("song.mp3");
+	std::fs::copy("test-data/formats/sample.mp3", &song_path).unwrap();
+	let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+	tag.add_frame(id3::frame::ExtendedText {
+		description: "REPLAYGAIN_TRACK_GAIN".to_owned(),
+		value: "-6.5 dB".to_owned(),
+	});
+	tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let song_virtual_path: PathBuf = [TEST_MOUNT_NAME, "song.mp3"].iter().collect();
+	let song = ctx.index.get_song(&song_virtual_path).unwrap();
+	assert_eq!(song.track_gain, Some(-6.5));
+	assert_eq!(song.album_gain, None);
+}
+
+#[test]
+fn can_get_album_page() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let picnic_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "Tobokegao", "Picnic"].iter().collect();
+	let page = ctx.index.get_album_page(&picnic_virtual_dir).unwrap();
+	assert_eq!(page.album.album, Some("Picnic".to_owned()));
+	assert_eq!(page.songs.len(), 7);
+	assert_eq!(page.credits.performers, vec!["Tobokegao".to_owned()]);
+	let similar: Vec<_> = page
+		.similar_albums
+		.into_iter()
+		.filter_map(|a| a.album)
+		.collect();
+	assert_eq!(similar, vec!["Picnic (Remixes)".to_owned()]);
+}
+
+#[test]
+fn can_browse_labels() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	let album_dir = collection_dir.join("album");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	std::fs::copy("test-data/formats/sample.mp3", album_dir.join("01.mp3")).unwrap();
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.mount("small", "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	assert_eq!(
+		ctx.index.get_labels().unwrap(),
+		vec!["TEST LABEL".to_owned()]
+	);
+
+	let label = ctx.index.get_label("TEST LABEL").unwrap();
+	let album_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "album"].iter().collect();
+	assert_eq!(label.albums.len(), 1);
+	assert_eq!(label.albums[0].path, album_virtual_dir.to_string_lossy());
+	assert_eq!(label.artists, vec!["TEST ARTIST".to_owned()]);
+
+	assert_eq!(ctx.index.get_label("Unknown").unwrap(), Label::default());
+
+	// Labels are listed and looked up without surrounding whitespace
+	let mut connection = ctx.db.connect().unwrap();
+	diesel::update(songs::table.filter(songs::label.is_not_null()))
+		.set(songs::label.eq(" TEST LABEL "))
+		.execute(&mut connection)
+		.unwrap();
+	assert_eq!(
+		ctx.index.get_labels().unwrap(),
+		vec!["TEST LABEL".to_owned()]
+	);
+	assert_eq!(ctx.index.get_label("TEST LABEL").unwrap().albums.len(), 1);
+}
+
+#[test]
+fn can_get_statistics() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	assert_eq!(ctx.index.get_statistics().unwrap(), Statistics::default());
+
+	ctx.index.update().unwrap();
+
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	let total_duration: i64 = songs.iter().filter_map(|s| s.duration).map(i64::from).sum();
+	assert_eq!(
+		ctx.index.get_statistics().unwrap(),
+		Statistics {
+			num_songs: 13,
+			num_albums: 3,
+			num_artists: 2,
+			num_genres: 4,
+			total_duration,
+		}
+	);
+}
+
+#[test]
+fn can_browse_by_year() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	assert_eq!(
+		ctx.index.get_years().unwrap(),
+		vec![Year {
+			year: 2016,
+			num_albums: 3
+		}]
+	);
+	assert_eq!(
+		ctx.index.get_decades().unwrap(),
+		vec![Decade {
+			decade: 2010,
+			num_albums: 3
+		}]
+	);
+
+	let albums = ctx.index.get_year_albums(2016).unwrap();
+	let names: Vec<_> = albums.into_iter().filter_map(|a| a.album).collect();
+	assert_eq!(names, vec!["Hunted", "Picnic", "Picnic (Remixes)"]);
+	assert!(ctx.index.get_year_albums(1999).unwrap().is_empty());
+}
+
+#[test]
+fn can_get_album_at_directory() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+
+	ctx.index.update().unwrap();
+
+	let hunted_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let album = ctx.index.get_album_at(&hunted_virtual_dir).unwrap();
+	assert_eq!(album.path, hunted_virtual_dir.to_string_lossy());
+	assert_eq!(album.album, Some("Hunted".to_owned()));
+
+	// Directories containing a single album resolve to it
+	let khemmis_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "Khemmis"].iter().collect();
+	let album = ctx.index.get_album_at(&khemmis_virtual_dir).unwrap();
+	assert_eq!(album.path, hunted_virtual_dir.to_string_lossy());
+
+	assert!(matches!(
+		ctx.index.get_album_at(Path::new(TEST_MOUNT_NAME)),
+		Err(QueryError::AlbumNotFound(_))
+	));
+}
+
+#[test]
+fn can_get_artist_songs() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let titles = |sort: SongSort| -> Vec<String> {
+		ctx.index
+			.get_artist_songs("Khemmis", sort)
+			.unwrap()
+			.into_iter()
+			.filter_map(|s| s.title)
+			.collect()
+	};
+
+	assert_eq!(
+		titles(SongSort::Album),
+		vec![
+			"Above The Water",
+			"Candlelight",
+			"Three Gates",
+			"Beyond The Door",
+			"Hunted"
+		]
+	);
+	assert_eq!(
+		titles(SongSort::Title),
+		vec![
+			"Above The Water",
+			"Beyond The Door",
+			"Candlelight",
+			"Hunted",
+			"Three Gates"
+		]
+	);
+	assert!(ctx
+		.index
+		.get_artist_songs("Not An Artist", SongSort::default())
+		.unwrap()
+		.is_empty());
+}
+
+#[test]
+fn can_get_genre_songs() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let count = |genres: &[&str]| -> usize {
+		let genres: Vec<String> = genres.iter().map(|g| g.to_string()).collect();
+		ctx.index
+			.get_genre_songs(&genres, SongSort::default())
+			.unwrap()
+			.len()
+	};
+
+	assert_eq!(count(&["Metal"]), 5);
+	assert_eq!(count(&["Metal", "Electronic"]), 13);
+	assert_eq!(count(&["Metal", "Doom Metal"]), 5);
+	assert_eq!(count(&["Chiptune"]), 7);
+	assert_eq!(count(&[" Chiptune "]), 7);
+	assert_eq!(count(&["Chip"]), 0);
+	assert_eq!(count(&[]), 0);
+}
+
 #[test]
 fn can_get_a_song() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -187,6 +950,33 @@ fn can_get_a_song() {
 	);
 }
 
+#[test]
+fn can_get_a_song_case_insensitively() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+
+	ctx.index.update().unwrap();
+
+	let song_virtual_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let miscased_virtual_path: PathBuf =
+		[TEST_MOUNT_NAME, "KHEMMIS", "hunted", "02 - CANDLELIGHT.mp3"]
+			.iter()
+			.collect();
+	assert!(ctx.index.get_song(&miscased_virtual_path).is_err());
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			case_insensitive_paths: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	let song = ctx.index.get_song(&miscased_virtual_path).unwrap();
+	assert_eq!(song.path, song_virtual_path.to_string_lossy().as_ref());
+}
+
 #[test]
 fn indexes_embedded_artwork() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -205,6 +995,71 @@ fn indexes_embedded_artwork() {
 	);
 }
 
+#[test]
+fn indexes_artwork_referenced_by_tags() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	let album_dir = collection_dir.join("album");
+	std::fs::create_dir_all(album_dir.join("covers")).unwrap();
+	std::fs::copy(
+		"test-data/small-collection/Khemmis/Hunted/Folder.jpg",
+		album_dir.join("covers").join("front.jpg"),
+	)
+	.unwrap();
+	std::fs::copy(
+		"test-data/small-collection/Khemmis/Hunted/Folder.jpg",
+		builder.test_directory.join("outside.jpg"),
+	)
+	.unwrap();
+
+	let link_artwork = |name: &str, reference: &str| {
+		let song_path = album_dir.join(name);
+		std::fs::copy("test-data/formats/sample.mp3", &song_path).unwrap();
+		let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+		tag.add_frame(id3::frame::Picture {
+			mime_type: "-->".to_owned(),
+			picture_type: id3::frame::PictureType::CoverFront,
+			description: String::new(),
+			data: reference.as_bytes().to_vec(),
+		});
+		tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+	};
+	link_artwork("inside.mp3", "covers/front.jpg");
+	link_artwork("outside.mp3", "../../outside.jpg");
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let album_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "album"].iter().collect();
+	let artwork_virtual_path = album_virtual_dir.join("covers").join("front.jpg");
+
+	let song = ctx
+		.index
+		.get_song(&album_virtual_dir.join("inside.mp3"))
+		.unwrap();
+	assert_eq!(
+		song.artwork,
+		Some(artwork_virtual_path.to_string_lossy().into_owned())
+	);
+
+	// References leaving the mount point are ignored
+	let song = ctx
+		.index
+		.get_song(&album_virtual_dir.join("outside.mp3"))
+		.unwrap();
+	assert_eq!(song.artwork, None);
+
+	let album = ctx.index.get_album_at(&album_virtual_dir).unwrap();
+	assert_eq!(
+		album.artwork,
+		Some(artwork_virtual_path.to_string_lossy().into_owned())
+	);
+}
+
 #[test]
 fn album_art_pattern_is_case_insensitive() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -231,3 +1086,335 @@ fn album_art_pattern_is_case_insensitive() {
 		);
 	}
 }
+
+#[test]
+fn unreadable_songs_are_skipped_by_default() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	std::fs::write(collection_dir.join("Corrupted.mp3"), b"not an mp3").unwrap();
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+
+	ctx.index.update().unwrap();
+
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	assert!(songs.is_empty());
+}
+
+#[test]
+fn can_keep_unreadable_songs() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	std::fs::write(collection_dir.join("Corrupted.mp3"), b"not an mp3").unwrap();
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			keep_unreadable_songs: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(songs[0].title, Some("Corrupted".to_owned()));
+	assert!(songs[0].metadata_error);
+}
+
+#[cfg(unix)]
+#[test]
+fn reports_non_utf8_paths() {
+	use std::os::unix::ffi::OsStrExt;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	let file_name = std::ffi::OsStr::from_bytes(b"Caf\xe9.mp3");
+	std::fs::copy(
+		"test-data/small-collection/Khemmis/Hunted/02 - Candlelight.mp3",
+		collection_dir.join(file_name),
+	)
+	.unwrap();
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.get_scan_status().non_utf8_paths, 1);
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	assert_eq!(songs.len(), 1);
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			skip_non_utf8_paths: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.get_scan_status().non_utf8_paths, 1);
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	assert!(songs.is_empty());
+}
+
+#[test]
+fn discards_years_out_of_range() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			min_year: Some(2010),
+			max_year: Some(2020),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.get_scan_status().invalid_years, 0);
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			max_year: Some(2000),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.get_scan_status().invalid_years, 13);
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	assert!(songs.iter().all(|s| s.year.is_none()));
+}
+
+#[test]
+fn path_pattern_fills_missing_tags() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	let album_dir = collection_dir.join("Khemmis").join("Hunted");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	std::fs::write(album_dir.join("03 - Three Gates.mp3"), b"not an mp3").unwrap();
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			keep_unreadable_songs: Some(true),
+			path_pattern: Some("{artist}/{album}/{track} - {title}".to_owned()),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(songs[0].artist, Some("Khemmis".to_owned()));
+	assert_eq!(songs[0].album, Some("Hunted".to_owned()));
+	assert_eq!(songs[0].track_number, Some(3));
+	assert_eq!(songs[0].title, Some("Three Gates".to_owned()));
+}
+
+#[test]
+fn reports_scan_progress() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+
+	assert_eq!(ctx.index.get_scan_status(), ScanStatus::default());
+	ctx.index.update().unwrap();
+
+	let status = ctx.index.get_scan_status();
+	assert!(!status.running);
+	assert_eq!(status.start_time, None);
+	assert_eq!(status.songs_found, 13);
+	assert!(status.directories_scanned > 0);
+	assert_eq!(ctx.index.cancel_scan(), status);
+}
+
+#[test]
+fn cancelling_stops_scan_in_progress() {
+	const NUM_ALBUMS: usize = 2000;
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("large-collection");
+	for i in 0..NUM_ALBUMS {
+		let album_dir = collection_dir.join(format!("Album {i}"));
+		std::fs::create_dir_all(&album_dir).unwrap();
+		std::fs::hard_link("test-data/formats/sample.mp3", album_dir.join("01.mp3")).unwrap();
+	}
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+
+	let index = ctx.index.clone();
+	let update = std::thread::spawn(move || index.update());
+	while ctx.index.get_scan_status().directories_scanned == 0 && !update.is_finished() {
+		std::thread::yield_now();
+	}
+	assert!(ctx.index.cancel_scan().cancel_requested);
+	update.join().unwrap().unwrap();
+
+	let status = ctx.index.get_scan_status();
+	assert!(!status.running);
+	assert!(!status.cancel_requested);
+	let cancelled_scan = ctx.index.get_scan_history()[0].clone();
+	assert!(cancelled_scan.cancelled);
+	assert!(cancelled_scan.songs_found < NUM_ALBUMS);
+
+	// The next scan starts over from scratch
+	ctx.index.update().unwrap();
+	let status = ctx.index.get_scan_status();
+	assert_eq!(status.songs_found, NUM_ALBUMS);
+	assert!(!ctx.index.get_scan_history()[0].cancelled);
+}
+
+#[test]
+fn initial_scan_completion_survives_restarts() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	assert!(!ctx.index.is_initial_scan_completed());
+	ctx.index.update().unwrap();
+	assert!(ctx.index.is_initial_scan_completed());
+
+	let restarted_index = Index::new(
+		ctx.db.clone(),
+		ctx.vfs_manager.clone(),
+		ctx.settings_manager.clone(),
+	);
+	assert!(restarted_index.is_initial_scan_completed());
+}
+
+#[test]
+fn update_records_collection_changes() {
+	let builder = test::ContextBuilder::new(test_name!());
+
+	let original_collection_dir: PathBuf = ["test-data", "small-collection"].iter().collect();
+	let test_collection_dir: PathBuf = builder.test_directory.join("small-collection");
+
+	let copy_options = fs_extra::dir::CopyOptions::new();
+	fs_extra::dir::copy(
+		&original_collection_dir,
+		&builder.test_directory,
+		&copy_options,
+	)
+	.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, test_collection_dir.to_str().unwrap())
+		.build();
+
+	ctx.index.update().unwrap();
+	let initial = ctx.index.get_changes_since(None).unwrap();
+	assert!(initial.full_sync);
+	assert_eq!(initial.added.len(), 13);
+	let generation = initial.generation;
+	assert!(generation.is_some());
+
+	ctx.index.update().unwrap();
+	let unchanged = ctx.index.get_changes_since(generation).unwrap();
+	assert_eq!(
+		unchanged,
+		SyncChanges {
+			generation,
+			..Default::default()
+		}
+	);
+
+	let removed_song = test_collection_dir.join("Khemmis/Hunted/05 - Hunted.mp3");
+	std::fs::remove_file(&removed_song).unwrap();
+	ctx.index.update().unwrap();
+	let changes = ctx.index.get_changes_since(generation).unwrap();
+	assert!(!changes.full_sync);
+	assert!(changes.generation > generation);
+	assert!(changes.added.is_empty());
+	assert!(changes.changed.is_empty());
+	let removed_virtual_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "05 - Hunted.mp3"]
+		.iter()
+		.collect();
+	assert_eq!(
+		changes.removed,
+		vec![removed_virtual_path.to_string_lossy().into_owned()]
+	);
+
+	// Changes are composed across every generation after the requested one
+	let removed_generation = changes.generation;
+	std::fs::copy(
+		original_collection_dir.join("Khemmis/Hunted/05 - Hunted.mp3"),
+		&removed_song,
+	)
+	.unwrap();
+	ctx.index.update().unwrap();
+	let changes = ctx.index.get_changes_since(removed_generation).unwrap();
+	assert_eq!(
+		changes.added,
+		vec![removed_virtual_path.to_string_lossy().into_owned()]
+	);
+	let changes = ctx.index.get_changes_since(generation).unwrap();
+	assert!(changes.added.is_empty());
+	assert!(changes.removed.is_empty());
+	assert!(changes.changed.is_empty());
+}
+
+#[test]
+fn can_get_generation_info() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	assert_eq!(
+		ctx.index.get_generation_info().unwrap(),
+		GenerationInfo::default()
+	);
+
+	ctx.index.update().unwrap();
+	let info = ctx.index.get_generation_info().unwrap();
+	assert_eq!(info.generation, ctx.index.get_generation().unwrap());
+	assert!(info.generation.is_some());
+	assert!(info.date.unwrap() > 0);
+}
+
+#[test]
+fn splits_cue_sheet_tracks() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/cue")
+		.build();
+
+	ctx.index.update().unwrap();
+
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	assert_eq!(songs.len(), 2);
+
+	let first_track_path: PathBuf = [TEST_MOUNT_NAME, "sample.cue#01"].iter().collect();
+	assert_eq!(songs[0].path, first_track_path.to_string_lossy().as_ref());
+	assert_eq!(songs[0].title, Some("First Track".to_owned()));
+	assert_eq!(songs[0].artist, Some("TEST CUE ARTIST".to_owned()));
+	assert_eq!(songs[0].album, Some("TEST CUE ALBUM".to_owned()));
+	assert_eq!(songs[0].track_number, Some(1));
+	assert_eq!(songs[0].start_offset_ms, Some(0));
+	assert_eq!(songs[0].end_offset_ms, Some(400));
+
+	assert_eq!(songs[1].title, Some("Second Track".to_owned()));
+	assert_eq!(songs[1].artist, Some("TEST GUEST ARTIST".to_owned()));
+	assert_eq!(songs[1].start_offset_ms, Some(400));
+	assert_eq!(songs[1].end_offset_ms, None);
+}
+
+#[test]
+fn stores_cue_sheet_track_segments() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/cue")
+		.build();
+
+	ctx.index.update().unwrap();
+
+	let track_path: PathBuf = [TEST_MOUNT_NAME, "sample.cue#02"].iter().collect();
+	let song = ctx.index.get_song(&track_path).unwrap();
+	let segment = song.cue_segment().unwrap();
+	let audio_path: PathBuf = ["test-data", "cue", "sample.flac"].iter().collect();
+	assert!(segment.audio_path.ends_with(audio_path));
+	assert_eq!(segment.start_ms, 400);
+	assert_eq!(segment.end_ms, None);
+}
@@ -1,16 +1,18 @@
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use super::cue;
 use crate::app::vfs::VFS;
 use crate::db::songs;
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum CollectionFile {
 	Directory(Directory),
 	Song(Song),
 }
 
-#[derive(Debug, PartialEq, Eq, Queryable, QueryableByName, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Queryable, QueryableByName, Serialize, Deserialize)]
 #[diesel(table_name = songs)]
 pub struct Song {
 	#[serde(skip_serializing, skip_deserializing)]
@@ -31,6 +33,25 @@ pub struct Song {
 	pub composer: Option<String>,
 	pub genre: Option<String>,
 	pub label: Option<String>,
+	// Position of cue sheet tracks within their audio file. `/audio` only serves this portion of
+	// the file for these tracks.
+	pub start_offset_ms: Option<i32>,
+	pub end_offset_ms: Option<i32>,
+	pub metadata_error: bool,
+	pub track_total: Option<i32>,
+	pub disc_total: Option<i32>,
+	#[serde(skip_serializing, skip_deserializing)]
+	pub mtime: Option<i64>,
+	// ReplayGain values, in dB for gains and relative to full scale for peaks
+	pub track_gain: Option<f32>,
+	pub track_peak: Option<f32>,
+	pub album_gain: Option<f32>,
+	pub album_peak: Option<f32>,
+	// Whether the duration was measured from the audio data instead of read from tags
+	pub duration_decoded: bool,
+	// Real path of the audio file containing cue sheet tracks
+	#[serde(skip_serializing, skip_deserializing)]
+	pub audio_path: Option<String>,
 }
 
 impl Song {
@@ -47,6 +68,16 @@ impl Song {
 		}
 		Some(self)
 	}
+
+	// Portion of its audio file covered by a cue sheet track, `None` for other songs
+	pub fn cue_segment(&self) -> Option<cue::Segment> {
+		let audio_path = self.audio_path.as_ref()?;
+		Some(cue::Segment {
+			audio_path: PathBuf::from(audio_path),
+			start_ms: self.start_offset_ms.unwrap_or_default() as u32,
+			end_ms: self.end_offset_ms.map(|e| e as u32),
+		})
+	}
 }
 
 #[derive(Debug, PartialEq, Eq, Queryable, Serialize, Deserialize)]
@@ -61,6 +92,8 @@ pub struct Directory {
 	pub album: Option<String>,
 	pub artwork: Option<String>,
 	pub date_added: i32,
+	// Total duration of the songs in this directory, in seconds
+	pub duration: Option<i64>,
 }
 
 impl Directory {
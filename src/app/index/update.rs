@@ -1,19 +1,24 @@
+use diesel::prelude::*;
 use log::{error, info};
-use std::time;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{self, SystemTime, UNIX_EPOCH};
 
 mod cleaner;
 mod collector;
 mod inserter;
 mod traverser;
 
-use crate::app::index::Index;
-use crate::app::vfs;
-use crate::db;
+use crate::app::index::{self, Index};
+use crate::app::{settings, vfs};
+use crate::db::{self, misc_settings, songs};
 
+pub use cleaner::CleanReport;
 use cleaner::Cleaner;
 use collector::Collector;
 use inserter::Inserter;
-use traverser::Traverser;
+use traverser::{KnownSongs, Traverser};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -23,16 +28,290 @@ pub enum Error {
 	Database(#[from] diesel::result::Error),
 	#[error(transparent)]
 	DatabaseConnection(#[from] db::Error),
+	#[error("An index update is already in progress")]
+	ScanInProgress,
 	#[error(transparent)]
 	Vfs(#[from] vfs::Error),
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanStatus {
+	pub running: bool,
+	pub start_time: Option<i32>,
+	pub directories_scanned: usize,
+	pub songs_found: usize,
+	// Files and directories whose path is not valid UTF-8
+	pub non_utf8_paths: usize,
+	// Songs indexed without a year because their tagged year is out of the configured range
+	pub invalid_years: usize,
+	pub cancel_requested: bool,
+}
+
+const MAX_SCAN_RECORDS: usize = 16; // Number of completed scans kept in the scan history
+
+// Summary of a completed index update
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanRecord {
+	pub start_time: i32,
+	pub duration_ms: u64,
+	pub directories_scanned: usize,
+	pub songs_found: usize,
+	pub cancelled: bool,
+}
+
+// Settings affecting the tags of indexed songs. Songs from a previous update are only reused
+// while these are unchanged. They are persisted alongside the settings as a JSON fingerprint, so
+// that unmodified files are not read again after a restart.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TagSettings {
+	album_art_pattern: String,
+	keep_unreadable_songs: bool,
+	path_pattern: String,
+	exact_durations: bool,
+	folder_name_albums: bool,
+	min_year: i32,
+	max_year: i32,
+}
+
+impl TagSettings {
+	fn fingerprint(&self) -> Option<String> {
+		serde_json::to_string(self).ok()
+	}
+}
+
+impl From<settings::Settings> for TagSettings {
+	fn from(s: settings::Settings) -> Self {
+		Self {
+			album_art_pattern: s.index_album_art_pattern,
+			keep_unreadable_songs: s.index_keep_unreadable_songs,
+			path_pattern: s.index_path_pattern,
+			exact_durations: s.index_exact_durations,
+			folder_name_albums: s.index_folder_name_albums,
+			min_year: s.index_min_year,
+			max_year: s.index_max_year,
+		}
+	}
+}
+
+// Progress of the index update in flight, shared with the threads performing it
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+	start_time: Mutex<Option<i32>>,
+	directories_scanned: AtomicUsize,
+	songs_found: AtomicUsize,
+	non_utf8_paths: AtomicUsize,
+	invalid_years: AtomicUsize,
+	cancel_requested: AtomicBool,
+}
+
+impl ScanProgress {
+	// Returns false, leaving progress untouched, when an update is already in flight
+	pub(super) fn begin(&self) -> bool {
+		let mut start_time = self.start_time.lock().unwrap();
+		if start_time.is_some() {
+			return false;
+		}
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs() as i32)
+			.unwrap_or_default();
+		*start_time = Some(now);
+		self.directories_scanned.store(0, Ordering::SeqCst);
+		self.songs_found.store(0, Ordering::SeqCst);
+		self.non_utf8_paths.store(0, Ordering::SeqCst);
+		self.invalid_years.store(0, Ordering::SeqCst);
+		self.cancel_requested.store(false, Ordering::SeqCst);
+		true
+	}
+
+	pub(super) fn end(&self) {
+		*self.start_time.lock().unwrap() = None;
+		self.cancel_requested.store(false, Ordering::SeqCst);
+	}
+
+	pub fn add_directory(&self, num_songs: usize) {
+		self.directories_scanned.fetch_add(1, Ordering::SeqCst);
+		self.songs_found.fetch_add(num_songs, Ordering::SeqCst);
+	}
+
+	pub fn add_non_utf8_path(&self) {
+		self.non_utf8_paths.fetch_add(1, Ordering::SeqCst);
+	}
+
+	pub fn add_invalid_year(&self) {
+		self.invalid_years.fetch_add(1, Ordering::SeqCst);
+	}
+
+	pub fn is_cancel_requested(&self) -> bool {
+		self.cancel_requested.load(Ordering::SeqCst)
+	}
+
+	fn status(&self) -> ScanStatus {
+		let start_time = *self.start_time.lock().unwrap();
+		ScanStatus {
+			running: start_time.is_some(),
+			start_time,
+			directories_scanned: self.directories_scanned.load(Ordering::SeqCst),
+			songs_found: self.songs_found.load(Ordering::SeqCst),
+			non_utf8_paths: self.non_utf8_paths.load(Ordering::SeqCst),
+			invalid_years: self.invalid_years.load(Ordering::SeqCst),
+			cancel_requested: self.is_cancel_requested(),
+		}
+	}
+}
+
 impl Index {
+	pub fn get_scan_status(&self) -> ScanStatus {
+		self.scan_progress.status()
+	}
+
+	// Returns the most recent scans first. History is not persisted across restarts.
+	pub fn get_scan_history(&self) -> Vec<ScanRecord> {
+		self.scan_history.lock().unwrap().iter().cloned().collect()
+	}
+
+	fn record_scan(&self, record: ScanRecord) {
+		let mut history = self.scan_history.lock().unwrap();
+		history.push_front(record);
+		history.truncate(MAX_SCAN_RECORDS);
+	}
+
+	// Stops the index update in flight, if any. Content indexed so far is kept.
+	pub fn cancel_scan(&self) -> ScanStatus {
+		let start_time = self.scan_progress.start_time.lock().unwrap();
+		if start_time.is_some() {
+			self.scan_progress
+				.cancel_requested
+				.store(true, Ordering::SeqCst);
+		}
+		drop(start_time);
+		self.scan_progress.status()
+	}
+
+	// Removes songs and directories which no longer exist, without scanning for new content.
+	// Cleaning counts as an index update, so it cannot run alongside one.
+	pub fn clean(&self) -> Result<CleanReport, Error> {
+		if !self.scan_progress.begin() {
+			return Err(Error::ScanInProgress);
+		}
+		let result = self.run_clean();
+		self.scan_progress.end();
+		result
+	}
+
+	fn run_clean(&self) -> Result<CleanReport, Error> {
+		let cleaner = Cleaner::new(self.db.clone(), self.vfs_manager.clone());
+		let report = cleaner.clean()?;
+		if report != CleanReport::default() {
+			if let Err(e) = self.record_generation() {
+				error!("Error while recording index generation: {}", e);
+			}
+		}
+		Ok(report)
+	}
+
+	fn read_tag_settings_fingerprint(&self) -> Result<Option<String>, Error> {
+		let mut connection = self.db.connect()?;
+		Ok(misc_settings::table
+			.select(misc_settings::index_tag_settings)
+			.get_result(&mut connection)?)
+	}
+
+	fn write_tag_settings_fingerprint(&self, fingerprint: Option<&str>) -> Result<(), Error> {
+		let mut connection = self.db.connect()?;
+		diesel::update(misc_settings::table)
+			.set(misc_settings::index_tag_settings.eq(fingerprint))
+			.execute(&mut connection)?;
+		Ok(())
+	}
+
+	fn read_known_songs(&self) -> Result<KnownSongs, Error> {
+		let mut connection = self.db.connect()?;
+		let known_songs: Vec<index::Song> = songs::table
+			.filter(songs::mtime.is_not_null())
+			.load(&mut connection)?;
+		Ok(known_songs.into_iter().collect())
+	}
+
+	// Only one update runs at a time. Triggers received before this call are merged into the
+	// update running by then.
 	pub fn update(&self) -> Result<(), Error> {
+		let began = self.scan_progress.begin();
+		// Clearing the flag after progress began means triggers always see an update pending
+		// or running, never neither
+		*self.pending_reindex.0.lock().unwrap() = false;
+		if !began {
+			return Err(Error::ScanInProgress);
+		}
+		let result = self.run_update();
+		self.scan_progress.end();
+		result
+	}
+
+	fn run_update(&self) -> Result<(), Error> {
 		let start = time::Instant::now();
 		info!("Beginning library index update");
 
 		let album_art_pattern = self.settings_manager.get_index_album_art_pattern().ok();
+		let album_artist_fallback = self
+			.settings_manager
+			.get_index_album_artist_fallback()
+			.unwrap_or_default();
+		let artwork_preference = self
+			.settings_manager
+			.get_index_artwork_preference()
+			.unwrap_or_default();
+		let keep_unreadable_songs = self
+			.settings_manager
+			.get_index_keep_unreadable_songs()
+			.unwrap_or_default();
+		let path_pattern = self
+			.settings_manager
+			.get_index_path_pattern()
+			.ok()
+			.flatten();
+		let exact_durations = self
+			.settings_manager
+			.get_index_exact_durations()
+			.unwrap_or_default();
+		let folder_name_albums = self
+			.settings_manager
+			.get_index_folder_name_albums()
+			.unwrap_or_default();
+		let skip_non_utf8_paths = self
+			.settings_manager
+			.get_index_skip_non_utf8_paths()
+			.unwrap_or_default();
+		let year_range = self
+			.settings_manager
+			.get_index_year_range()
+			.unwrap_or_default();
+
+		let fingerprint = self
+			.settings_manager
+			.read()
+			.ok()
+			.map(TagSettings::from)
+			.and_then(|t| t.fingerprint());
+		let previous_fingerprint = self.read_tag_settings_fingerprint().unwrap_or_else(|e| {
+			error!("Error while reading previous tag settings: {}", e);
+			None
+		});
+		let reuse_known_songs = fingerprint.is_some() && previous_fingerprint == fingerprint;
+		if !reuse_known_songs {
+			// Songs read with the previous settings must not be reused if this update is cut short
+			if let Err(e) = self.write_tag_settings_fingerprint(None) {
+				error!("Error while clearing previous tag settings: {}", e);
+			}
+		}
+		let known_songs = match reuse_known_songs {
+			true => self.read_known_songs().unwrap_or_else(|e| {
+				error!("Error while reading previously indexed songs: {}", e);
+				KnownSongs::new()
+			}),
+			false => KnownSongs::new(),
+		};
+		let known_songs = Arc::new(known_songs);
 
 		let cleaner = Cleaner::new(self.db.clone(), self.vfs_manager.clone());
 		cleaner.clean()?;
@@ -46,15 +325,34 @@ impl Index {
 
 		let (collect_sender, collect_receiver) = crossbeam_channel::unbounded();
 		let collector_thread = std::thread::spawn(move || {
-			let collector = Collector::new(collect_receiver, insert_sender, album_art_pattern);
+			let collector = Collector::new(
+				collect_receiver,
+				insert_sender,
+				album_art_pattern,
+				album_artist_fallback,
+				folder_name_albums,
+				artwork_preference,
+			);
 			collector.collect();
 		});
 
 		let vfs = self.vfs_manager.get_vfs()?;
+		let progress = self.scan_progress.clone();
 		let traverser_thread = std::thread::spawn(move || {
 			let mounts = vfs.mounts();
-			let traverser = Traverser::new(collect_sender);
-			traverser.traverse(mounts.iter().map(|p| p.source.clone()).collect());
+			let traverser = Traverser::new(
+				collect_sender,
+				keep_unreadable_songs,
+				path_pattern,
+				exact_durations,
+				skip_non_utf8_paths,
+				year_range,
+				progress,
+			);
+			traverser.traverse(
+				mounts.iter().map(|p| p.source.clone()).collect(),
+				known_songs,
+			);
 		});
 
 		if let Err(e) = traverser_thread.join() {
@@ -69,6 +367,29 @@ impl Index {
 			error!("Error joining on inserter thread: {:?}", e);
 		}
 
+		if self.scan_progress.is_cancel_requested() {
+			info!("Library index update was cancelled");
+		}
+
+		if !self.scan_progress.is_cancel_requested() {
+			if let Err(e) = self.write_tag_settings_fingerprint(fingerprint.as_deref()) {
+				error!("Error while recording tag settings: {}", e);
+			}
+		}
+
+		let status = self.scan_progress.status();
+		self.record_scan(ScanRecord {
+			start_time: status.start_time.unwrap_or_default(),
+			duration_ms: start.elapsed().as_millis() as u64,
+			directories_scanned: status.directories_scanned,
+			songs_found: status.songs_found,
+			cancelled: status.cancel_requested,
+		});
+
+		if let Err(e) = self.record_generation() {
+			error!("Error while recording index generation: {}", e);
+		}
+
 		info!(
 			"Library index update took {} seconds",
 			start.elapsed().as_millis() as f32 / 1000.0
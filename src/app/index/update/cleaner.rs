@@ -1,5 +1,6 @@
 use diesel::prelude::*;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 use crate::app::vfs;
@@ -19,6 +20,12 @@ pub enum Error {
 	Vfs(#[from] vfs::Error),
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CleanReport {
+	pub directories_removed: usize,
+	pub songs_removed: usize,
+}
+
 pub struct Cleaner {
 	db: DB,
 	vfs_manager: vfs::Manager,
@@ -29,7 +36,7 @@ impl Cleaner {
 		Self { db, vfs_manager }
 	}
 
-	pub fn clean(&self) -> Result<(), Error> {
+	pub fn clean(&self) -> Result<CleanReport, Error> {
 		let vfs = self.vfs_manager.get_vfs()?;
 
 		let all_directories: Vec<String> = {
@@ -39,9 +46,12 @@ impl Cleaner {
 				.load(&mut connection)?
 		};
 
-		let all_songs: Vec<String> = {
+		// Cue sheet tracks are missing when the audio file containing them is
+		let all_songs: Vec<(String, Option<String>)> = {
 			let mut connection = self.db.connect()?;
-			songs::table.select(songs::path).load(&mut connection)?
+			songs::table
+				.select((songs::path, songs::audio_path))
+				.load(&mut connection)?
 		};
 
 		let list_missing_directories = || {
@@ -57,10 +67,12 @@ impl Cleaner {
 		let list_missing_songs = || {
 			all_songs
 				.par_iter()
-				.filter(|ref song_path| {
-					let path = Path::new(&song_path);
-					!path.exists() || vfs.real_to_virtual(path).is_err()
+				.filter(|(song_path, audio_path)| {
+					let path = Path::new(song_path);
+					let audio_path = audio_path.as_deref().map(Path::new).unwrap_or(path);
+					!audio_path.exists() || vfs.real_to_virtual(path).is_err()
 				})
+				.map(|(song_path, _)| song_path)
 				.collect::<Vec<_>>()
 		};
 
@@ -80,6 +92,9 @@ impl Cleaner {
 			}
 		}
 
-		Ok(())
+		Ok(CleanReport {
+			directories_removed: missing_directories.len(),
+			songs_removed: missing_songs.len(),
+		})
 	}
 }
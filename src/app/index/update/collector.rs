@@ -3,11 +3,16 @@ use log::error;
 use regex::Regex;
 
 use super::*;
+use crate::app::index::VARIOUS_ARTISTS;
+use crate::app::settings::{AlbumArtistFallback, ArtworkPreference};
 
 pub struct Collector {
 	receiver: Receiver<traverser::Directory>,
 	sender: Sender<inserter::Item>,
 	album_art_pattern: Option<Regex>,
+	album_artist_fallback: AlbumArtistFallback,
+	folder_name_albums: bool,
+	artwork_preference: ArtworkPreference,
 }
 
 impl Collector {
@@ -15,11 +20,17 @@ impl Collector {
 		receiver: Receiver<traverser::Directory>,
 		sender: Sender<inserter::Item>,
 		album_art_pattern: Option<Regex>,
+		album_artist_fallback: AlbumArtistFallback,
+		folder_name_albums: bool,
+		artwork_preference: ArtworkPreference,
 	) -> Self {
 		Self {
 			receiver,
 			sender,
 			album_art_pattern,
+			album_artist_fallback,
+			folder_name_albums,
+			artwork_preference,
 		}
 	}
 
@@ -29,21 +40,61 @@ impl Collector {
 		}
 	}
 
-	fn collect_directory(&self, directory: traverser::Directory) {
+	fn collect_directory(&self, mut directory: traverser::Directory) {
+		// Songs without any album tag are grouped into an album named after their directory
+		let is_untagged_album = !directory.songs.is_empty()
+			&& directory.songs.iter().all(|s| s.metadata.album.is_none());
+		if self.folder_name_albums && is_untagged_album {
+			let folder_name = directory
+				.path
+				.file_name()
+				.map(|n| n.to_string_lossy().to_string());
+			for song in &mut directory.songs {
+				song.metadata.album = folder_name.clone();
+			}
+		}
+
 		let mut directory_album = None;
 		let mut directory_year = None;
 		let mut directory_artist = None;
 		let mut inconsistent_directory_album = false;
 		let mut inconsistent_directory_year = false;
 		let mut inconsistent_directory_artist = false;
+		// Songs without a known duration are left out of the total
+		let directory_duration = if directory.songs.is_empty() {
+			None
+		} else {
+			Some(
+				directory
+					.songs
+					.iter()
+					.filter_map(|s| s.metadata.duration)
+					.map(i64::from)
+					.sum(),
+			)
+		};
 
 		let directory_artwork = self.get_artwork(&directory);
+		// Image files named by tags are only used when no other artwork is available
+		let referenced_artwork = directory
+			.songs
+			.iter()
+			.find_map(|song| song.metadata.artwork_reference.clone());
 		let directory_path_string = directory.path.to_string_lossy().to_string();
 		let directory_parent_string = directory.parent.map(|p| p.to_string_lossy().to_string());
+		let fallback_artist = match self.album_artist_fallback {
+			AlbumArtistFallback::DirectoryName => directory
+				.path
+				.parent()
+				.and_then(|p| p.file_name())
+				.map(|n| n.to_string_lossy().to_string()),
+			_ => None,
+		};
 
 		for song in directory.songs {
-			let tags = song.metadata;
 			let path_string = song.path.to_string_lossy().to_string();
+			let audio_path_string = song.audio_path().to_string_lossy().to_string();
+			let tags = song.metadata;
 
 			if tags.year.is_some() {
 				inconsistent_directory_year |=
@@ -61,6 +112,10 @@ impl Collector {
 				inconsistent_directory_artist |=
 					directory_artist.is_some() && directory_artist != tags.album_artist;
 				directory_artist = tags.album_artist.as_ref().cloned();
+			} else if self.album_artist_fallback == AlbumArtistFallback::DirectoryName {
+				inconsistent_directory_artist |=
+					directory_artist.is_some() && directory_artist != fallback_artist;
+				directory_artist = fallback_artist.as_ref().cloned();
 			} else if tags.artist.is_some() {
 				inconsistent_directory_artist |=
 					directory_artist.is_some() && directory_artist != tags.artist;
@@ -68,9 +123,12 @@ impl Collector {
 			}
 
 			let artwork_path = if tags.has_artwork {
-				Some(path_string.clone())
+				Some(audio_path_string)
 			} else {
-				directory_artwork.as_ref().cloned()
+				directory_artwork
+					.as_ref()
+					.cloned()
+					.or_else(|| tags.artwork_reference.clone())
 			};
 
 			if let Err(e) = self.sender.send(inserter::Item::Song(inserter::Song {
@@ -89,6 +147,24 @@ impl Collector {
 				composer: tags.composer,
 				genre: tags.genre,
 				label: tags.label,
+				start_offset_ms: song.cue_segment.as_ref().map(|s| s.start_ms as i32),
+				end_offset_ms: song
+					.cue_segment
+					.as_ref()
+					.and_then(|s| s.end_ms.map(|e| e as i32)),
+				metadata_error: song.metadata_error,
+				mtime: song.mtime,
+				track_gain: tags.replay_gain.track_gain,
+				track_peak: tags.replay_gain.track_peak,
+				album_gain: tags.replay_gain.album_gain,
+				album_peak: tags.replay_gain.album_peak,
+				track_total: tags.track_total.map(|n| n as i32),
+				disc_total: tags.disc_total.map(|n| n as i32),
+				duration_decoded: song.duration_decoded,
+				audio_path: song
+					.cue_segment
+					.as_ref()
+					.map(|s| s.audio_path.to_string_lossy().into_owned()),
 			})) {
 				error!("Error while sending song from collector: {}", e);
 			}
@@ -101,7 +177,10 @@ impl Collector {
 			directory_album = None;
 		}
 		if inconsistent_directory_artist {
-			directory_artist = None;
+			directory_artist = match self.album_artist_fallback {
+				AlbumArtistFallback::VariousArtists => Some(VARIOUS_ARTISTS.to_owned()),
+				_ => None,
+			};
 		}
 
 		if let Err(e) = self
@@ -109,11 +188,12 @@ impl Collector {
 			.send(inserter::Item::Directory(inserter::Directory {
 				path: directory_path_string,
 				parent: directory_parent_string,
-				artwork: directory_artwork,
+				artwork: directory_artwork.or(referenced_artwork),
 				album: directory_album,
 				artist: directory_artist,
 				year: directory_year,
 				date_added: directory.created,
+				duration: directory_duration,
 			})) {
 			error!("Error while sending directory from collector: {}", e);
 		}
@@ -138,12 +218,261 @@ impl Collector {
 
 		let embedded_artwork = directory.songs.iter().find_map(|song| {
 			if song.metadata.has_artwork {
-				Some(song.path.to_string_lossy().to_string())
+				Some(song.audio_path().to_string_lossy().to_string())
 			} else {
 				None
 			}
 		});
 
-		regex_artwork.or(embedded_artwork)
+		match self.artwork_preference {
+			ArtworkPreference::Folder => regex_artwork.or(embedded_artwork),
+			ArtworkPreference::Embedded => embedded_artwork.or(regex_artwork),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::*;
+	use crate::app::index::metadata::SongTags;
+
+	fn song(path: PathBuf, metadata: SongTags) -> traverser::Song {
+		traverser::Song {
+			path,
+			metadata,
+			cue_segment: None,
+			metadata_error: false,
+			mtime: None,
+			duration_decoded: false,
+		}
+	}
+
+	fn directory(path: PathBuf, songs: Vec<traverser::Song>) -> traverser::Directory {
+		traverser::Directory {
+			parent: path.parent().map(|p| p.to_owned()),
+			path,
+			songs,
+			other_files: Vec::new(),
+			created: 0,
+		}
+	}
+
+	// Runs a collector over a single directory and returns everything it sent to the inserter
+	fn collect(
+		directory: traverser::Directory,
+		album_art_pattern: Option<Regex>,
+		fallback: AlbumArtistFallback,
+		folder_name_albums: bool,
+		preference: ArtworkPreference,
+	) -> Vec<inserter::Item> {
+		let (directory_sender, directory_receiver) = crossbeam_channel::unbounded();
+		let (item_sender, item_receiver) = crossbeam_channel::unbounded();
+		directory_sender.send(directory).unwrap();
+		drop(directory_sender);
+
+		Collector::new(
+			directory_receiver,
+			item_sender,
+			album_art_pattern,
+			fallback,
+			folder_name_albums,
+			preference,
+		)
+		.collect();
+
+		item_receiver.iter().collect()
+	}
+
+	fn collect_directory_artist(
+		fallback: AlbumArtistFallback,
+		artists: &[(Option<&str>, Option<&str>)],
+	) -> Option<String> {
+		let path: PathBuf = ["Compilations", "Summer Hits"].iter().collect();
+		let songs = artists
+			.iter()
+			.enumerate()
+			.map(|(i, (artist, album_artist))| {
+				let metadata = SongTags {
+					artist: artist.map(|a| a.to_owned()),
+					album_artist: album_artist.map(|a| a.to_owned()),
+					..Default::default()
+				};
+				song(path.join(format!("{}.mp3", i)), metadata)
+			})
+			.collect();
+
+		let directory = directory(path, songs);
+		collect(
+			directory,
+			None,
+			fallback,
+			false,
+			ArtworkPreference::default(),
+		)
+		.into_iter()
+		.find_map(|item| match item {
+			inserter::Item::Directory(d) => Some(d.artist),
+			_ => None,
+		})
+		.unwrap()
+	}
+
+	#[test]
+	fn artist_fallback_uses_song_artist() {
+		let fallback = AlbumArtistFallback::Artist;
+		assert_eq!(
+			collect_directory_artist(
+				fallback,
+				&[(Some("Khemmis"), None), (Some("Khemmis"), None)]
+			),
+			Some("Khemmis".to_owned())
+		);
+		assert_eq!(
+			collect_directory_artist(
+				fallback,
+				&[(Some("Khemmis"), None), (Some("Tobokegao"), None)]
+			),
+			None
+		);
+	}
+
+	#[test]
+	fn various_artists_fallback_groups_mixed_artists() {
+		let fallback = AlbumArtistFallback::VariousArtists;
+		assert_eq!(
+			collect_directory_artist(
+				fallback,
+				&[(Some("Khemmis"), None), (Some("Khemmis"), None)]
+			),
+			Some("Khemmis".to_owned())
+		);
+		assert_eq!(
+			collect_directory_artist(
+				fallback,
+				&[(Some("Khemmis"), None), (Some("Tobokegao"), None)]
+			),
+			Some(VARIOUS_ARTISTS.to_owned())
+		);
+	}
+
+	#[test]
+	fn directory_name_fallback_uses_parent_folder() {
+		let fallback = AlbumArtistFallback::DirectoryName;
+		assert_eq!(
+			collect_directory_artist(
+				fallback,
+				&[(Some("Khemmis"), None), (Some("Tobokegao"), None)]
+			),
+			Some("Compilations".to_owned())
+		);
+	}
+
+	#[test]
+	fn album_artist_tag_takes_precedence_over_fallback() {
+		for fallback in [
+			AlbumArtistFallback::Artist,
+			AlbumArtistFallback::VariousArtists,
+			AlbumArtistFallback::DirectoryName,
+		] {
+			assert_eq!(
+				collect_directory_artist(
+					fallback,
+					&[
+						(Some("Khemmis"), Some("DJ Mixer")),
+						(Some("Tobokegao"), Some("DJ Mixer"))
+					]
+				),
+				Some("DJ Mixer".to_owned())
+			);
+		}
+	}
+
+	fn collect_albums(folder_name_albums: bool, albums: &[Option<&str>]) -> Vec<Option<String>> {
+		let path: PathBuf = ["Khemmis", "Loose Tracks"].iter().collect();
+		let songs = albums
+			.iter()
+			.enumerate()
+			.map(|(i, album)| {
+				let metadata = SongTags {
+					album: album.map(|a| a.to_owned()),
+					..Default::default()
+				};
+				song(path.join(format!("{}.mp3", i)), metadata)
+			})
+			.collect();
+
+		let directory = directory(path, songs);
+		let fallback = AlbumArtistFallback::Artist;
+		let preference = ArtworkPreference::default();
+		collect(directory, None, fallback, folder_name_albums, preference)
+			.into_iter()
+			.map(|item| match item {
+				inserter::Item::Song(s) => s.album,
+				inserter::Item::Directory(d) => d.album,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn folder_name_albums_group_untagged_songs() {
+		let folder_album = Some("Loose Tracks".to_owned());
+		assert_eq!(collect_albums(false, &[None, None]), vec![None, None, None]);
+		assert_eq!(
+			collect_albums(true, &[None, None]),
+			vec![folder_album.clone(), folder_album.clone(), folder_album]
+		);
+	}
+
+	#[test]
+	fn folder_name_albums_ignore_tagged_songs() {
+		let hunted = Some("Hunted".to_owned());
+		assert_eq!(
+			collect_albums(true, &[Some("Hunted"), None]),
+			vec![hunted.clone(), None, hunted]
+		);
+	}
+
+	fn collect_directory_artwork(preference: ArtworkPreference) -> Option<String> {
+		let path: PathBuf = ["Khemmis", "Hunted"].iter().collect();
+		let metadata = SongTags {
+			has_artwork: true,
+			..Default::default()
+		};
+		let songs = vec![song(path.join("01.mp3"), metadata)];
+
+		let directory = traverser::Directory {
+			other_files: vec![path.join("Folder.jpg")],
+			..directory(path, songs)
+		};
+		let pattern = Regex::new("Folder.jpg").ok();
+		collect(
+			directory,
+			pattern,
+			AlbumArtistFallback::Artist,
+			false,
+			preference,
+		)
+		.into_iter()
+		.find_map(|item| match item {
+			inserter::Item::Directory(d) => Some(d.artwork),
+			_ => None,
+		})
+		.unwrap()
+	}
+
+	#[test]
+	fn artwork_preference_orders_directory_artwork() {
+		let folder_path: PathBuf = ["Khemmis", "Hunted", "Folder.jpg"].iter().collect();
+		let song_path: PathBuf = ["Khemmis", "Hunted", "01.mp3"].iter().collect();
+		assert_eq!(
+			collect_directory_artwork(ArtworkPreference::Folder),
+			Some(folder_path.to_string_lossy().to_string())
+		);
+		assert_eq!(
+			collect_directory_artwork(ArtworkPreference::Embedded),
+			Some(song_path.to_string_lossy().to_string())
+		);
 	}
 }
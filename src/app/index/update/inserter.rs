@@ -1,8 +1,10 @@
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{Receiver, RecvTimeoutError};
 use diesel::prelude::*;
 use log::error;
+use std::time::{Duration, Instant};
 
 use crate::db::{directories, songs, DB};
+use crate::utils::read_env_var;
 
 const INDEX_BUILDING_INSERT_BUFFER_SIZE: usize = 1000; // Insertions in each transaction
 
@@ -24,6 +26,18 @@ pub struct Song {
 	pub composer: Option<String>,
 	pub genre: Option<String>,
 	pub label: Option<String>,
+	pub start_offset_ms: Option<i32>,
+	pub end_offset_ms: Option<i32>,
+	pub metadata_error: bool,
+	pub track_total: Option<i32>,
+	pub disc_total: Option<i32>,
+	pub mtime: Option<i64>,
+	pub track_gain: Option<f32>,
+	pub track_peak: Option<f32>,
+	pub album_gain: Option<f32>,
+	pub album_peak: Option<f32>,
+	pub duration_decoded: bool,
+	pub audio_path: Option<String>,
 }
 
 #[derive(Debug, Insertable)]
@@ -36,8 +50,10 @@ pub struct Directory {
 	pub album: Option<String>,
 	pub artwork: Option<String>,
 	pub date_added: i32,
+	pub duration: Option<i64>,
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum Item {
 	Directory(Directory),
 	Song(Song),
@@ -48,10 +64,25 @@ pub struct Inserter {
 	new_directories: Vec<Directory>,
 	new_songs: Vec<Song>,
 	db: DB,
+	// When set, pending insertions are also written at this interval so that
+	// slow scans make their results visible before the buffers fill up
+	flush_interval: Option<Duration>,
+	last_flush: Instant,
 }
 
 impl Inserter {
 	pub fn new(db: DB, receiver: Receiver<Item>) -> Self {
+		let flush_interval = read_env_var("POLARIS_INDEX_FLUSH_INTERVAL_SECONDS")
+			.filter(|s: &u64| *s > 0)
+			.map(Duration::from_secs);
+		Self::with_flush_interval(db, receiver, flush_interval)
+	}
+
+	fn with_flush_interval(
+		db: DB,
+		receiver: Receiver<Item>,
+		flush_interval: Option<Duration>,
+	) -> Self {
 		let new_directories = Vec::with_capacity(INDEX_BUILDING_INSERT_BUFFER_SIZE);
 		let new_songs = Vec::with_capacity(INDEX_BUILDING_INSERT_BUFFER_SIZE);
 		Self {
@@ -59,12 +90,38 @@ impl Inserter {
 			new_directories,
 			new_songs,
 			db,
+			flush_interval,
+			last_flush: Instant::now(),
 		}
 	}
 
 	pub fn insert(&mut self) {
-		while let Ok(item) = self.receiver.recv() {
-			self.insert_item(item);
+		let Some(flush_interval) = self.flush_interval else {
+			while let Ok(item) = self.receiver.recv() {
+				self.insert_item(item);
+			}
+			return;
+		};
+
+		loop {
+			let timeout = flush_interval.saturating_sub(self.last_flush.elapsed());
+			match self.receiver.recv_timeout(timeout) {
+				Ok(item) => self.insert_item(item),
+				Err(RecvTimeoutError::Timeout) => (),
+				Err(RecvTimeoutError::Disconnected) => break,
+			}
+			if self.last_flush.elapsed() >= flush_interval {
+				self.flush();
+			}
+		}
+	}
+
+	fn flush(&mut self) {
+		if !self.new_directories.is_empty() {
+			self.flush_directories();
+		}
+		if !self.new_songs.is_empty() {
+			self.flush_songs();
 		}
 	}
 
@@ -96,6 +153,7 @@ impl Inserter {
 			error!("Could not insert new directories in database");
 		}
 		self.new_directories.clear();
+		self.last_flush = Instant::now();
 	}
 
 	fn flush_songs(&mut self) {
@@ -109,16 +167,60 @@ impl Inserter {
 			error!("Could not insert new songs in database");
 		}
 		self.new_songs.clear();
+		self.last_flush = Instant::now();
 	}
 }
 
 impl Drop for Inserter {
 	fn drop(&mut self) {
-		if !self.new_directories.is_empty() {
-			self.flush_directories();
-		}
-		if !self.new_songs.is_empty() {
-			self.flush_songs();
+		self.flush();
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	#[test]
+	fn flushes_insertions_at_interval() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let (sender, receiver) = crossbeam_channel::unbounded();
+		let db = ctx.db.clone();
+		let insertion_thread = std::thread::spawn(move || {
+			let flush_interval = Some(Duration::from_millis(50));
+			Inserter::with_flush_interval(db, receiver, flush_interval).insert();
+		});
+
+		sender
+			.send(Item::Directory(Directory {
+				path: "Khemmis".to_owned(),
+				parent: None,
+				artist: None,
+				year: None,
+				album: None,
+				artwork: None,
+				date_added: 0,
+				duration: None,
+			}))
+			.unwrap();
+
+		// The directory is written while the channel is still open and the buffer is not full
+		let count_directories = || -> i64 {
+			let mut connection = ctx.db.connect().unwrap();
+			directories::table
+				.count()
+				.get_result(&mut connection)
+				.unwrap()
+		};
+		let deadline = Instant::now() + Duration::from_secs(5);
+		while count_directories() == 0 && Instant::now() < deadline {
+			std::thread::sleep(Duration::from_millis(10));
 		}
+		assert_eq!(count_directories(), 1);
+
+		drop(sender);
+		insertion_thread.join().unwrap();
 	}
 }
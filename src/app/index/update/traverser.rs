@@ -1,20 +1,130 @@
 use crossbeam_channel::{self, Receiver, Sender};
-use log::{error, info};
+use log::{error, info, warn};
 use std::cmp::min;
+use std::collections::HashMap;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, UNIX_EPOCH};
 
-use crate::app::index::metadata::{self, SongTags};
+use crate::app::index::metadata::{self, ReplayGain, SongTags};
+use crate::app::index::PathPattern;
+use crate::app::index::{self, cue};
+use crate::app::settings::YearRange;
+use crate::utils;
 
-#[derive(Debug)]
+use super::ScanProgress;
+
+#[derive(Clone, Debug)]
 pub struct Song {
 	pub path: PathBuf,
 	pub metadata: SongTags,
+	pub cue_segment: Option<cue::Segment>,
+	pub metadata_error: bool,
+	// Modification time of the file, in milliseconds since the Unix epoch
+	pub mtime: Option<i64>,
+	pub duration_decoded: bool,
+}
+
+// Songs from a previous update. The tags of a song can depend on the other songs in its
+// directory, so they are only reused while none of these songs were added, removed or modified.
+#[derive(Default)]
+pub struct KnownSongs {
+	songs: HashMap<PathBuf, Song>,
+	num_songs_per_directory: HashMap<PathBuf, usize>,
+}
+
+impl KnownSongs {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn get(&self, path: &Path) -> Option<&Song> {
+		self.songs.get(path)
+	}
+
+	// Files are listed with their modification time
+	fn is_directory_unchanged(&self, directory: &Path, files: &[(PathBuf, Option<i64>)]) -> bool {
+		let mut num_songs = 0;
+		for (path, mtime) in files {
+			if utils::get_audio_format(path).is_none() {
+				continue;
+			}
+			match self.songs.get(path) {
+				Some(song) if mtime.is_some() && song.mtime == *mtime => num_songs += 1,
+				_ => return false,
+			}
+		}
+		let num_known_songs = self.num_songs_per_directory.get(directory).copied();
+		num_known_songs.unwrap_or_default() == num_songs
+	}
+}
+
+impl FromIterator<index::Song> for KnownSongs {
+	fn from_iter<I: IntoIterator<Item = index::Song>>(iter: I) -> Self {
+		let mut known_songs = Self::new();
+		for song in iter {
+			let path = PathBuf::from(&song.path);
+			if let Some(directory) = path.parent() {
+				*known_songs
+					.num_songs_per_directory
+					.entry(directory.to_owned())
+					.or_default() += 1;
+			}
+			known_songs.songs.insert(path, song.into());
+		}
+		known_songs
+	}
+}
+
+impl From<index::Song> for Song {
+	fn from(song: index::Song) -> Self {
+		let has_artwork = song.artwork.as_ref() == Some(&song.path);
+		let artwork_reference = song.artwork.filter(|_| !has_artwork);
+		Self {
+			path: PathBuf::from(song.path),
+			metadata: SongTags {
+				disc_number: song.disc_number.map(|n| n as u32),
+				disc_total: song.disc_total.map(|n| n as u32),
+				track_number: song.track_number.map(|n| n as u32),
+				track_total: song.track_total.map(|n| n as u32),
+				title: song.title,
+				duration: song.duration.map(|n| n as u32),
+				artist: song.artist,
+				album_artist: song.album_artist,
+				album: song.album,
+				year: song.year,
+				has_artwork,
+				artwork_reference,
+				lyricist: song.lyricist,
+				composer: song.composer,
+				genre: song.genre,
+				label: song.label,
+				replay_gain: ReplayGain {
+					track_gain: song.track_gain,
+					track_peak: song.track_peak,
+					album_gain: song.album_gain,
+					album_peak: song.album_peak,
+				},
+			},
+			cue_segment: None,
+			metadata_error: song.metadata_error,
+			mtime: song.mtime,
+			duration_decoded: song.duration_decoded,
+		}
+	}
+}
+
+impl Song {
+	pub fn audio_path(&self) -> &Path {
+		match &self.cue_segment {
+			Some(segment) => &segment.audio_path,
+			None => &self.path,
+		}
+	}
 }
 
 #[derive(Debug)]
@@ -28,20 +138,43 @@ pub struct Directory {
 
 pub struct Traverser {
 	directory_sender: Sender<Directory>,
+	keep_unreadable_songs: bool,
+	path_pattern: Option<PathPattern>,
+	exact_durations: bool,
+	skip_non_utf8_paths: bool,
+	year_range: YearRange,
+	progress: Arc<ScanProgress>,
 }
 
 #[derive(Debug)]
 struct WorkItem {
+	root: PathBuf,
 	parent: Option<PathBuf>,
 	path: PathBuf,
 }
 
 impl Traverser {
-	pub fn new(directory_sender: Sender<Directory>) -> Self {
-		Self { directory_sender }
+	pub fn new(
+		directory_sender: Sender<Directory>,
+		keep_unreadable_songs: bool,
+		path_pattern: Option<PathPattern>,
+		exact_durations: bool,
+		skip_non_utf8_paths: bool,
+		year_range: YearRange,
+		progress: Arc<ScanProgress>,
+	) -> Self {
+		Self {
+			directory_sender,
+			keep_unreadable_songs,
+			path_pattern,
+			exact_durations,
+			skip_non_utf8_paths,
+			year_range,
+			progress,
+		}
 	}
 
-	pub fn traverse(&self, roots: Vec<PathBuf>) {
+	pub fn traverse(&self, roots: Vec<PathBuf>, known_songs: Arc<KnownSongs>) {
 		let num_pending_work_items = Arc::new(AtomicUsize::new(roots.len()));
 		let (work_item_sender, work_item_receiver) = crossbeam_channel::unbounded();
 
@@ -58,12 +191,26 @@ impl Traverser {
 			let work_item_receiver = work_item_receiver.clone();
 			let directory_sender = self.directory_sender.clone();
 			let num_pending_work_items = num_pending_work_items.clone();
+			let keep_unreadable_songs = self.keep_unreadable_songs;
+			let path_pattern = self.path_pattern.clone();
+			let exact_durations = self.exact_durations;
+			let skip_non_utf8_paths = self.skip_non_utf8_paths;
+			let year_range = self.year_range;
+			let known_songs = known_songs.clone();
+			let progress = self.progress.clone();
 			threads.push(thread::spawn(move || {
 				let worker = Worker {
 					work_item_sender,
 					work_item_receiver,
 					directory_sender,
 					num_pending_work_items,
+					keep_unreadable_songs,
+					path_pattern,
+					exact_durations,
+					skip_non_utf8_paths,
+					year_range,
+					known_songs,
+					progress,
 				};
 				worker.run();
 			}));
@@ -71,6 +218,7 @@ impl Traverser {
 
 		for root in roots {
 			let work_item = WorkItem {
+				root: root.clone(),
 				parent: None,
 				path: root,
 			};
@@ -92,6 +240,13 @@ struct Worker {
 	work_item_receiver: Receiver<WorkItem>,
 	directory_sender: Sender<Directory>,
 	num_pending_work_items: Arc<AtomicUsize>,
+	keep_unreadable_songs: bool,
+	path_pattern: Option<PathPattern>,
+	exact_durations: bool,
+	skip_non_utf8_paths: bool,
+	year_range: YearRange,
+	known_songs: Arc<KnownSongs>,
+	progress: Arc<ScanProgress>,
 }
 
 impl Worker {
@@ -104,7 +259,7 @@ impl Worker {
 
 	fn find_work_item(&self) -> Option<WorkItem> {
 		loop {
-			if self.is_all_work_done() {
+			if self.is_all_work_done() || self.progress.is_cancel_requested() {
 				return None;
 			}
 			if let Ok(w) = self
@@ -130,6 +285,7 @@ impl Worker {
 	}
 
 	fn emit_directory(&self, directory: Directory) {
+		self.progress.add_directory(directory.songs.len());
 		self.directory_sender.send(directory).unwrap();
 	}
 
@@ -147,6 +303,7 @@ impl Worker {
 		};
 
 		let mut sub_directories = Vec::new();
+		let mut files = Vec::new();
 		let mut songs = Vec::new();
 		let mut other_files = Vec::new();
 
@@ -163,12 +320,104 @@ impl Worker {
 				}
 			};
 
+			// Such paths can only be indexed under a lossy version of their name
+			if path.to_str().is_none() {
+				self.progress.add_non_utf8_path();
+				if self.skip_non_utf8_paths {
+					warn!(
+						"Skipping path which is not valid UTF-8: `{}`",
+						path.display()
+					);
+					continue;
+				}
+				warn!(
+					"Indexing path which is not valid UTF-8: `{}`",
+					path.display()
+				);
+			}
+
 			if path.is_dir() {
 				sub_directories.push(path);
-			} else if let Some(metadata) = metadata::read(&path) {
-				songs.push(Song { path, metadata });
-			} else {
-				other_files.push(path);
+				continue;
+			}
+
+			let mtime = Self::get_date_modified(&path);
+			files.push((path, mtime));
+		}
+
+		let reuse_known_songs = self
+			.known_songs
+			.is_directory_unchanged(&work_item.path, &files);
+
+		for (path, mtime) in files {
+			if reuse_known_songs {
+				if let Some(known_song) = self.known_songs.get(&path) {
+					let mut song = known_song.clone();
+					// Referenced artwork may have been deleted without modifying the song
+					song.metadata.artwork_reference = song
+						.metadata
+						.artwork_reference
+						.filter(|a| Path::new(a).is_file());
+					songs.push(song);
+					continue;
+				}
+			}
+
+			match metadata::read(&path) {
+				Ok(Some(mut metadata)) => {
+					if let Some(pattern) = &self.path_pattern {
+						pattern.apply(&path, &mut metadata);
+					}
+					let mut duration_decoded = false;
+					if self.exact_durations && metadata.duration.is_none() {
+						metadata.duration = metadata::decode_duration(&path);
+						duration_decoded = metadata.duration.is_some();
+					}
+					metadata.artwork_reference = metadata.artwork_reference.and_then(|r| {
+						Self::resolve_artwork_reference(&work_item.root, &work_item.path, &r)
+							.map(|p| p.to_string_lossy().into_owned())
+					});
+					songs.push(Song {
+						path,
+						metadata,
+						cue_segment: None,
+						metadata_error: false,
+						mtime,
+						duration_decoded,
+					})
+				}
+				Ok(None) => other_files.push(path),
+				Err(e) => {
+					error!(
+						"Error while reading file metadata for `{}`: {}",
+						path.display(),
+						e
+					);
+					if self.keep_unreadable_songs {
+						songs.push(self.unreadable_song(path, mtime));
+					} else {
+						other_files.push(path);
+					}
+				}
+			}
+		}
+
+		for cue_path in other_files.iter().filter(|p| cue::is_cue_sheet(p)) {
+			Self::split_cue_sheet(cue_path, &mut songs);
+		}
+
+		for song in &mut songs {
+			let Some(year) = song.metadata.year else {
+				continue;
+			};
+			if !self.year_range.contains(year) {
+				warn!(
+					"Ignoring year {} outside of the allowed range for `{}`",
+					year,
+					song.path.display()
+				);
+				song.metadata.year = None;
+				self.progress.add_invalid_year();
 			}
 		}
 
@@ -184,12 +433,143 @@ impl Worker {
 
 		for sub_directory in sub_directories.into_iter() {
 			self.queue_work(WorkItem {
+				root: work_item.root.clone(),
 				parent: Some(work_item.path.clone()),
 				path: sub_directory,
 			});
 		}
 	}
 
+	// Image files named by tags must live within the mount point being scanned
+	fn resolve_artwork_reference(
+		root: &Path,
+		directory: &Path,
+		reference: &str,
+	) -> Option<PathBuf> {
+		let mut path = directory.to_owned();
+		for component in Path::new(reference).components() {
+			match component {
+				Component::Normal(name) => path.push(name),
+				Component::CurDir => (),
+				Component::ParentDir => {
+					path.pop();
+				}
+				Component::RootDir | Component::Prefix(_) => return None,
+			}
+		}
+		if !path.starts_with(root) || !path.is_file() {
+			warn!(
+				"Ignoring artwork reference `{}` in `{}`",
+				reference,
+				directory.display()
+			);
+			return None;
+		}
+		Some(path)
+	}
+
+	// Songs whose metadata could not be read are titled after their file name,
+	// unless the path pattern provides a title
+	fn unreadable_song(&self, path: PathBuf, mtime: Option<i64>) -> Song {
+		let mut metadata = SongTags::default();
+		if let Some(pattern) = &self.path_pattern {
+			pattern.apply(&path, &mut metadata);
+		}
+		metadata.title = metadata
+			.title
+			.or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()));
+		Song {
+			path,
+			metadata,
+			cue_segment: None,
+			metadata_error: true,
+			mtime,
+			duration_decoded: false,
+		}
+	}
+
+	fn split_cue_sheet(cue_path: &Path, songs: &mut Vec<Song>) {
+		let sheet = match cue::read(cue_path) {
+			Ok(sheet) => sheet,
+			Err(e) => {
+				error!(
+					"Error while reading cue sheet `{}`: {}",
+					cue_path.display(),
+					e
+				);
+				return;
+			}
+		};
+
+		let directory = cue_path.parent().unwrap_or_else(|| Path::new(""));
+		for file in &sheet.files {
+			let audio_path = directory.join(&file.name);
+			let Some(index) = songs.iter().position(|s| s.path == audio_path) else {
+				continue;
+			};
+			if file.tracks.is_empty() {
+				continue;
+			}
+			let audio_file = songs.remove(index);
+
+			for (i, track) in file.tracks.iter().enumerate() {
+				let start_ms = track.start_ms.unwrap_or_default();
+				let end_ms = file.tracks.get(i + 1).and_then(|t| t.start_ms);
+				let duration = match end_ms {
+					Some(end_ms) => Some(end_ms.saturating_sub(start_ms) / 1000),
+					None => audio_file
+						.metadata
+						.duration
+						.map(|d| d.saturating_sub(start_ms / 1000)),
+				};
+				let tags = &audio_file.metadata;
+				songs.push(Song {
+					path: cue::track_path(cue_path, track.number),
+					metadata: SongTags {
+						track_number: Some(track.number),
+						title: track.title.clone(),
+						duration,
+						artist: track
+							.performer
+							.clone()
+							.or_else(|| sheet.performer.clone())
+							.or_else(|| tags.artist.clone()),
+						album_artist: tags
+							.album_artist
+							.clone()
+							.or_else(|| sheet.performer.clone()),
+						album: sheet.title.clone().or_else(|| tags.album.clone()),
+						year: sheet.year.or(tags.year),
+						composer: track
+							.songwriter
+							.clone()
+							.or_else(|| sheet.songwriter.clone())
+							.or_else(|| tags.composer.clone()),
+						genre: sheet.genre.clone().or_else(|| tags.genre.clone()),
+						..tags.clone()
+					},
+					cue_segment: Some(cue::Segment {
+						audio_path: audio_path.clone(),
+						start_ms,
+						end_ms,
+					}),
+					metadata_error: audio_file.metadata_error,
+					mtime: None,
+					// Only the last track's duration comes from the audio file
+					duration_decoded: end_ms.is_none() && audio_file.duration_decoded,
+				});
+			}
+		}
+	}
+
+	fn get_date_modified(path: &Path) -> Option<i64> {
+		let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+		modified
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_millis() as i64)
+			.ok()
+	}
+
 	fn get_date_created(path: &Path) -> Option<i32> {
 		if let Ok(t) = fs::metadata(path).and_then(|m| m.created().or_else(|_| m.modified())) {
 			t.duration_since(std::time::UNIX_EPOCH)
@@ -1,12 +1,18 @@
 use core::clone::Clone;
 use diesel::prelude::*;
 use diesel::sql_types;
+use diesel::sqlite::SqliteConnection;
 use diesel::BelongingToDsl;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::app::index::Song;
 use crate::app::vfs;
-use crate::db::{self, playlist_songs, playlists, users, DB};
+use crate::db::{self, playlist_songs, playlists, queue_songs, queues, users, DB};
+
+// Columns of the `songs` table, in the order `Song` expects them, for raw SQL queries
+const SONG_COLUMNS: &str = "s.id, s.path, s.parent, s.track_number, s.disc_number, s.title, s.artist, s.album_artist, s.year, s.album, s.artwork, s.duration, s.lyricist, s.composer, s.genre, s.label, s.start_offset_ms, s.end_offset_ms, s.metadata_error, s.track_total, s.disc_total, s.mtime, s.track_gain, s.track_peak, s.album_gain, s.album_peak, s.duration_decoded, s.audio_path";
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -18,6 +24,8 @@ pub enum Error {
 	UserNotFound,
 	#[error("Playlist not found")]
 	PlaylistNotFound,
+	#[error("Playlist has no track at index {0}")]
+	TrackIndexOutOfRange(usize),
 	#[error(transparent)]
 	Vfs(#[from] vfs::Error),
 }
@@ -33,7 +41,7 @@ impl Manager {
 		Self { db, vfs_manager }
 	}
 
-	pub fn list_playlists(&self, owner: &str) -> Result<Vec<String>, Error> {
+	pub fn list_playlists(&self, owner: &str) -> Result<Vec<PlaylistHeader>, Error> {
 		let mut connection = self.db.connect()?;
 
 		let user: User = {
@@ -48,89 +56,191 @@ impl Manager {
 
 		{
 			use self::playlists::dsl::*;
-			let found_playlists: Vec<String> = Playlist::belonging_to(&user)
-				.select(name)
+			let found_playlists: Vec<PlaylistHeader> = Playlist::belonging_to(&user)
+				.select((name, created_at, updated_at))
 				.load(&mut connection)?;
 			Ok(found_playlists)
 		}
 	}
 
+	// When `dedupe` is set, only the first occurrence of each track is saved.
+	// Returns how many duplicate tracks were dropped.
 	pub fn save_playlist(
 		&self,
 		playlist_name: &str,
 		owner: &str,
 		content: &[String],
-	) -> Result<(), Error> {
-		let new_playlist: NewPlaylist;
-		let playlist: Playlist;
-		let vfs = self.vfs_manager.get_vfs()?;
-
-		{
-			let mut connection = self.db.connect()?;
+		dedupe: bool,
+	) -> Result<usize, Error> {
+		let deduped: Vec<String>;
+		let mut duplicates_removed = 0;
+		let content = if dedupe {
+			let mut seen = HashSet::new();
+			deduped = content
+				.iter()
+				.filter(|path| seen.insert(*path))
+				.cloned()
+				.collect();
+			duplicates_removed = content.len() - deduped.len();
+			&deduped
+		} else {
+			content
+		};
 
-			// Find owner
-			let user: User = {
-				use self::users::dsl::*;
-				users
-					.filter(name.eq(owner))
-					.select((id,))
-					.first(&mut connection)
-					.optional()?
-					.ok_or(Error::UserNotFound)?
-			};
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let user = find_user(&mut connection, owner)?;
+		connection.transaction::<_, diesel::result::Error, _>(|connection| {
+			write_playlist(connection, &vfs, user.id, playlist_name, content)
+		})?;
+		Ok(duplicates_removed)
+	}
 
-			// Create playlist
-			new_playlist = NewPlaylist {
-				name: playlist_name.into(),
-				owner: user.id,
-			};
+	// Creates or replaces several playlists at once. Either all playlists are saved, or none are.
+	// Playlists without a name, or whose name was already used earlier in the list, are skipped.
+	pub fn save_playlists(
+		&self,
+		owner: &str,
+		playlists: &[(String, Vec<String>)],
+	) -> Result<Vec<SaveReport>, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let user = find_user(&mut connection, owner)?;
+		let reports = connection.transaction::<_, diesel::result::Error, _>(|connection| {
+			let mut names = HashSet::new();
+			let mut reports = Vec::with_capacity(playlists.len());
+			for (name, content) in playlists {
+				let mut report = SaveReport {
+					name: name.clone(),
+					saved: false,
+					tracks_saved: 0,
+					tracks_skipped: content.len(),
+				};
+				if !name.is_empty() && names.insert(name) {
+					report.tracks_saved = write_playlist(connection, &vfs, user.id, name, content)?;
+					report.tracks_skipped = content.len() - report.tracks_saved;
+					report.saved = true;
+				}
+				reports.push(report);
+			}
+			Ok(reports)
+		})?;
+		Ok(reports)
+	}
 
-			diesel::insert_into(playlists::table)
-				.values(&new_playlist)
-				.execute(&mut connection)?;
+	// Adds tracks after the current end of an existing playlist. Returns how many tracks were added.
+	pub fn append_to_playlist(
+		&self,
+		playlist_name: &str,
+		owner: &str,
+		content: &[String],
+	) -> Result<usize, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let user = find_user(&mut connection, owner)?;
 
-			playlist = {
+		// Orderings are read and written under the same write lock, so concurrent
+		// appends cannot produce duplicate indices
+		connection.immediate_transaction::<_, Error, _>(|connection| {
+			let playlist: Playlist = {
 				use self::playlists::dsl::*;
 				playlists
 					.select((id, owner))
 					.filter(name.eq(playlist_name).and(owner.eq(user.id)))
-					.get_result(&mut connection)?
-			}
-		}
+					.get_result(&mut *connection)
+					.optional()?
+					.ok_or(Error::PlaylistNotFound)?
+			};
 
-		let mut new_songs: Vec<NewPlaylistSong> = Vec::with_capacity(content.len());
+			let last_ordering: Option<i32> = playlist_songs::table
+				.filter(playlist_songs::playlist.eq(playlist.id))
+				.select(diesel::dsl::max(playlist_songs::ordering))
+				.first(&mut *connection)?;
+			let first_ordering = last_ordering.map(|o| o + 1).unwrap_or_default();
 
-		for (i, path) in content.iter().enumerate() {
-			let virtual_path = Path::new(&path);
-			if let Some(real_path) = vfs
-				.virtual_to_real(virtual_path)
-				.ok()
-				.and_then(|p| p.to_str().map(|s| s.to_owned()))
-			{
-				new_songs.push(NewPlaylistSong {
+			let new_songs: Vec<NewPlaylistSong> = resolve_tracks(&vfs, content)
+				.enumerate()
+				.map(|(i, (_, path))| NewPlaylistSong {
 					playlist: playlist.id,
-					path: real_path,
-					ordering: i as i32,
-				});
+					path,
+					ordering: first_ordering + i as i32,
+				})
+				.collect();
+			diesel::insert_into(playlist_songs::table)
+				.values(&new_songs)
+				.execute(&mut *connection)?;
+			diesel::update(playlists::table.find(playlist.id))
+				.set(playlists::updated_at.eq(now()))
+				.execute(&mut *connection)?;
+
+			Ok(new_songs.len())
+		})
+	}
+
+	// Moves the track at `from_index` to `to_index`, shifting the tracks in between
+	pub fn move_track(
+		&self,
+		playlist_name: &str,
+		owner: &str,
+		from_index: usize,
+		to_index: usize,
+	) -> Result<(), Error> {
+		use self::playlist_songs::dsl::*;
+		let mut connection = self.db.connect()?;
+		let user = find_user(&mut connection, owner)?;
+
+		connection.immediate_transaction::<_, Error, _>(|connection| {
+			let playlist_id: i32 = playlists::table
+				.select(playlists::id)
+				.filter(playlists::name.eq(playlist_name))
+				.filter(playlists::owner.eq(user.id))
+				.get_result(&mut *connection)
+				.optional()?
+				.ok_or(Error::PlaylistNotFound)?;
+
+			// Orderings can have gaps, so indices are resolved to the orderings they point at
+			let mut ordering_at = |index: usize| -> Result<i32, Error> {
+				playlist_songs
+					.filter(playlist.eq(playlist_id))
+					.select(ordering)
+					.order(ordering)
+					.offset(index as i64)
+					.first(&mut *connection)
+					.optional()?
+					.ok_or(Error::TrackIndexOutOfRange(index))
+			};
+			let from = ordering_at(from_index)?;
+			let to = ordering_at(to_index)?;
+			if from == to {
+				return Ok(());
 			}
-		}
 
-		{
-			let mut connection = self.db.connect()?;
-			connection.transaction::<_, diesel::result::Error, _>(|connection| {
-				// Delete old content (if any)
-				let old_songs = PlaylistSong::belonging_to(&playlist);
-				diesel::delete(old_songs).execute(connection)?;
-
-				// Insert content
-				diesel::insert_into(playlist_songs::table)
-					.values(&new_songs)
-					.execute(&mut *connection)?; // TODO https://github.com/diesel-rs/diesel/issues/1822
-				Ok(())
-			})?;
-		}
+			// Orderings must stay unique after each statement, or conflicting rows would be
+			// replaced. The moved track is parked at -1 and shifted tracks go through negative
+			// orderings, which are never used otherwise.
+			let track = playlist_songs.filter(playlist.eq(playlist_id));
+			diesel::update(track.filter(ordering.eq(from)))
+				.set(ordering.eq(-1))
+				.execute(&mut *connection)?;
+			let (first, last, offset) = match from < to {
+				true => (from + 1, to, -1),
+				false => (to, from - 1, 1),
+			};
+			diesel::update(track.filter(ordering.between(first, last)))
+				.set(ordering.eq(ordering * -1 - 2))
+				.execute(&mut *connection)?;
+			diesel::update(track.filter(ordering.lt(-1)))
+				.set(ordering.eq(ordering * -1 - 2 + offset))
+				.execute(&mut *connection)?;
+			diesel::update(track.filter(ordering.eq(-1)))
+				.set(ordering.eq(to))
+				.execute(&mut *connection)?;
 
-		Ok(())
+			diesel::update(playlists::table.find(playlist_id))
+				.set(playlists::updated_at.eq(now()))
+				.execute(&mut *connection)?;
+			Ok(())
+		})
 	}
 
 	pub fn read_playlist(&self, playlist_name: &str, owner: &str) -> Result<Vec<Song>, Error> {
@@ -163,15 +273,15 @@ impl Manager {
 			};
 
 			// Select songs. Not using Diesel because we need to LEFT JOIN using a custom column
-			let query = diesel::sql_query(
+			let query = diesel::sql_query(format!(
 				r#"
-			SELECT s.id, s.path, s.parent, s.track_number, s.disc_number, s.title, s.artist, s.album_artist, s.year, s.album, s.artwork, s.duration, s.lyricist, s.composer, s.genre, s.label
+			SELECT {SONG_COLUMNS}
 			FROM playlist_songs ps
 			LEFT JOIN songs s ON ps.path = s.path
 			WHERE ps.playlist = ?
 			ORDER BY ps.ordering
-		"#,
-			);
+		"#
+			));
 			let query = query.bind::<sql_types::Integer, _>(playlist.id);
 			songs = query.get_results(&mut connection)?;
 		}
@@ -207,6 +317,231 @@ impl Manager {
 			}
 		}
 	}
+
+	// Each user has a single play queue, stored separately from their playlists
+	pub fn save_queue(
+		&self,
+		owner: &str,
+		content: &[String],
+		current_index: u32,
+		position_ms: u32,
+	) -> Result<(), Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let user = find_user(&mut connection, owner)?;
+
+		let tracks: Vec<(usize, String)> = resolve_tracks(&vfs, content).collect();
+		// Tracks outside of the collection are dropped, so the index is shifted accordingly
+		let current_index = tracks
+			.iter()
+			.filter(|(i, _)| *i < current_index as usize)
+			.count();
+
+		connection.transaction::<_, diesel::result::Error, _>(|connection| {
+			diesel::delete(queues::table.filter(queues::owner.eq(user.id)))
+				.execute(&mut *connection)?;
+			diesel::insert_into(queues::table)
+				.values(&NewQueue {
+					owner: user.id,
+					current_index: current_index as i32,
+					position_ms: position_ms as i32,
+				})
+				.execute(&mut *connection)?;
+			let queue: i32 = queues::table
+				.filter(queues::owner.eq(user.id))
+				.select(queues::id)
+				.get_result(&mut *connection)?;
+
+			let new_songs: Vec<NewQueueSong> = tracks
+				.into_iter()
+				.enumerate()
+				.map(|(i, (_, path))| NewQueueSong {
+					queue,
+					path,
+					ordering: i as i32,
+				})
+				.collect();
+			diesel::insert_into(queue_songs::table)
+				.values(&new_songs)
+				.execute(&mut *connection)?;
+			Ok(())
+		})?;
+
+		Ok(())
+	}
+
+	// Returns an empty queue if none was saved yet
+	pub fn read_queue(&self, owner: &str) -> Result<Queue, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let user = find_user(&mut connection, owner)?;
+
+		let Some((queue, current_index, position_ms)) = queues::table
+			.filter(queues::owner.eq(user.id))
+			.select((queues::id, queues::current_index, queues::position_ms))
+			.get_result::<(i32, i32, i32)>(&mut connection)
+			.optional()?
+		else {
+			return Ok(Queue::default());
+		};
+
+		let paths: Vec<String> = queue_songs::table
+			.filter(queue_songs::queue.eq(queue))
+			.order(queue_songs::ordering)
+			.select(queue_songs::path)
+			.load(&mut connection)?;
+		let query = diesel::sql_query(format!(
+			r#"
+			SELECT {SONG_COLUMNS}
+			FROM songs s
+			WHERE s.path IN (SELECT path FROM queue_songs WHERE queue = ?)
+		"#
+		));
+		let songs: HashMap<String, Song> = query
+			.bind::<sql_types::Integer, _>(queue)
+			.load::<Song>(&mut connection)?
+			.into_iter()
+			.map(|s| (s.path.clone(), s))
+			.collect();
+
+		// Songs which are no longer in the collection are left out, and the current index is
+		// shifted to keep pointing at the same song, or the one after it if it is gone
+		let mut result = Queue {
+			position_ms: position_ms as u32,
+			..Default::default()
+		};
+		for (i, path) in paths.iter().enumerate() {
+			let song = songs.get(path).cloned().and_then(|s| s.virtualize(&vfs));
+			if i == current_index as usize {
+				result.current_index = result.songs.len() as u32;
+				if song.is_none() {
+					result.position_ms = 0;
+				}
+			}
+			result.songs.extend(song);
+		}
+		if current_index as usize >= paths.len() {
+			result.current_index = result.songs.len() as u32;
+		}
+		if !result.songs.is_empty() && result.current_index as usize >= result.songs.len() {
+			result.current_index = result.songs.len() as u32 - 1;
+			result.position_ms = 0;
+		}
+		Ok(result)
+	}
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Queue {
+	pub songs: Vec<Song>,
+	pub current_index: u32,
+	// Playback position within the current song
+	pub position_ms: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Queryable)]
+pub struct PlaylistHeader {
+	pub name: String,
+	// Unix timestamps, in seconds. Playlists saved before these dates were recorded
+	// have no creation date.
+	pub created_at: Option<i32>,
+	pub updated_at: i32,
+}
+
+// Outcome of saving one of the playlists passed to `Manager::save_playlists`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveReport {
+	pub name: String,
+	pub saved: bool,
+	pub tracks_saved: usize,
+	// Tracks which do not belong to any mount point
+	pub tracks_skipped: usize,
+}
+
+fn find_user(connection: &mut SqliteConnection, owner: &str) -> Result<User, Error> {
+	use self::users::dsl::*;
+	users
+		.filter(name.eq(owner))
+		.select((id,))
+		.first(connection)
+		.optional()?
+		.ok_or(Error::UserNotFound)
+}
+
+// Replaces the content of a playlist, creating it if needed. Returns how many tracks were saved.
+fn write_playlist(
+	connection: &mut SqliteConnection,
+	vfs: &vfs::VFS,
+	owner_id: i32,
+	playlist_name: &str,
+	content: &[String],
+) -> Result<usize, diesel::result::Error> {
+	// Saving over an existing playlist replaces its row, so its creation date is carried over
+	let updated_at = now();
+	let created_at: Option<i32> = playlists::table
+		.filter(playlists::name.eq(playlist_name))
+		.filter(playlists::owner.eq(owner_id))
+		.select(playlists::created_at)
+		.get_result::<Option<i32>>(&mut *connection)
+		.optional()?
+		.flatten();
+
+	diesel::insert_into(playlists::table)
+		.values(&NewPlaylist {
+			name: playlist_name.into(),
+			owner: owner_id,
+			created_at: created_at.unwrap_or(updated_at),
+			updated_at,
+		})
+		.execute(&mut *connection)?;
+
+	let playlist: Playlist = {
+		use self::playlists::dsl::*;
+		playlists
+			.select((id, owner))
+			.filter(name.eq(playlist_name).and(owner.eq(owner_id)))
+			.get_result(&mut *connection)?
+	};
+
+	let new_songs: Vec<NewPlaylistSong> = resolve_tracks(vfs, content)
+		.map(|(i, path)| NewPlaylistSong {
+			playlist: playlist.id,
+			path,
+			ordering: i as i32,
+		})
+		.collect();
+
+	// Delete old content (if any)
+	let old_songs = PlaylistSong::belonging_to(&playlist);
+	diesel::delete(old_songs).execute(&mut *connection)?;
+
+	// Insert content
+	diesel::insert_into(playlist_songs::table)
+		.values(&new_songs)
+		.execute(&mut *connection)?; // TODO https://github.com/diesel-rs/diesel/issues/1822
+
+	Ok(new_songs.len())
+}
+
+fn now() -> i32 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs() as i32)
+		.unwrap_or_default()
+}
+
+// Maps virtual paths to real paths, along with their position in `content`.
+// Tracks which do not belong to any mount point are left out.
+fn resolve_tracks<'a>(
+	vfs: &'a vfs::VFS,
+	content: &'a [String],
+) -> impl Iterator<Item = (usize, String)> + 'a {
+	content.iter().enumerate().filter_map(|(i, path)| {
+		vfs.virtual_to_real(Path::new(path))
+			.ok()
+			.and_then(|p| p.to_str().map(|s| s.to_owned()))
+			.map(|p| (i, p))
+	})
 }
 
 #[derive(Identifiable, Queryable, Associations)]
@@ -228,6 +563,8 @@ struct PlaylistSong {
 struct NewPlaylist {
 	name: String,
 	owner: i32,
+	created_at: i32,
+	updated_at: i32,
 }
 
 #[derive(Insertable)]
@@ -238,6 +575,22 @@ struct NewPlaylistSong {
 	ordering: i32,
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = queues)]
+struct NewQueue {
+	owner: i32,
+	current_index: i32,
+	position_ms: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = queue_songs)]
+struct NewQueueSong {
+	queue: i32,
+	path: String,
+	ordering: i32,
+}
+
 #[derive(Identifiable, Queryable)]
 struct User {
 	id: i32,
@@ -245,9 +598,12 @@ struct User {
 
 #[cfg(test)]
 mod test {
+	use diesel::prelude::*;
 	use std::path::{Path, PathBuf};
 
+	use super::{Queue, SaveReport};
 	use crate::app::test;
+	use crate::db::playlists;
 	use crate::test_name;
 
 	const TEST_USER: &str = "test_user";
@@ -255,6 +611,17 @@ mod test {
 	const TEST_PLAYLIST_NAME: &str = "Chill & Grill";
 	const TEST_MOUNT_NAME: &str = "root";
 
+	// Indexes the collection and lists the virtual paths of its songs
+	fn indexed_song_paths(ctx: &test::Context) -> Vec<String> {
+		ctx.index.update().unwrap();
+		ctx.index
+			.flatten(Path::new(TEST_MOUNT_NAME))
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.collect()
+	}
+
 	#[test]
 	fn save_playlist_golden_path() {
 		let ctx = test::ContextBuilder::new(test_name!())
@@ -262,12 +629,53 @@ mod test {
 			.build();
 
 		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &Vec::new())
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &Vec::new(), false)
 			.unwrap();
 
 		let found_playlists = ctx.playlist_manager.list_playlists(TEST_USER).unwrap();
 		assert_eq!(found_playlists.len(), 1);
-		assert_eq!(found_playlists[0], TEST_PLAYLIST_NAME);
+		assert_eq!(found_playlists[0].name, TEST_PLAYLIST_NAME);
+	}
+
+	#[test]
+	fn save_playlist_records_dates() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &Vec::new(), false)
+			.unwrap();
+		let found_playlists = ctx.playlist_manager.list_playlists(TEST_USER).unwrap();
+		assert!(found_playlists[0].updated_at > 0);
+		assert_eq!(
+			found_playlists[0].created_at,
+			Some(found_playlists[0].updated_at)
+		);
+
+		let backdate = || {
+			let mut connection = ctx.db.connect().unwrap();
+			diesel::update(playlists::table)
+				.set((playlists::created_at.eq(1), playlists::updated_at.eq(1)))
+				.execute(&mut connection)
+				.unwrap();
+		};
+
+		backdate();
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &Vec::new(), false)
+			.unwrap();
+		let found_playlists = ctx.playlist_manager.list_playlists(TEST_USER).unwrap();
+		assert_eq!(found_playlists[0].created_at, Some(1));
+		assert!(found_playlists[0].updated_at > 1);
+
+		backdate();
+		ctx.playlist_manager
+			.append_to_playlist(TEST_PLAYLIST_NAME, TEST_USER, &[])
+			.unwrap();
+		let found_playlists = ctx.playlist_manager.list_playlists(TEST_USER).unwrap();
+		assert_eq!(found_playlists[0].created_at, Some(1));
+		assert!(found_playlists[0].updated_at > 1);
 	}
 
 	#[test]
@@ -277,30 +685,375 @@ mod test {
 			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
 			.build();
 
-		ctx.index.update().unwrap();
+		let playlist_content = indexed_song_paths(&ctx);
+		assert_eq!(playlist_content.len(), 13);
 
-		let playlist_content: Vec<String> = ctx
-			.index
-			.flatten(Path::new(TEST_MOUNT_NAME))
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content, false)
+			.unwrap();
+
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content, false)
+			.unwrap();
+
+		let songs = ctx
+			.playlist_manager
+			.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap();
+		assert_eq!(songs.len(), 13);
+	}
+
+	#[test]
+	fn save_playlist_can_drop_duplicates() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		let songs = indexed_song_paths(&ctx);
+		let playlist_content = vec![
+			songs[0].clone(),
+			songs[1].clone(),
+			songs[0].clone(),
+			songs[0].clone(),
+		];
+
+		let duplicates_removed = ctx
+			.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content, false)
+			.unwrap();
+		assert_eq!(duplicates_removed, 0);
+		let saved = ctx
+			.playlist_manager
+			.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap();
+		assert_eq!(saved.len(), 4);
+
+		let duplicates_removed = ctx
+			.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content, true)
+			.unwrap();
+		assert_eq!(duplicates_removed, 2);
+		let saved: Vec<String> = ctx
+			.playlist_manager
+			.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
 			.unwrap()
 			.into_iter()
 			.map(|s| s.path)
 			.collect();
-		assert_eq!(playlist_content.len(), 13);
+		assert_eq!(saved, vec![songs[0].clone(), songs[1].clone()]);
+	}
 
-		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content)
+	#[test]
+	fn save_playlists_reports_each_playlist() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		let songs = indexed_song_paths(&ctx);
+		let mut content_with_bad_track = songs[0..2].to_vec();
+		content_with_bad_track.push("not_my_collection/song.mp3".to_owned());
+
+		let reports = ctx
+			.playlist_manager
+			.save_playlists(
+				TEST_USER,
+				&[
+					("All".to_owned(), songs.clone()),
+					("Partial".to_owned(), content_with_bad_track),
+					("".to_owned(), songs.clone()),
+					("All".to_owned(), Vec::new()),
+				],
+			)
+			.unwrap();
+
+		assert_eq!(
+			reports,
+			vec![
+				SaveReport {
+					name: "All".to_owned(),
+					saved: true,
+					tracks_saved: 13,
+					tracks_skipped: 0,
+				},
+				SaveReport {
+					name: "Partial".to_owned(),
+					saved: true,
+					tracks_saved: 2,
+					tracks_skipped: 1,
+				},
+				SaveReport {
+					name: "".to_owned(),
+					saved: false,
+					tracks_saved: 0,
+					tracks_skipped: 13,
+				},
+				SaveReport {
+					name: "All".to_owned(),
+					saved: false,
+					tracks_saved: 0,
+					tracks_skipped: 0,
+				},
+			]
+		);
+
+		let mut found_playlists: Vec<String> = ctx
+			.playlist_manager
+			.list_playlists(TEST_USER)
+			.unwrap()
+			.into_iter()
+			.map(|p| p.name)
+			.collect();
+		found_playlists.sort();
+		assert_eq!(
+			found_playlists,
+			vec!["All".to_owned(), "Partial".to_owned()]
+		);
+		let all = ctx
+			.playlist_manager
+			.read_playlist("All", TEST_USER)
 			.unwrap();
+		assert_eq!(all.len(), 13);
+	}
 
+	#[test]
+	fn append_to_playlist_keeps_existing_tracks() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		let songs = indexed_song_paths(&ctx);
+
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &songs[0..2], false)
+			.unwrap();
+		let added = ctx
+			.playlist_manager
+			.append_to_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&[
+					songs[4].clone(),
+					"not_my_collection/song.mp3".to_owned(),
+					songs[3].clone(),
+				],
+			)
+			.unwrap();
+		assert_eq!(added, 2);
 		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content)
+			.append_to_playlist(TEST_PLAYLIST_NAME, TEST_USER, &songs[0..1])
 			.unwrap();
 
-		let songs = ctx
+		let saved: Vec<String> = ctx
 			.playlist_manager
 			.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.collect();
+		assert_eq!(
+			saved,
+			vec![
+				songs[0].clone(),
+				songs[1].clone(),
+				songs[4].clone(),
+				songs[3].clone(),
+				songs[0].clone(),
+			]
+		);
+	}
+
+	#[test]
+	fn append_to_missing_playlist_fails() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+
+		let result = ctx
+			.playlist_manager
+			.append_to_playlist(TEST_PLAYLIST_NAME, TEST_USER, &[]);
+		assert!(matches!(result, Err(super::Error::PlaylistNotFound)));
+		assert!(ctx
+			.playlist_manager
+			.list_playlists(TEST_USER)
+			.unwrap()
+			.is_empty());
+	}
+
+	#[test]
+	fn move_track_shifts_tracks_in_between() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		let songs = indexed_song_paths(&ctx);
+		// The track outside of the collection leaves a gap in orderings
+		let content = vec![
+			songs[0].clone(),
+			songs[1].clone(),
+			"not_my_collection/song.mp3".to_owned(),
+			songs[2].clone(),
+			songs[3].clone(),
+		];
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &content, false)
 			.unwrap();
-		assert_eq!(songs.len(), 13);
+
+		let read = || -> Vec<String> {
+			ctx.playlist_manager
+				.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+				.unwrap()
+				.into_iter()
+				.map(|s| s.path)
+				.collect()
+		};
+
+		ctx.playlist_manager
+			.move_track(TEST_PLAYLIST_NAME, TEST_USER, 0, 2)
+			.unwrap();
+		assert_eq!(
+			read(),
+			vec![
+				songs[1].clone(),
+				songs[2].clone(),
+				songs[0].clone(),
+				songs[3].clone()
+			]
+		);
+
+		ctx.playlist_manager
+			.move_track(TEST_PLAYLIST_NAME, TEST_USER, 3, 0)
+			.unwrap();
+		assert_eq!(
+			read(),
+			vec![
+				songs[3].clone(),
+				songs[1].clone(),
+				songs[2].clone(),
+				songs[0].clone()
+			]
+		);
+
+		ctx.playlist_manager
+			.move_track(TEST_PLAYLIST_NAME, TEST_USER, 1, 1)
+			.unwrap();
+		ctx.playlist_manager
+			.append_to_playlist(TEST_PLAYLIST_NAME, TEST_USER, &songs[4..5])
+			.unwrap();
+		assert_eq!(
+			read(),
+			vec![
+				songs[3].clone(),
+				songs[1].clone(),
+				songs[2].clone(),
+				songs[0].clone(),
+				songs[4].clone()
+			]
+		);
+	}
+
+	#[test]
+	fn move_track_rejects_out_of_range_indices() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &[], false)
+			.unwrap();
+		let result = ctx
+			.playlist_manager
+			.move_track(TEST_PLAYLIST_NAME, TEST_USER, 0, 0);
+		assert!(matches!(result, Err(super::Error::TrackIndexOutOfRange(0))));
+	}
+
+	#[test]
+	fn queue_is_empty_by_default() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+		let queue = ctx.playlist_manager.read_queue(TEST_USER).unwrap();
+		assert_eq!(queue, Queue::default());
+	}
+
+	#[test]
+	fn queue_skips_songs_missing_from_collection() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		let songs = indexed_song_paths(&ctx);
+		let missing_song: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "missing.mp3"].iter().collect();
+		let content = vec![
+			songs[0].clone(),
+			missing_song.to_string_lossy().into_owned(),
+			songs[1].clone(),
+			songs[2].clone(),
+		];
+
+		ctx.playlist_manager
+			.save_queue(TEST_USER, &content, 2, 12_000)
+			.unwrap();
+		let queue = ctx.playlist_manager.read_queue(TEST_USER).unwrap();
+		let paths: Vec<String> = queue.songs.into_iter().map(|s| s.path).collect();
+		assert_eq!(paths, songs[0..3]);
+		assert_eq!(queue.current_index, 1);
+		assert_eq!(queue.position_ms, 12_000);
+
+		// When the current song is missing, playback resumes from the start of the next one
+		ctx.playlist_manager
+			.save_queue(TEST_USER, &content, 1, 12_000)
+			.unwrap();
+		let queue = ctx.playlist_manager.read_queue(TEST_USER).unwrap();
+		assert_eq!(queue.current_index, 1);
+		assert_eq!(queue.position_ms, 0);
+	}
+
+	#[test]
+	fn queue_round_trip() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		let songs = indexed_song_paths(&ctx);
+		let content = vec![
+			songs[3].clone(),
+			"not_my_collection/song.mp3".to_owned(),
+			songs[1].clone(),
+			songs[2].clone(),
+		];
+
+		ctx.playlist_manager
+			.save_queue(TEST_USER, &content, 2, 12_000)
+			.unwrap();
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &songs, false)
+			.unwrap();
+
+		let queue = ctx.playlist_manager.read_queue(TEST_USER).unwrap();
+		let paths: Vec<String> = queue.songs.into_iter().map(|s| s.path).collect();
+		assert_eq!(
+			paths,
+			vec![songs[3].clone(), songs[1].clone(), songs[2].clone()]
+		);
+		assert_eq!(queue.current_index, 1);
+		assert_eq!(queue.position_ms, 12_000);
+
+		ctx.playlist_manager
+			.save_queue(TEST_USER, &songs[0..1], 0, 0)
+			.unwrap();
+		let queue = ctx.playlist_manager.read_queue(TEST_USER).unwrap();
+		assert_eq!(queue.songs.len(), 1);
+		assert_eq!(queue.songs[0].path, songs[0]);
+
+		let found_playlists = ctx.playlist_manager.list_playlists(TEST_USER).unwrap();
+		assert_eq!(found_playlists.len(), 1);
+		assert_eq!(found_playlists[0].name, TEST_PLAYLIST_NAME);
 	}
 
 	#[test]
@@ -312,7 +1065,7 @@ mod test {
 		let playlist_content = Vec::new();
 
 		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content)
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content, false)
 			.unwrap();
 
 		ctx.playlist_manager
@@ -330,19 +1083,11 @@ mod test {
 			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
 			.build();
 
-		ctx.index.update().unwrap();
-
-		let playlist_content: Vec<String> = ctx
-			.index
-			.flatten(Path::new(TEST_MOUNT_NAME))
-			.unwrap()
-			.into_iter()
-			.map(|s| s.path)
-			.collect();
+		let playlist_content = indexed_song_paths(&ctx);
 		assert_eq!(playlist_content.len(), 13);
 
 		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content)
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content, false)
 			.unwrap();
 
 		let songs = ctx
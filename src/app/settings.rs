@@ -2,8 +2,11 @@ use diesel::prelude::*;
 use regex::Regex;
 use serde::Deserialize;
 use std::convert::TryInto;
+use std::str::FromStr;
 use std::time::Duration;
 
+use crate::app::index::PathPattern;
+use crate::app::transcode;
 use crate::db::{self, misc_settings, DB};
 
 #[derive(thiserror::Error, Debug)]
@@ -18,6 +21,20 @@ pub enum Error {
 	MiscSettingsNotFound,
 	#[error("Index album art pattern is not a valid regex")]
 	IndexAlbumArtPatternInvalid,
+	#[error("Index album artist fallback must be one of `artist`, `various_artists` or `directory_name`")]
+	IndexAlbumArtistFallbackInvalid,
+	#[error("Index artwork preference must be one of `folder` or `embedded`")]
+	IndexArtworkPreferenceInvalid,
+	#[error("Index path pattern is not valid")]
+	IndexPathPatternInvalid,
+	#[error("Index sleep duration cannot be negative")]
+	IndexSleepDurationInvalid,
+	#[error("Thumbnail default size must be one of `small`, `large` or `native`")]
+	ThumbnailDefaultSizeInvalid,
+	#[error(
+		"Transcode targets must be written as `codec:bitrate`, with a bitrate between 32 and 320"
+	)]
+	TranscodeTargetInvalid,
 	#[error(transparent)]
 	Database(#[from] diesel::result::Error),
 }
@@ -27,16 +44,157 @@ pub struct AuthSecret {
 	pub key: [u8; 32],
 }
 
+// Decides which artist a directory is attributed to when its songs have no album artist tag
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlbumArtistFallback {
+	// Use the artist tag of the songs, leaving mixed-artist directories without an artist
+	#[default]
+	Artist,
+	// Use the artist tag of the songs, attributing mixed-artist directories to `Various Artists`
+	VariousArtists,
+	// Use the name of the folder containing the directory (eg. `Artist/Album`)
+	DirectoryName,
+}
+
+impl FromStr for AlbumArtistFallback {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"artist" => Ok(Self::Artist),
+			"various_artists" => Ok(Self::VariousArtists),
+			"directory_name" => Ok(Self::DirectoryName),
+			_ => Err(Error::IndexAlbumArtistFallbackInvalid),
+		}
+	}
+}
+
+// Decides which artwork represents a directory containing both an image file matching the album
+// art pattern and songs with embedded artwork
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArtworkPreference {
+	#[default]
+	Folder,
+	Embedded,
+}
+
+impl FromStr for ArtworkPreference {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"folder" => Ok(Self::Folder),
+			"embedded" => Ok(Self::Embedded),
+			_ => Err(Error::IndexArtworkPreferenceInvalid),
+		}
+	}
+}
+
+// Size of the artwork served when a thumbnail request does not specify one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThumbnailSize {
+	#[default]
+	Small,
+	Large,
+	Native,
+}
+
+impl FromStr for ThumbnailSize {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"small" => Ok(Self::Small),
+			"large" => Ok(Self::Large),
+			"native" => Ok(Self::Native),
+			_ => Err(Error::ThumbnailDefaultSizeInvalid),
+		}
+	}
+}
+
+// Requirements for new passwords. Existing passwords are not affected when these change.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PasswordPolicy {
+	pub min_length: usize,
+	// Require lowercase letters, uppercase letters and digits
+	pub require_complexity: bool,
+}
+
+// Bounds for the years read from song tags, songs tagged outside of them are indexed without a year
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct YearRange {
+	pub min: Option<i32>,
+	pub max: Option<i32>,
+}
+
+impl YearRange {
+	pub fn contains(&self, year: i32) -> bool {
+		self.min.is_none_or(|min| year >= min) && self.max.is_none_or(|max| year <= max)
+	}
+}
+
 #[derive(Debug, Queryable)]
 pub struct Settings {
 	pub index_sleep_duration_seconds: i32,
 	pub index_album_art_pattern: String,
+	pub index_album_artist_fallback: String,
+	pub index_keep_unreadable_songs: bool,
+	pub index_path_pattern: String,
+	pub index_exact_durations: bool,
+	pub index_skip_non_utf8_paths: bool,
+	pub password_min_length: i32,
+	pub password_require_complexity: bool,
+	pub case_insensitive_paths: bool,
+	pub index_folder_name_albums: bool,
+	pub index_min_year: i32,
+	pub index_max_year: i32,
+	pub thumbnail_default_size: String,
+	pub index_artwork_preference: String,
+	// One name per line
+	pub index_compilation_artist_names: String,
+	// Comma separated, eg. `mp3:128,opus:96`
+	pub transcode_targets: String,
+}
+
+impl Settings {
+	pub fn compilation_artist_names(&self) -> Vec<String> {
+		self.index_compilation_artist_names
+			.lines()
+			.map(str::trim)
+			.filter(|n| !n.is_empty())
+			.map(str::to_owned)
+			.collect()
+	}
+
+	pub fn transcode_targets(&self) -> Vec<String> {
+		self.transcode_targets
+			.split(',')
+			.map(str::trim)
+			.filter(|t| !t.is_empty())
+			.map(str::to_owned)
+			.collect()
+	}
 }
 
 #[derive(Debug, Default, Deserialize)]
 pub struct NewSettings {
 	pub reindex_every_n_seconds: Option<i32>,
 	pub album_art_pattern: Option<String>,
+	pub album_artist_fallback: Option<String>,
+	pub keep_unreadable_songs: Option<bool>,
+	pub path_pattern: Option<String>,
+	pub exact_durations: Option<bool>,
+	pub skip_non_utf8_paths: Option<bool>,
+	pub password_min_length: Option<i32>,
+	pub password_require_complexity: Option<bool>,
+	pub case_insensitive_paths: Option<bool>,
+	pub folder_name_albums: Option<bool>,
+	pub min_year: Option<i32>,
+	pub max_year: Option<i32>,
+	pub thumbnail_default_size: Option<String>,
+	pub artwork_preference: Option<String>,
+	pub compilation_artist_names: Option<Vec<String>>,
+	pub transcode_targets: Option<Vec<String>>,
 }
 
 #[derive(Clone)]
@@ -65,11 +223,13 @@ impl Manager {
 			.map(|key| AuthSecret { key })
 	}
 
-	pub fn get_index_sleep_duration(&self) -> Result<Duration, Error> {
+	// Returns `None` when periodic index updates are disabled
+	pub fn get_index_sleep_duration(&self) -> Result<Option<Duration>, Error> {
 		let settings = self.read()?;
-		Ok(Duration::from_secs(
-			settings.index_sleep_duration_seconds as u64,
-		))
+		match settings.index_sleep_duration_seconds {
+			0 => Ok(None),
+			s => Ok(Some(Duration::from_secs(s.max(0) as u64))),
+		}
 	}
 
 	pub fn get_index_album_art_pattern(&self) -> Result<Regex, Error> {
@@ -79,12 +239,113 @@ impl Manager {
 		Ok(regex)
 	}
 
+	pub fn get_index_album_artist_fallback(&self) -> Result<AlbumArtistFallback, Error> {
+		let settings = self.read()?;
+		settings.index_album_artist_fallback.parse()
+	}
+
+	pub fn get_index_artwork_preference(&self) -> Result<ArtworkPreference, Error> {
+		let settings = self.read()?;
+		settings.index_artwork_preference.parse()
+	}
+
+	// Artists excluded from artist listings, compared regardless of case
+	pub fn get_index_compilation_artist_names(&self) -> Result<Vec<String>, Error> {
+		let settings = self.read()?;
+		Ok(settings.compilation_artist_names())
+	}
+
+	// Codecs and bitrates clients may request transcoded audio in
+	pub fn get_transcode_targets(&self) -> Result<Vec<transcode::Options>, Error> {
+		let settings = self.read()?;
+		settings
+			.transcode_targets()
+			.iter()
+			.map(|t| t.parse().map_err(|_| Error::TranscodeTargetInvalid))
+			.collect()
+	}
+
+	pub fn get_index_keep_unreadable_songs(&self) -> Result<bool, Error> {
+		let settings = self.read()?;
+		Ok(settings.index_keep_unreadable_songs)
+	}
+
+	// Returns `None` when no path pattern is configured
+	pub fn get_index_path_pattern(&self) -> Result<Option<PathPattern>, Error> {
+		let settings = self.read()?;
+		if settings.index_path_pattern.is_empty() {
+			return Ok(None);
+		}
+		PathPattern::new(&settings.index_path_pattern)
+			.map(Some)
+			.ok_or(Error::IndexPathPatternInvalid)
+	}
+
+	pub fn get_index_exact_durations(&self) -> Result<bool, Error> {
+		let settings = self.read()?;
+		Ok(settings.index_exact_durations)
+	}
+
+	pub fn get_index_skip_non_utf8_paths(&self) -> Result<bool, Error> {
+		let settings = self.read()?;
+		Ok(settings.index_skip_non_utf8_paths)
+	}
+
+	pub fn get_index_folder_name_albums(&self) -> Result<bool, Error> {
+		let settings = self.read()?;
+		Ok(settings.index_folder_name_albums)
+	}
+
+	pub fn get_index_year_range(&self) -> Result<YearRange, Error> {
+		let settings = self.read()?;
+		Ok(YearRange {
+			min: Some(settings.index_min_year).filter(|y| *y != 0),
+			max: Some(settings.index_max_year).filter(|y| *y != 0),
+		})
+	}
+
+	pub fn get_thumbnail_default_size(&self) -> Result<ThumbnailSize, Error> {
+		let settings = self.read()?;
+		settings.thumbnail_default_size.parse()
+	}
+
+	pub fn get_case_insensitive_paths(&self) -> Result<bool, Error> {
+		let settings = self.read()?;
+		Ok(settings.case_insensitive_paths)
+	}
+
+	pub fn get_password_policy(&self) -> Result<PasswordPolicy, Error> {
+		let settings = self.read()?;
+		Ok(PasswordPolicy {
+			min_length: settings.password_min_length.max(0) as usize,
+			require_complexity: settings.password_require_complexity,
+		})
+	}
+
 	pub fn read(&self) -> Result<Settings, Error> {
 		use self::misc_settings::dsl::*;
 		let mut connection = self.db.connect()?;
 
 		let settings: Settings = misc_settings
-			.select((index_sleep_duration_seconds, index_album_art_pattern))
+			.select((
+				index_sleep_duration_seconds,
+				index_album_art_pattern,
+				index_album_artist_fallback,
+				index_keep_unreadable_songs,
+				index_path_pattern,
+				index_exact_durations,
+				index_skip_non_utf8_paths,
+				password_min_length,
+				password_require_complexity,
+				case_insensitive_paths,
+				index_folder_name_albums,
+				index_min_year,
+				index_max_year,
+				thumbnail_default_size,
+				index_artwork_preference,
+				index_compilation_artist_names,
+				transcode_targets,
+			))
 			.get_result(&mut connection)
 			.map_err(|e| match e {
 				diesel::result::Error::NotFound => Error::MiscSettingsNotFound,
@@ -98,6 +359,9 @@ impl Manager {
 		let mut connection = self.db.connect()?;
 
 		if let Some(sleep_duration) = new_settings.reindex_every_n_seconds {
+			if sleep_duration < 0 {
+				return Err(Error::IndexSleepDurationInvalid);
+			}
 			diesel::update(misc_settings::table)
 				.set(misc_settings::index_sleep_duration_seconds.eq(sleep_duration))
 				.execute(&mut connection)?;
@@ -109,6 +373,117 @@ impl Manager {
 				.execute(&mut connection)?;
 		}
 
+		if let Some(ref album_artist_fallback) = new_settings.album_artist_fallback {
+			album_artist_fallback.parse::<AlbumArtistFallback>()?;
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_album_artist_fallback.eq(album_artist_fallback))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(keep_unreadable_songs) = new_settings.keep_unreadable_songs {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_keep_unreadable_songs.eq(keep_unreadable_songs))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref path_pattern) = new_settings.path_pattern {
+			if !path_pattern.is_empty() && PathPattern::new(path_pattern).is_none() {
+				return Err(Error::IndexPathPatternInvalid);
+			}
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_path_pattern.eq(path_pattern))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(exact_durations) = new_settings.exact_durations {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_exact_durations.eq(exact_durations))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(skip_non_utf8_paths) = new_settings.skip_non_utf8_paths {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_skip_non_utf8_paths.eq(skip_non_utf8_paths))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(min_length) = new_settings.password_min_length {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::password_min_length.eq(min_length.max(0)))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(require_complexity) = new_settings.password_require_complexity {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::password_require_complexity.eq(require_complexity))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(case_insensitive_paths) = new_settings.case_insensitive_paths {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::case_insensitive_paths.eq(case_insensitive_paths))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(folder_name_albums) = new_settings.folder_name_albums {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_folder_name_albums.eq(folder_name_albums))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(min_year) = new_settings.min_year {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_min_year.eq(min_year))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(max_year) = new_settings.max_year {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_max_year.eq(max_year))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref thumbnail_default_size) = new_settings.thumbnail_default_size {
+			thumbnail_default_size.parse::<ThumbnailSize>()?;
+			diesel::update(misc_settings::table)
+				.set(misc_settings::thumbnail_default_size.eq(thumbnail_default_size))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref artwork_preference) = new_settings.artwork_preference {
+			artwork_preference.parse::<ArtworkPreference>()?;
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_artwork_preference.eq(artwork_preference))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref compilation_artist_names) = new_settings.compilation_artist_names {
+			let compilation_artist_names: Vec<&str> = compilation_artist_names
+				.iter()
+				.flat_map(|n| n.lines())
+				.map(str::trim)
+				.filter(|n| !n.is_empty())
+				.collect();
+			diesel::update(misc_settings::table)
+				.set(
+					misc_settings::index_compilation_artist_names
+						.eq(compilation_artist_names.join("\n")),
+				)
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref transcode_targets) = new_settings.transcode_targets {
+			for target in transcode_targets {
+				target
+					.parse::<transcode::Options>()
+					.map_err(|_| Error::TranscodeTargetInvalid)?;
+			}
+			let transcode_targets: Vec<&str> = transcode_targets.iter().map(|t| t.trim()).collect();
+			diesel::update(misc_settings::table)
+				.set(misc_settings::transcode_targets.eq(transcode_targets.join(",")))
+				.execute(&mut connection)?;
+		}
+
 		Ok(())
 	}
 }
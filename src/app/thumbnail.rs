@@ -1,11 +1,18 @@
+use image::imageops::FilterType;
 use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, ImageOutputFormat};
+use log::{error, warn};
 use std::cmp;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::fs::{self, File};
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once};
+use std::thread;
 
-use crate::utils::{get_audio_format, AudioFormat};
+use crate::utils::{get_audio_format, read_env_var, AudioFormat};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -23,13 +30,88 @@ pub enum Error {
 	Mp4aMeta(PathBuf, mp4ameta::Error),
 	#[error("This file format is not supported: {0}")]
 	UnsupportedFormat(&'static str),
+	#[error("Too many thumbnails are being generated")]
+	TooManyRequests,
 }
 
-#[derive(Debug, Hash)]
+// Image formats which can be decoded (matches the features enabled on the `image` crate)
+pub const IMAGE_FORMATS: &[&str] = &["bmp", "gif", "jpeg", "png", "webp"];
+
+// Name of cached artwork extracted from audio files, next to their thumbnails
+const ORIGINAL_ARTWORK_NAME: &str = "original";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Format {
+	#[default]
+	Jpeg,
+	Png,
+	WebP,
+}
+
+impl Format {
+	// The `image` crate is built without its `webp-encoder` feature, so WebP thumbnails are
+	// encoded as JPEG instead
+	fn encodable(self) -> Self {
+		match self {
+			Self::WebP => Self::Jpeg,
+			format => format,
+		}
+	}
+
+	pub fn is_encodable(self) -> bool {
+		self.encodable() == self
+	}
+
+	// Same as `encodable`, but warns the first time a format falls back to another one
+	fn resolve(self) -> Self {
+		static FALLBACK_WARNING: Once = Once::new();
+		let format = self.encodable();
+		if format != self {
+			FALLBACK_WARNING.call_once(|| {
+				warn!(
+					"This server cannot encode {:?} thumbnails, they are encoded as {:?} instead",
+					self, format
+				)
+			});
+		}
+		format
+	}
+
+	fn extension(self) -> &'static str {
+		match self {
+			Self::Jpeg => "jpg",
+			Self::Png => "png",
+			Self::WebP => "webp",
+		}
+	}
+
+	fn output_format(self) -> ImageOutputFormat {
+		match self {
+			Self::Png => ImageOutputFormat::Png,
+			_ => ImageOutputFormat::Jpeg(80),
+		}
+	}
+}
+
+// How non-square images are fit into a square thumbnail
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Fit {
+	// Scaled to fit, keeping the thumbnail the shape of the image
+	Contain,
+	// Scaled to fill the square, cropping the edges of the image
+	Cover,
+	// Scaled to fit, then padded with `Options::pad_color`
+	#[default]
+	Pad,
+}
+
+#[derive(Clone, Copy, Debug, Hash)]
 pub struct Options {
 	pub max_dimension: Option<u32>,
 	pub resize_if_almost_square: bool,
-	pub pad_to_square: bool,
+	pub fit: Fit,
+	pub pad_color: [u8; 3],
+	pub format: Format,
 }
 
 impl Default for Options {
@@ -37,20 +119,130 @@ impl Default for Options {
 		Self {
 			max_dimension: Some(400),
 			resize_if_almost_square: true,
-			pad_to_square: true,
+			fit: Fit::default(),
+			pad_color: [255, 255, 255],
+			format: Format::default(),
+		}
+	}
+}
+
+// Resampling filter used to scale images down. When none is set, a faster but lower quality
+// algorithm is used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResizeFilter {
+	Nearest,
+	Triangle,
+	CatmullRom,
+	Lanczos3,
+}
+
+impl FromStr for ResizeFilter {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"nearest" => Ok(Self::Nearest),
+			"triangle" => Ok(Self::Triangle),
+			"catmullrom" => Ok(Self::CatmullRom),
+			"lanczos" | "lanczos3" => Ok(Self::Lanczos3),
+			_ => Err(()),
+		}
+	}
+}
+
+impl From<ResizeFilter> for FilterType {
+	fn from(filter: ResizeFilter) -> Self {
+		match filter {
+			ResizeFilter::Nearest => FilterType::Nearest,
+			ResizeFilter::Triangle => FilterType::Triangle,
+			ResizeFilter::CatmullRom => FilterType::CatmullRom,
+			ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+		}
+	}
+}
+
+// Limits how many thumbnails are generated simultaneously.
+// Requests beyond this limit wait for their turn, unless too many are already waiting.
+struct Limiter {
+	max_active: usize,
+	max_queued: usize,
+	state: Mutex<LimiterState>,
+	condvar: Condvar,
+}
+
+#[derive(Default)]
+struct LimiterState {
+	active: usize,
+	queued: usize,
+}
+
+struct Permit<'a> {
+	limiter: &'a Limiter,
+}
+
+impl Limiter {
+	fn new(max_active: usize, max_queued: usize) -> Self {
+		Self {
+			max_active: max_active.max(1),
+			max_queued,
+			state: Mutex::new(LimiterState::default()),
+			condvar: Condvar::new(),
+		}
+	}
+
+	fn acquire(&self) -> Result<Permit<'_>, Error> {
+		let mut state = self.state.lock().unwrap();
+		if state.active >= self.max_active {
+			if state.queued >= self.max_queued {
+				return Err(Error::TooManyRequests);
+			}
+			state.queued += 1;
+			while state.active >= self.max_active {
+				state = self.condvar.wait(state).unwrap();
+			}
+			state.queued -= 1;
 		}
+		state.active += 1;
+		Ok(Permit { limiter: self })
 	}
 }
 
+impl Drop for Permit<'_> {
+	fn drop(&mut self) {
+		let mut state = self.limiter.state.lock().unwrap();
+		state.active -= 1;
+		self.limiter.condvar.notify_one();
+	}
+}
+
+// Outcome of `Manager::prewarm_thumbnails`, in number of images
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrewarmReport {
+	pub already_cached: usize,
+	pub generated: usize,
+	pub failed: usize,
+}
+
 #[derive(Clone)]
 pub struct Manager {
 	thumbnails_dir_path: PathBuf,
+	limiter: Arc<Limiter>,
+	// Not part of the thumbnail cache keys, so changing it only affects thumbnails generated later
+	resize_filter: Option<ResizeFilter>,
+	// Thumbnails being generated, so identical concurrent requests only generate them once
+	in_progress: Arc<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>>,
 }
 
 impl Manager {
 	pub fn new(thumbnails_dir_path: PathBuf) -> Self {
+		let max_active =
+			read_env_var("POLARIS_MAX_CONCURRENT_THUMBNAILS").unwrap_or_else(num_cpus::get);
+		let max_queued = read_env_var("POLARIS_MAX_QUEUED_THUMBNAILS").unwrap_or(max_active * 16);
 		Self {
 			thumbnails_dir_path,
+			limiter: Arc::new(Limiter::new(max_active, max_queued)),
+			resize_filter: read_env_var("POLARIS_THUMBNAIL_RESIZE_FILTER"),
+			in_progress: Arc::default(),
 		}
 	}
 
@@ -59,16 +251,179 @@ impl Manager {
 		image_path: &Path,
 		thumbnailoptions: &Options,
 	) -> Result<PathBuf, Error> {
+		// Formats are resolved first, so that fallbacks share cache entries with their target format
+		let thumbnailoptions = &Options {
+			format: thumbnailoptions.format.resolve(),
+			..*thumbnailoptions
+		};
 		match self.retrieve_thumbnail(image_path, thumbnailoptions) {
 			Some(path) => Ok(path),
-			None => self.create_thumbnail(image_path, thumbnailoptions),
+			None => self.create_thumbnail_once(image_path, thumbnailoptions),
+		}
+	}
+
+	// Returns a file containing the original artwork, without any resizing or re-encoding.
+	// Image files are returned as-is, artwork embedded in audio files is extracted and cached.
+	pub fn get_original_artwork(&self, image_path: &Path) -> Result<PathBuf, Error> {
+		let Some(format) = get_audio_format(image_path) else {
+			return Ok(image_path.to_owned());
+		};
+
+		if let Some(path) = self.retrieve_original_artwork(image_path) {
+			return Ok(path);
 		}
+
+		let data = read_embedded_artwork(image_path, format)?;
+		let extension = image::guess_format(&data)
+			.ok()
+			.and_then(|f| f.extensions_str().first().copied())
+			.unwrap_or("jpg");
+		let source_dir = self.get_source_dir_path(image_path);
+		fs::create_dir_all(&source_dir).map_err(|e| Error::Io(source_dir.clone(), e))?;
+		let path = source_dir.join(format!("{}.{}", ORIGINAL_ARTWORK_NAME, extension));
+		let temp_path = path.with_extension("tmp");
+		fs::write(&temp_path, data).map_err(|e| Error::Io(temp_path.clone(), e))?;
+		fs::rename(&temp_path, &path).map_err(|e| Error::Io(path.clone(), e))?;
+		Ok(path)
+	}
+
+	// Generates thumbnails ahead of their first request, a few images at a time. Passing no options
+	// prewarms the original artwork, which only needs to be extracted from audio files.
+	pub fn prewarm_thumbnails(
+		&self,
+		image_paths: &[PathBuf],
+		thumbnailoptions: Option<&Options>,
+	) -> PrewarmReport {
+		let next = AtomicUsize::new(0);
+		let num_workers = self.limiter.max_active.min(image_paths.len());
+		thread::scope(|scope| {
+			let workers: Vec<_> = (0..num_workers)
+				.map(|_| scope.spawn(|| self.prewarm_worker(image_paths, &next, thumbnailoptions)))
+				.collect();
+			workers.into_iter().filter_map(|w| w.join().ok()).fold(
+				PrewarmReport::default(),
+				|total, report| PrewarmReport {
+					already_cached: total.already_cached + report.already_cached,
+					generated: total.generated + report.generated,
+					failed: total.failed + report.failed,
+				},
+			)
+		})
+	}
+
+	// Prewarms images from `image_paths` until none are left, starting with the one at `next`
+	fn prewarm_worker(
+		&self,
+		image_paths: &[PathBuf],
+		next: &AtomicUsize,
+		thumbnailoptions: Option<&Options>,
+	) -> PrewarmReport {
+		let mut report = PrewarmReport::default();
+		while let Some(image_path) = image_paths.get(next.fetch_add(1, Ordering::Relaxed)) {
+			match self.prewarm_thumbnail(image_path, thumbnailoptions) {
+				Ok(true) => report.generated += 1,
+				Ok(false) => report.already_cached += 1,
+				Err(e) => {
+					error!("Could not prewarm thumbnail: {}", e);
+					report.failed += 1;
+				}
+			}
+		}
+		report
+	}
+
+	// Returns whether anything had to be generated
+	fn prewarm_thumbnail(
+		&self,
+		image_path: &Path,
+		thumbnailoptions: Option<&Options>,
+	) -> Result<bool, Error> {
+		fs::metadata(image_path).map_err(|e| Error::Io(image_path.to_owned(), e))?;
+		let Some(thumbnailoptions) = thumbnailoptions else {
+			if get_audio_format(image_path).is_none()
+				|| self.retrieve_original_artwork(image_path).is_some()
+			{
+				return Ok(false);
+			}
+			self.get_original_artwork(image_path)?;
+			return Ok(true);
+		};
+		let thumbnailoptions = &Options {
+			format: thumbnailoptions.format.resolve(),
+			..*thumbnailoptions
+		};
+		if self
+			.retrieve_thumbnail(image_path, thumbnailoptions)
+			.is_some()
+		{
+			return Ok(false);
+		}
+		self.create_thumbnail_once(image_path, thumbnailoptions)?;
+		Ok(true)
+	}
+
+	// Removes cached thumbnails of images which were modified after their thumbnails were created.
+	// Returns how many images had their thumbnails removed.
+	pub fn invalidate_thumbnails<I, P>(&self, image_paths: I) -> Result<usize, Error>
+	where
+		I: IntoIterator<Item = P>,
+		P: AsRef<Path>,
+	{
+		let mut num_invalidated = 0;
+		for image_path in image_paths {
+			let image_path = image_path.as_ref();
+			let source_dir = self.get_source_dir_path(image_path);
+			if !source_dir.is_dir() {
+				continue;
+			}
+			let image_modified = fs::metadata(image_path).and_then(|m| m.modified()).ok();
+			let thumbnails =
+				fs::read_dir(&source_dir).map_err(|e| Error::Io(source_dir.clone(), e))?;
+			let is_stale = thumbnails.flatten().any(|thumbnail| {
+				let thumbnail_modified = thumbnail.metadata().and_then(|m| m.modified()).ok();
+				match (image_modified, thumbnail_modified) {
+					(Some(image), Some(thumbnail)) => image > thumbnail,
+					_ => true,
+				}
+			});
+			if is_stale {
+				fs::remove_dir_all(&source_dir).map_err(|e| Error::Io(source_dir.clone(), e))?;
+				num_invalidated += 1;
+			}
+		}
+		Ok(num_invalidated)
+	}
+
+	// Removes all cached thumbnails. Returns how many images had their thumbnails removed.
+	pub fn invalidate_all_thumbnails(&self) -> Result<usize, Error> {
+		if !self.thumbnails_dir_path.is_dir() {
+			return Ok(0);
+		}
+		let entries = fs::read_dir(&self.thumbnails_dir_path)
+			.map_err(|e| Error::Io(self.thumbnails_dir_path.clone(), e))?;
+		let mut num_invalidated = 0;
+		for entry in entries.flatten() {
+			let path = entry.path();
+			if path.is_dir() {
+				fs::remove_dir_all(&path).map_err(|e| Error::Io(path.clone(), e))?;
+				num_invalidated += 1;
+			} else {
+				fs::remove_file(&path).map_err(|e| Error::Io(path.clone(), e))?;
+			}
+		}
+		Ok(num_invalidated)
+	}
+
+	// Thumbnails are grouped in one directory per source image, so they can be invalidated together
+	fn get_source_dir_path(&self, image_path: &Path) -> PathBuf {
+		let hash = Manager::hash(image_path);
+		self.thumbnails_dir_path.join(hash.to_string())
 	}
 
 	fn get_thumbnail_path(&self, image_path: &Path, thumbnailoptions: &Options) -> PathBuf {
-		let hash = Manager::hash(image_path, thumbnailoptions);
-		let mut thumbnail_path = self.thumbnails_dir_path.clone();
-		thumbnail_path.push(format!("{}.jpg", hash));
+		let hash = Manager::hash(thumbnailoptions);
+		let mut thumbnail_path = self.get_source_dir_path(image_path);
+		thumbnail_path.push(format!("{}.{}", hash, thumbnailoptions.format.extension()));
 		thumbnail_path
 	}
 
@@ -81,35 +436,91 @@ impl Manager {
 		}
 	}
 
+	fn retrieve_original_artwork(&self, image_path: &Path) -> Option<PathBuf> {
+		let source_dir = self.get_source_dir_path(image_path);
+		fs::read_dir(source_dir)
+			.ok()?
+			.flatten()
+			.map(|e| e.path())
+			.find(|p| {
+				p.file_stem() == Some(ORIGINAL_ARTWORK_NAME.as_ref())
+					&& p.extension().is_some_and(|e| e != "tmp")
+			})
+	}
+
+	fn create_thumbnail_once(
+		&self,
+		image_path: &Path,
+		thumbnailoptions: &Options,
+	) -> Result<PathBuf, Error> {
+		let thumbnail_path = self.get_thumbnail_path(image_path, thumbnailoptions);
+		let lock = {
+			let mut in_progress = self.in_progress.lock().unwrap();
+			in_progress
+				.entry(thumbnail_path.clone())
+				.or_default()
+				.clone()
+		};
+
+		let result = {
+			let _guard = lock.lock().unwrap();
+			match self.retrieve_thumbnail(image_path, thumbnailoptions) {
+				Some(path) => Ok(path),
+				None => self.create_thumbnail(image_path, thumbnailoptions),
+			}
+		};
+
+		let mut in_progress = self.in_progress.lock().unwrap();
+		if Arc::strong_count(&lock) == 2 {
+			in_progress.remove(&thumbnail_path);
+		}
+
+		result
+	}
+
 	fn create_thumbnail(
 		&self,
 		image_path: &Path,
 		thumbnailoptions: &Options,
 	) -> Result<PathBuf, Error> {
-		let thumbnail = generate_thumbnail(image_path, thumbnailoptions)?;
-		let quality = 80;
+		let _permit = self.limiter.acquire()?;
+		let thumbnail = generate_thumbnail(image_path, thumbnailoptions, self.resize_filter)?;
 
-		fs::create_dir_all(&self.thumbnails_dir_path)
-			.map_err(|e| Error::Io(self.thumbnails_dir_path.clone(), e))?;
+		let source_dir = self.get_source_dir_path(image_path);
+		fs::create_dir_all(&source_dir).map_err(|e| Error::Io(source_dir.clone(), e))?;
 		let path = self.get_thumbnail_path(image_path, thumbnailoptions);
+		// Thumbnails are written under a temporary name so they are never served half-written
+		let temp_path = path.with_extension("tmp");
 		let mut out_file =
-			File::create(&path).map_err(|e| Error::Io(self.thumbnails_dir_path.clone(), e))?;
+			File::create(&temp_path).map_err(|e| Error::Io(source_dir.clone(), e))?;
 		thumbnail
-			.write_to(&mut out_file, ImageOutputFormat::Jpeg(quality))
+			.write_to(&mut out_file, thumbnailoptions.format.output_format())
 			.map_err(|e| Error::Image(image_path.to_owned(), e))?;
+		fs::rename(&temp_path, &path).map_err(|e| Error::Io(path.clone(), e))?;
 		Ok(path)
 	}
 
-	fn hash(path: &Path, thumbnailoptions: &Options) -> u64 {
+	fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
 		let mut hasher = DefaultHasher::new();
-		path.hash(&mut hasher);
-		thumbnailoptions.hash(&mut hasher);
+		value.hash(&mut hasher);
 		hasher.finish()
 	}
 }
 
-fn generate_thumbnail(image_path: &Path, options: &Options) -> Result<DynamicImage, Error> {
+fn generate_thumbnail(
+	image_path: &Path,
+	options: &Options,
+	filter: Option<ResizeFilter>,
+) -> Result<DynamicImage, Error> {
 	let source_image = DynamicImage::ImageRgb8(read(image_path)?.into_rgb8());
+	let scale = |width, height| match filter {
+		Some(f) => source_image.resize(width, height, f.into()),
+		None => source_image.thumbnail(width, height),
+	};
+	let scale_exact = |width, height| match filter {
+		Some(f) => source_image.resize_exact(width, height, f.into()),
+		None => source_image.thumbnail_exact(width, height),
+	};
 	let (source_width, source_height) = source_image.dimensions();
 	let largest_dimension = cmp::max(source_width, source_height);
 	let out_dimension = cmp::min(
@@ -117,16 +528,32 @@ fn generate_thumbnail(image_path: &Path, options: &Options) -> Result<DynamicIma
 		largest_dimension,
 	);
 
+	if options.fit == Fit::Cover {
+		// Images are never scaled up, so the thumbnail is at most as large as their shortest side
+		let smallest_dimension = cmp::min(source_width, source_height);
+		let out_dimension = cmp::min(out_dimension, smallest_dimension);
+		let ratio = out_dimension as f32 / smallest_dimension as f32;
+		let scaled_width = cmp::max((source_width as f32 * ratio).round() as u32, out_dimension);
+		let scaled_height = cmp::max((source_height as f32 * ratio).round() as u32, out_dimension);
+		let scaled_image = scale_exact(scaled_width, scaled_height);
+		return Ok(scaled_image.crop_imm(
+			(scaled_width - out_dimension) / 2,
+			(scaled_height - out_dimension) / 2,
+			out_dimension,
+			out_dimension,
+		));
+	}
+
 	let source_aspect_ratio: f32 = source_width as f32 / source_height as f32;
 	let is_almost_square = source_aspect_ratio > 0.8 && source_aspect_ratio < 1.2;
 
 	let mut final_image;
 	if is_almost_square && options.resize_if_almost_square {
-		final_image = source_image.thumbnail_exact(out_dimension, out_dimension);
-	} else if options.pad_to_square {
-		let scaled_image = source_image.thumbnail(out_dimension, out_dimension);
+		final_image = scale_exact(out_dimension, out_dimension);
+	} else if options.fit == Fit::Pad {
+		let scaled_image = scale(out_dimension, out_dimension);
 		let (scaled_width, scaled_height) = scaled_image.dimensions();
-		let background = image::Rgb([255, 255_u8, 255_u8]);
+		let background = image::Rgb(options.pad_color);
 		final_image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(
 			out_dimension,
 			out_dimension,
@@ -140,7 +567,7 @@ fn generate_thumbnail(image_path: &Path, options: &Options) -> Result<DynamicIma
 			)
 			.map_err(|e| Error::Image(image_path.to_owned(), e))?;
 	} else {
-		final_image = source_image.thumbnail(out_dimension, out_dimension);
+		final_image = scale(out_dimension, out_dimension);
 	}
 
 	Ok(final_image)
@@ -148,75 +575,388 @@ fn generate_thumbnail(image_path: &Path, options: &Options) -> Result<DynamicIma
 
 fn read(image_path: &Path) -> Result<DynamicImage, Error> {
 	match get_audio_format(image_path) {
-		Some(AudioFormat::AIFF) => read_aiff(image_path),
-		Some(AudioFormat::FLAC) => read_flac(image_path),
-		Some(AudioFormat::MP3) => read_mp3(image_path),
-		Some(AudioFormat::OGG) => read_vorbis(image_path),
-		Some(AudioFormat::OPUS) => read_opus(image_path),
-		Some(AudioFormat::WAVE) => read_wave(image_path),
-		Some(AudioFormat::APE) | Some(AudioFormat::MPC) => read_ape(image_path),
-		Some(AudioFormat::MP4) | Some(AudioFormat::M4B) => read_mp4(image_path),
-		None => image::open(image_path).map_err(|e| Error::Image(image_path.to_owned(), e)),
+		Some(format) => {
+			let data = read_embedded_artwork(image_path, format)?;
+			image::load_from_memory(&data).map_err(|e| Error::Image(image_path.to_owned(), e))
+		}
+		None => read_image(image_path),
+	}
+}
+
+// Returns the undecoded bytes of the artwork embedded in an audio file
+fn read_embedded_artwork(path: &Path, format: AudioFormat) -> Result<Vec<u8>, Error> {
+	match format {
+		AudioFormat::AIFF => read_aiff(path),
+		AudioFormat::FLAC => read_flac(path),
+		AudioFormat::MP3 => read_mp3(path),
+		AudioFormat::OGG => read_vorbis(path),
+		AudioFormat::OPUS => read_opus(path),
+		AudioFormat::WAVE => read_wave(path),
+		AudioFormat::APE | AudioFormat::MPC => read_ape(path),
+		AudioFormat::MP4 | AudioFormat::M4B => read_mp4(path),
 	}
 }
 
-fn read_ape(_: &Path) -> Result<DynamicImage, Error> {
+// Animated images (eg. GIF) are decoded as their first frame.
+// The image format is detected from file contents, so misnamed files can still be read.
+fn read_image(path: &Path) -> Result<DynamicImage, Error> {
+	image::io::Reader::open(path)
+		.map_err(|e| Error::Io(path.to_owned(), e))?
+		.with_guessed_format()
+		.map_err(|e| Error::Io(path.to_owned(), e))?
+		.decode()
+		.map_err(|e| Error::Image(path.to_owned(), e))
+}
+
+fn read_ape(_: &Path) -> Result<Vec<u8>, Error> {
 	Err(Error::UnsupportedFormat("ape"))
 }
 
-fn read_flac(path: &Path) -> Result<DynamicImage, Error> {
+fn read_flac(path: &Path) -> Result<Vec<u8>, Error> {
 	let tag =
 		metaflac::Tag::read_from_path(path).map_err(|e| Error::Metaflac(path.to_owned(), e))?;
-	if let Some(p) = tag.pictures().next() {
-		return image::load_from_memory(&p.data).map_err(|e| Error::Image(path.to_owned(), e));
-	}
-	Err(Error::EmbeddedArtworkNotFound(path.to_owned()))
+	let data = tag.pictures().next().map(|p| p.data.clone());
+	data.ok_or_else(|| Error::EmbeddedArtworkNotFound(path.to_owned()))
 }
 
-fn read_mp3(path: &Path) -> Result<DynamicImage, Error> {
+fn read_mp3(path: &Path) -> Result<Vec<u8>, Error> {
 	let tag = id3::Tag::read_from_path(path).map_err(|e| Error::Id3(path.to_owned(), e))?;
 	read_id3(path, &tag)
 }
 
-fn read_aiff(path: &Path) -> Result<DynamicImage, Error> {
+fn read_aiff(path: &Path) -> Result<Vec<u8>, Error> {
 	let tag = id3::Tag::read_from_aiff_path(path).map_err(|e| Error::Id3(path.to_owned(), e))?;
 	read_id3(path, &tag)
 }
 
-fn read_wave(path: &Path) -> Result<DynamicImage, Error> {
+fn read_wave(path: &Path) -> Result<Vec<u8>, Error> {
 	let tag = id3::Tag::read_from_wav_path(path).map_err(|e| Error::Id3(path.to_owned(), e))?;
 	read_id3(path, &tag)
 }
 
-fn read_id3(path: &Path, tag: &id3::Tag) -> Result<DynamicImage, Error> {
+fn read_id3(path: &Path, tag: &id3::Tag) -> Result<Vec<u8>, Error> {
 	tag.pictures()
-		.next()
+		.find(|p| p.mime_type != "-->") // Linked pictures only hold the location of an image file
+		.map(|p| p.data.clone())
 		.ok_or_else(|| Error::EmbeddedArtworkNotFound(path.to_owned()))
-		.and_then(|d| {
-			image::load_from_memory(&d.data).map_err(|e| Error::Image(path.to_owned(), e))
-		})
 }
 
-fn read_mp4(path: &Path) -> Result<DynamicImage, Error> {
+fn read_mp4(path: &Path) -> Result<Vec<u8>, Error> {
 	let tag =
 		mp4ameta::Tag::read_from_path(path).map_err(|e| Error::Mp4aMeta(path.to_owned(), e))?;
 	tag.artwork()
+		.map(|a| a.data.to_vec())
 		.ok_or_else(|| Error::EmbeddedArtworkNotFound(path.to_owned()))
-		.and_then(|d| image::load_from_memory(d.data).map_err(|e| Error::Image(path.to_owned(), e)))
 }
 
-fn read_vorbis(_: &Path) -> Result<DynamicImage, Error> {
+fn read_vorbis(_: &Path) -> Result<Vec<u8>, Error> {
 	Err(Error::UnsupportedFormat("vorbis"))
 }
 
-fn read_opus(_: &Path) -> Result<DynamicImage, Error> {
+fn read_opus(_: &Path) -> Result<Vec<u8>, Error> {
 	Err(Error::UnsupportedFormat("opus"))
 }
 
 #[cfg(test)]
 mod test {
 
+	use std::time::{Duration, SystemTime};
+
 	use super::*;
+	use crate::test::prepare_test_directory;
+	use crate::test_name;
+
+	#[test]
+	fn can_read_image_with_wrong_extension() {
+		let test_directory = prepare_test_directory(test_name!());
+		let image_path = test_directory.join("Folder.jpg");
+		fs::copy("test-data/artwork/Folder.webp", &image_path).unwrap();
+		let expected = image::open("test-data/artwork/Folder.png")
+			.unwrap()
+			.to_rgb8();
+		assert_eq!(read(&image_path).unwrap().to_rgb8(), expected);
+	}
+
+	#[test]
+	fn limiter_rejects_requests_beyond_queue_depth() {
+		let limiter = Limiter::new(1, 0);
+		let permit = limiter.acquire().unwrap();
+		assert!(matches!(limiter.acquire(), Err(Error::TooManyRequests)));
+		drop(permit);
+		assert!(limiter.acquire().is_ok());
+	}
+
+	#[test]
+	fn limiter_queues_requests() {
+		let limiter = Arc::new(Limiter::new(1, 1));
+		let permit = limiter.acquire().unwrap();
+		let waiting_thread = {
+			let limiter = limiter.clone();
+			std::thread::spawn(move || limiter.acquire().is_ok())
+		};
+		while limiter.state.lock().unwrap().queued == 0 {
+			std::thread::yield_now();
+		}
+		assert!(matches!(limiter.acquire(), Err(Error::TooManyRequests)));
+		drop(permit);
+		assert!(waiting_thread.join().unwrap());
+	}
+
+	#[test]
+	fn coalesces_concurrent_requests() {
+		let test_directory = prepare_test_directory(test_name!());
+		let manager = Manager::new(test_directory.join("thumbnails"));
+		let image_path = Path::new("test-data/small-collection/Tobokegao/Picnic/Folder.png");
+
+		let threads: Vec<_> = (0..8)
+			.map(|_| {
+				let manager = manager.clone();
+				std::thread::spawn(move || manager.get_thumbnail(image_path, &Options::default()))
+			})
+			.collect();
+		let paths: Vec<PathBuf> = threads
+			.into_iter()
+			.map(|t| t.join().unwrap().unwrap())
+			.collect();
+
+		assert!(paths.iter().all(|p| p == &paths[0]));
+		assert!(image::open(&paths[0]).is_ok());
+		assert!(manager.in_progress.lock().unwrap().is_empty());
+	}
+
+	#[test]
+	fn pads_with_requested_color() {
+		let test_directory = prepare_test_directory(test_name!());
+		let image_path = test_directory.join("wide.png");
+		ImageBuffer::from_pixel(40, 20, image::Rgb([255_u8, 0, 0]))
+			.save(&image_path)
+			.unwrap();
+
+		let options = Options {
+			pad_color: [16, 32, 64],
+			..Default::default()
+		};
+		let thumbnail = generate_thumbnail(&image_path, &options, None)
+			.unwrap()
+			.to_rgb8();
+		assert_eq!(thumbnail.dimensions(), (40, 40));
+		assert_eq!(thumbnail.get_pixel(0, 0), &image::Rgb([16, 32, 64]));
+		assert_eq!(thumbnail.get_pixel(20, 20), &image::Rgb([255, 0, 0]));
+	}
+
+	#[test]
+	fn fits_non_square_images() {
+		let test_directory = prepare_test_directory(test_name!());
+		let image_path = test_directory.join("wide.png");
+		let mut image = ImageBuffer::from_pixel(60, 20, image::Rgb([255_u8, 0, 0]));
+		for y in 0..20 {
+			for x in 20..40 {
+				image.put_pixel(x, y, image::Rgb([0, 0, 255]));
+			}
+		}
+		image.save(&image_path).unwrap();
+
+		let thumbnail = |fit: Fit| {
+			let options = Options {
+				fit,
+				..Default::default()
+			};
+			generate_thumbnail(&image_path, &options, None)
+				.unwrap()
+				.to_rgb8()
+		};
+
+		let contained = thumbnail(Fit::Contain);
+		assert_eq!(contained.dimensions(), (60, 20));
+
+		let covered = thumbnail(Fit::Cover);
+		assert_eq!(covered.dimensions(), (20, 20));
+		assert_eq!(covered.get_pixel(0, 0), &image::Rgb([0, 0, 255]));
+		assert_eq!(covered.get_pixel(19, 19), &image::Rgb([0, 0, 255]));
+
+		let padded = thumbnail(Fit::Pad);
+		assert_eq!(padded.dimensions(), (60, 60));
+		assert_eq!(padded.get_pixel(0, 0), &image::Rgb([255, 255, 255]));
+	}
+
+	#[test]
+	fn encodes_requested_format() {
+		let test_directory = prepare_test_directory(test_name!());
+		let manager = Manager::new(test_directory.join("thumbnails"));
+		let image_path = Path::new("test-data/artwork/Folder.png");
+
+		let jpeg = manager
+			.get_thumbnail(image_path, &Options::default())
+			.unwrap();
+		let png_options = Options {
+			format: Format::Png,
+			..Default::default()
+		};
+		let png = manager.get_thumbnail(image_path, &png_options).unwrap();
+		assert_ne!(jpeg, png);
+		assert_eq!(
+			image::guess_format(&fs::read(&jpeg).unwrap()).unwrap(),
+			image::ImageFormat::Jpeg
+		);
+		assert_eq!(
+			image::guess_format(&fs::read(&png).unwrap()).unwrap(),
+			image::ImageFormat::Png
+		);
+
+		let webp_options = Options {
+			format: Format::WebP,
+			..Default::default()
+		};
+		let webp = manager.get_thumbnail(image_path, &webp_options).unwrap();
+		assert_eq!(webp, jpeg);
+	}
+
+	#[test]
+	fn can_resize_with_filters() {
+		assert_eq!(
+			ResizeFilter::from_str("Lanczos"),
+			Ok(ResizeFilter::Lanczos3)
+		);
+		assert_eq!(ResizeFilter::from_str("bicubic"), Err(()));
+
+		let image_path = Path::new("test-data/artwork/Folder.png");
+		let options = Options {
+			max_dimension: Some(100),
+			fit: Fit::Contain,
+			..Default::default()
+		};
+		let expected = generate_thumbnail(image_path, &options, None)
+			.unwrap()
+			.dimensions();
+		for filter in [
+			ResizeFilter::Nearest,
+			ResizeFilter::Triangle,
+			ResizeFilter::CatmullRom,
+			ResizeFilter::Lanczos3,
+		] {
+			let thumbnail = generate_thumbnail(image_path, &options, Some(filter)).unwrap();
+			assert_eq!(thumbnail.dimensions(), expected);
+		}
+	}
+
+	#[test]
+	fn can_invalidate_stale_thumbnails() {
+		let test_directory = prepare_test_directory(test_name!());
+		let image_path = test_directory.join("Folder.png");
+		fs::copy("test-data/artwork/Folder.png", &image_path).unwrap();
+		let manager = Manager::new(test_directory.join("thumbnails"));
+		let options = Options::default();
+
+		let thumbnail_path = manager.get_thumbnail(&image_path, &options).unwrap();
+		assert_eq!(manager.invalidate_thumbnails([&image_path]).unwrap(), 0);
+		assert!(thumbnail_path.exists());
+
+		let later = SystemTime::now() + Duration::from_secs(60);
+		File::options()
+			.write(true)
+			.open(&image_path)
+			.unwrap()
+			.set_modified(later)
+			.unwrap();
+		assert_eq!(manager.invalidate_thumbnails([&image_path]).unwrap(), 1);
+		assert!(!thumbnail_path.exists());
+	}
+
+	#[test]
+	fn can_invalidate_all_thumbnails() {
+		let test_directory = prepare_test_directory(test_name!());
+		let manager = Manager::new(test_directory.join("thumbnails"));
+		let options = Options::default();
+
+		let images = [
+			"test-data/artwork/Folder.png",
+			"test-data/artwork/Embedded.png",
+		];
+		let thumbnail_paths: Vec<PathBuf> = images
+			.iter()
+			.map(|i| manager.get_thumbnail(Path::new(i), &options).unwrap())
+			.collect();
+		assert_eq!(manager.invalidate_all_thumbnails().unwrap(), 2);
+		assert!(thumbnail_paths.iter().all(|p| !p.exists()));
+	}
+
+	#[test]
+	fn can_prewarm_thumbnails() {
+		let test_directory = prepare_test_directory(test_name!());
+		let manager = Manager::new(test_directory.join("thumbnails"));
+		let options = Options::default();
+
+		let image_paths: Vec<PathBuf> = [
+			"test-data/artwork/Folder.png",
+			"test-data/artwork/not-an-image.png",
+			"test-data/artwork/Embedded.png",
+		]
+		.iter()
+		.map(PathBuf::from)
+		.collect();
+		manager.get_thumbnail(&image_paths[0], &options).unwrap();
+
+		let report = manager.prewarm_thumbnails(&image_paths, Some(&options));
+		assert_eq!(
+			report,
+			PrewarmReport {
+				already_cached: 1,
+				generated: 1,
+				failed: 1,
+			}
+		);
+		let report = manager.prewarm_thumbnails(&image_paths, Some(&options));
+		assert_eq!(report.already_cached, 2);
+		assert_eq!(report.generated, 0);
+	}
+
+	#[test]
+	fn can_prewarm_original_artwork() {
+		let test_directory = prepare_test_directory(test_name!());
+		let manager = Manager::new(test_directory.join("thumbnails"));
+
+		let image_paths: Vec<PathBuf> = [
+			"test-data/artwork/Folder.png",
+			"test-data/artwork/sample.mp3",
+		]
+		.iter()
+		.map(PathBuf::from)
+		.collect();
+		let report = manager.prewarm_thumbnails(&image_paths, None);
+		assert_eq!(report.already_cached, 1);
+		assert_eq!(report.generated, 1);
+		assert!(manager.retrieve_original_artwork(&image_paths[1]).is_some());
+	}
+
+	#[test]
+	fn can_get_original_artwork() {
+		let test_directory = prepare_test_directory(test_name!());
+		let manager = Manager::new(test_directory.join("thumbnails"));
+
+		let image_path = Path::new("test-data/artwork/Folder.png");
+		assert_eq!(
+			manager.get_original_artwork(image_path).unwrap(),
+			image_path
+		);
+
+		let audio_path = Path::new("test-data/artwork/sample.mp3");
+		let artwork_path = manager.get_original_artwork(audio_path).unwrap();
+		assert_eq!(artwork_path.extension(), Some("png".as_ref()));
+		assert_eq!(
+			image::open(&artwork_path).unwrap().to_rgb8(),
+			image::open("test-data/artwork/Embedded.png")
+				.unwrap()
+				.to_rgb8()
+		);
+		assert_eq!(
+			manager.get_original_artwork(audio_path).unwrap(),
+			artwork_path
+		);
+
+		assert!(manager
+			.get_original_artwork(Path::new("test-data/artwork/sample.ogg"))
+			.is_err());
+	}
 
 	#[test]
 	fn can_read_artwork_data() {
@@ -232,6 +972,16 @@ mod test {
 			.to_rgb8();
 		assert_eq!(folder_img, ext_img);
 
+		let webp_img = read(Path::new("test-data/artwork/Folder.webp"))
+			.unwrap()
+			.to_rgb8();
+		assert_eq!(webp_img, ext_img);
+
+		let gif_img = read(Path::new("test-data/artwork/Animated.gif"))
+			.unwrap()
+			.to_rgb8();
+		assert_eq!(gif_img, ext_img);
+
 		let aiff_img = read(Path::new("test-data/artwork/sample.aif"))
 			.unwrap()
 			.to_rgb8();
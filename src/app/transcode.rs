@@ -0,0 +1,423 @@
+use log::error;
+use std::ffi::OsString;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::app::index::CueSegment;
+use crate::utils::read_env_var;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("Could not start ffmpeg to transcode `{0}`:\n\n{1}")]
+	Spawn(PathBuf, std::io::Error),
+	#[error("Too many transcodes in progress")]
+	TooManyRequests,
+}
+
+// In kbps
+const DEFAULT_BITRATE: u32 = 128;
+const MIN_BITRATE: u32 = 32;
+const MAX_BITRATE: u32 = 320;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Codec {
+	Mp3,
+	Opus,
+	// Not available to clients, only used to cut cue sheet tracks out of their audio file
+	Flac,
+}
+
+impl FromStr for Codec {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"mp3" => Ok(Self::Mp3),
+			"opus" => Ok(Self::Opus),
+			_ => Err(()),
+		}
+	}
+}
+
+impl Codec {
+	pub fn mime_type(&self) -> &'static str {
+		match self {
+			Self::Mp3 => "audio/mpeg",
+			Self::Opus => "audio/ogg",
+			Self::Flac => "audio/flac",
+		}
+	}
+
+	fn encoder(&self) -> &'static str {
+		match self {
+			Self::Mp3 => "libmp3lame",
+			Self::Opus => "libopus",
+			Self::Flac => "flac",
+		}
+	}
+
+	fn container(&self) -> &'static str {
+		match self {
+			Self::Mp3 => "mp3",
+			Self::Opus => "ogg",
+			Self::Flac => "flac",
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Options {
+	pub codec: Codec,
+	pub bitrate: u32,
+}
+
+// Written as `codec:bitrate` (eg. `mp3:192`) in the allowed transcode targets setting
+impl FromStr for Options {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (codec, bitrate) = s.trim().split_once(':').ok_or(())?;
+		let bitrate: u32 = bitrate.trim().parse().map_err(|_| ())?;
+		if !(MIN_BITRATE..=MAX_BITRATE).contains(&bitrate) {
+			return Err(());
+		}
+		Ok(Self {
+			codec: codec.trim().parse()?,
+			bitrate,
+		})
+	}
+}
+
+impl Options {
+	// Bitrates are clamped to a range supported by all codecs
+	pub fn new(codec: Codec, bitrate: Option<u32>) -> Self {
+		Self {
+			codec,
+			bitrate: bitrate
+				.unwrap_or(DEFAULT_BITRATE)
+				.clamp(MIN_BITRATE, MAX_BITRATE),
+		}
+	}
+
+	pub fn lossless() -> Self {
+		Self {
+			codec: Codec::Flac,
+			bitrate: 0,
+		}
+	}
+}
+
+// Transcodes last as long as the songs they stream, so requests beyond the limit are rejected
+// instead of waiting for a slot
+struct Limiter {
+	max_active: usize,
+	active: Mutex<usize>,
+}
+
+struct Permit {
+	limiter: Arc<Limiter>,
+}
+
+impl Limiter {
+	fn new(max_active: usize) -> Self {
+		Self {
+			max_active: max_active.max(1),
+			active: Mutex::new(0),
+		}
+	}
+
+	fn acquire(self: &Arc<Self>) -> Result<Permit, Error> {
+		let mut active = self.active.lock().unwrap();
+		if *active >= self.max_active {
+			return Err(Error::TooManyRequests);
+		}
+		*active += 1;
+		Ok(Permit {
+			limiter: self.clone(),
+		})
+	}
+}
+
+impl Drop for Permit {
+	fn drop(&mut self) {
+		*self.limiter.active.lock().unwrap() -= 1;
+	}
+}
+
+// Output of an ffmpeg process. The process is stopped when this is dropped, so clients
+// disconnecting halfway through a song do not leave it running.
+pub struct Transcode {
+	child: Child,
+	stdout: ChildStdout,
+	_permit: Option<Permit>,
+}
+
+impl Read for Transcode {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.stdout.read(buf)?;
+		if read == 0 && !buf.is_empty() {
+			let status = self.child.wait()?;
+			if !status.success() {
+				return Err(io::Error::other(format!("ffmpeg exited with {}", status)));
+			}
+		}
+		Ok(read)
+	}
+}
+
+impl Drop for Transcode {
+	fn drop(&mut self) {
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+	}
+}
+
+// The ffmpeg executable is looked up in the `PATH`, unless `POLARIS_FFMPEG_PATH` is set
+fn ffmpeg_path() -> PathBuf {
+	read_env_var("POLARIS_FFMPEG_PATH").unwrap_or_else(|| PathBuf::from("ffmpeg"))
+}
+
+// Whether the ffmpeg executable can be run at all, which transcoding depends on
+pub fn is_available() -> bool {
+	Command::new(ffmpeg_path())
+		.arg("-version")
+		.stdin(Stdio::null())
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.status()
+		.map(|status| status.success())
+		.unwrap_or(false)
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	limiter: Arc<Limiter>,
+}
+
+impl Manager {
+	pub fn new() -> Self {
+		let max_active =
+			read_env_var("POLARIS_MAX_CONCURRENT_TRANSCODES").unwrap_or_else(num_cpus::get);
+		Self {
+			limiter: Arc::new(Limiter::new(max_active)),
+		}
+	}
+
+	// Only the portion of the file covered by `segment` is transcoded, when set
+	pub fn transcode(
+		&self,
+		path: &Path,
+		segment: Option<&CueSegment>,
+		options: &Options,
+	) -> Result<Transcode, Error> {
+		let permit = self.limiter.acquire()?;
+		let mut transcode = spawn(&ffmpeg_path(), path, segment, options)?;
+		transcode._permit = Some(permit);
+		Ok(transcode)
+	}
+}
+
+impl Default for Manager {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+fn spawn(
+	ffmpeg: &Path,
+	path: &Path,
+	segment: Option<&CueSegment>,
+	options: &Options,
+) -> Result<Transcode, Error> {
+	let mut child = Command::new(ffmpeg)
+		.args(arguments(path, segment, options))
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.map_err(|e| Error::Spawn(path.to_owned(), e))?;
+	let (Some(stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+		let _ = child.kill();
+		return Err(Error::Spawn(
+			path.to_owned(),
+			io::ErrorKind::BrokenPipe.into(),
+		));
+	};
+	// Read until ffmpeg exits, so that it never blocks on a full stderr pipe
+	let source = path.to_owned();
+	std::thread::spawn(move || {
+		let mut output = String::new();
+		let _ = stderr.read_to_string(&mut output);
+		if !output.trim().is_empty() {
+			error!(
+				"ffmpeg reported errors while transcoding `{}`:\n\n{}",
+				source.display(),
+				output.trim()
+			);
+		}
+	});
+	Ok(Transcode {
+		child,
+		stdout,
+		_permit: None,
+	})
+}
+
+fn timestamp(ms: u32) -> String {
+	format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+fn arguments(path: &Path, segment: Option<&CueSegment>, options: &Options) -> Vec<OsString> {
+	let mut arguments: Vec<OsString> = vec!["-v".into(), "error".into()];
+	if let Some(segment) = segment {
+		arguments.extend(["-ss".into(), timestamp(segment.start_ms).into()]);
+		if let Some(end_ms) = segment.end_ms {
+			arguments.extend(["-to".into(), timestamp(end_ms).into()]);
+		}
+	}
+	arguments.push("-i".into());
+	arguments.push(path.as_os_str().to_owned());
+	// Only the first audio stream is kept, leaving out embedded artwork
+	arguments.extend(["-map", "0:a:0", "-c:a", options.codec.encoder()].map(OsString::from));
+	if options.codec != Codec::Flac {
+		arguments.extend(["-b:a".into(), format!("{}k", options.bitrate).into()]);
+	}
+	arguments.extend(["-f", options.codec.container(), "pipe:1"].map(OsString::from));
+	arguments
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn can_parse_codecs() {
+		assert_eq!(Codec::from_str("MP3"), Ok(Codec::Mp3));
+		assert_eq!(Codec::from_str("opus"), Ok(Codec::Opus));
+		assert_eq!(Codec::from_str("flac"), Err(()));
+	}
+
+	#[test]
+	fn can_parse_targets() {
+		assert_eq!(
+			Options::from_str("mp3:192"),
+			Ok(Options::new(Codec::Mp3, Some(192)))
+		);
+		assert_eq!(
+			Options::from_str(" OPUS : 96 "),
+			Ok(Options::new(Codec::Opus, Some(96)))
+		);
+		assert_eq!(Options::from_str("mp3"), Err(()));
+		assert_eq!(Options::from_str("mp3:9000"), Err(()));
+		assert_eq!(Options::from_str("flac:320"), Err(()));
+	}
+
+	#[test]
+	fn clamps_bitrate() {
+		assert_eq!(Options::new(Codec::Mp3, None).bitrate, 128);
+		assert_eq!(Options::new(Codec::Mp3, Some(8)).bitrate, 32);
+		assert_eq!(Options::new(Codec::Mp3, Some(9000)).bitrate, 320);
+	}
+
+	#[test]
+	fn passes_options_to_ffmpeg() {
+		let path = Path::new("music/song.flac");
+		let arguments = arguments(path, None, &Options::new(Codec::Opus, Some(96)));
+		let arguments: Vec<&str> = arguments.iter().filter_map(|a| a.to_str()).collect();
+		assert_eq!(
+			arguments,
+			vec![
+				"-v",
+				"error",
+				"-i",
+				"music/song.flac",
+				"-map",
+				"0:a:0",
+				"-c:a",
+				"libopus",
+				"-b:a",
+				"96k",
+				"-f",
+				"ogg",
+				"pipe:1"
+			]
+		);
+	}
+
+	#[test]
+	fn only_transcodes_cue_track_segment() {
+		let segment = CueSegment {
+			audio_path: PathBuf::from("music/album.flac"),
+			start_ms: 61_500,
+			end_ms: Some(125_040),
+		};
+		let ffmpeg_arguments = arguments(
+			&segment.audio_path,
+			Some(&segment),
+			&Options::new(Codec::Mp3, None),
+		);
+		let ffmpeg_arguments: Vec<&str> =
+			ffmpeg_arguments.iter().filter_map(|a| a.to_str()).collect();
+		assert_eq!(
+			ffmpeg_arguments[..8],
+			[
+				"-v",
+				"error",
+				"-ss",
+				"61.500",
+				"-to",
+				"125.040",
+				"-i",
+				"music/album.flac"
+			]
+		);
+
+		let segment = CueSegment {
+			end_ms: None,
+			..segment
+		};
+		let ffmpeg_arguments = arguments(
+			&segment.audio_path,
+			Some(&segment),
+			&Options::new(Codec::Mp3, None),
+		);
+		let ffmpeg_arguments: Vec<&str> =
+			ffmpeg_arguments.iter().filter_map(|a| a.to_str()).collect();
+		assert_eq!(
+			ffmpeg_arguments[..6],
+			["-v", "error", "-ss", "61.500", "-i", "music/album.flac"]
+		);
+
+		let ffmpeg_arguments = arguments(&segment.audio_path, Some(&segment), &Options::lossless());
+		let ffmpeg_arguments: Vec<&str> =
+			ffmpeg_arguments.iter().filter_map(|a| a.to_str()).collect();
+		assert_eq!(
+			ffmpeg_arguments[6..],
+			["-map", "0:a:0", "-c:a", "flac", "-f", "flac", "pipe:1"]
+		);
+	}
+
+	#[test]
+	fn limiter_rejects_transcodes_beyond_limit() {
+		let limiter = Arc::new(Limiter::new(1));
+		let permit = limiter.acquire().unwrap();
+		assert!(matches!(limiter.acquire(), Err(Error::TooManyRequests)));
+		drop(permit);
+		assert!(limiter.acquire().is_ok());
+	}
+
+	#[test]
+	fn reports_missing_ffmpeg() {
+		let ffmpeg = Path::new("test-data/not-ffmpeg");
+		let result = spawn(
+			ffmpeg,
+			Path::new("song.flac"),
+			None,
+			&Options::new(Codec::Mp3, None),
+		);
+		assert!(matches!(result, Err(Error::Spawn(_, _))));
+	}
+}
@@ -3,10 +3,18 @@ use pbkdf2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, Salt
 use pbkdf2::Pbkdf2;
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::app::settings::AuthSecret;
-use crate::db::{self, users, DB};
+use crate::app::settings::{self, AuthSecret, PasswordPolicy};
+use crate::db::{self, device_preferences, users, DB};
+use crate::utils::read_env_var;
+
+const MAX_LOGIN_LOCKOUT: Duration = Duration::from_secs(60 * 60);
+const FAILED_LOGIN_MEMORY: Duration = Duration::from_secs(15 * 60); // Before a lockout
+const MAX_TRACKED_LOGIN_FAILURES: usize = 10_000;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -18,6 +26,8 @@ pub enum Error {
 	EmptyUsername,
 	#[error("Cannot use empty password")]
 	EmptyPassword,
+	#[error("{0}")]
+	WeakPassword(String),
 	#[error("Username does not exist")]
 	IncorrectUsername,
 	#[error("Password does not match username")]
@@ -34,6 +44,8 @@ pub enum Error {
 	AuthorizationTokenEncoding,
 	#[error("Failed to encode Branca token")]
 	BrancaTokenEncoding,
+	#[error("Too many failed login attempts, retry in {0:?}")]
+	TooManyLoginAttempts(Duration),
 }
 
 #[derive(Debug, Insertable, Queryable)]
@@ -79,15 +91,146 @@ pub struct Preferences {
 	pub web_theme_accent: Option<String>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreferenceType {
+	String,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreferenceField {
+	pub name: String,
+	#[serde(rename = "type")]
+	pub value_type: PreferenceType,
+	pub default: serde_json::Value,
+	// Read-only fields are ignored when writing preferences
+	pub read_only: bool,
+	// Per-device fields can be overridden for individual devices
+	pub per_device: bool,
+}
+
+impl Preferences {
+	const FIELDS: [(&'static str, PreferenceType, bool, bool); 3] = [
+		("lastfm_username", PreferenceType::String, true, false),
+		("web_theme_base", PreferenceType::String, false, true),
+		("web_theme_accent", PreferenceType::String, false, true),
+	];
+
+	// Default values are read from `Preferences::default()`
+	pub fn schema() -> Vec<PreferenceField> {
+		let defaults = serde_json::to_value(Preferences::default()).unwrap_or_default();
+		Self::FIELDS
+			.iter()
+			.map(
+				|(name, value_type, read_only, per_device)| PreferenceField {
+					name: name.to_string(),
+					value_type: *value_type,
+					default: defaults.get(name).cloned().unwrap_or_default(),
+					read_only: *read_only,
+					per_device: *per_device,
+				},
+			)
+			.collect()
+	}
+}
+
+#[derive(Debug)]
+struct FailedLogins {
+	count: u32,
+	locked_until: Option<Instant>,
+	// Failures are forgotten after this point, so lockouts do not keep growing for clients which
+	// stopped retrying
+	forget_at: Instant,
+}
+
+// Failed logins are tracked per username and client address, so an attacker cannot lock a user
+// out from another address. Clients sharing an address, such as those of a reverse proxy whose
+// forwarding headers are not trusted, share their lockouts too.
+type LoginKey = (String, Option<IpAddr>);
+
+// Locks clients out of a username after repeated failed logins, for a duration doubling with each
+// further failure
+struct LoginThrottle {
+	max_failed_attempts: u32,
+	base_lockout: Duration,
+	failures: Mutex<HashMap<LoginKey, FailedLogins>>,
+}
+
+impl LoginThrottle {
+	fn new(max_failed_attempts: u32, base_lockout: Duration) -> Self {
+		Self {
+			max_failed_attempts: max_failed_attempts.max(1),
+			base_lockout,
+			failures: Mutex::default(),
+		}
+	}
+
+	fn check(&self, key: &LoginKey, now: Instant) -> Result<(), Error> {
+		let failures = self.failures.lock().unwrap();
+		let locked_until = failures.get(key).and_then(|f| f.locked_until);
+		match locked_until.map(|t| t.saturating_duration_since(now)) {
+			Some(remaining) if !remaining.is_zero() => Err(Error::TooManyLoginAttempts(remaining)),
+			_ => Ok(()),
+		}
+	}
+
+	fn record_failure(&self, key: &LoginKey, now: Instant) {
+		let mut failures = self.failures.lock().unwrap();
+		failures.retain(|_, f| f.forget_at > now);
+		if failures.len() >= MAX_TRACKED_LOGIN_FAILURES && !failures.contains_key(key) {
+			let oldest = failures
+				.iter()
+				.min_by_key(|(_, f)| f.forget_at)
+				.map(|(k, _)| k.clone());
+			if let Some(oldest) = oldest {
+				failures.remove(&oldest);
+			}
+		}
+		let entry = failures.entry(key.clone()).or_insert(FailedLogins {
+			count: 0,
+			locked_until: None,
+			forget_at: now,
+		});
+		entry.count += 1;
+		entry.forget_at = now + FAILED_LOGIN_MEMORY;
+		if entry.count >= self.max_failed_attempts {
+			let exponent = (entry.count - self.max_failed_attempts).min(16);
+			let lockout = self
+				.base_lockout
+				.saturating_mul(1 << exponent)
+				.min(MAX_LOGIN_LOCKOUT);
+			entry.locked_until = Some(now + lockout);
+			// Clients which wait out a whole lockout period without retrying start over
+			entry.forget_at = now + FAILED_LOGIN_MEMORY.max(lockout.saturating_mul(2));
+		}
+	}
+
+	fn record_success(&self, key: &LoginKey) {
+		self.failures.lock().unwrap().remove(key);
+	}
+}
+
 #[derive(Clone)]
 pub struct Manager {
 	db: DB,
 	auth_secret: AuthSecret,
+	settings_manager: settings::Manager,
+	login_throttle: Arc<LoginThrottle>,
 }
 
 impl Manager {
-	pub fn new(db: DB, auth_secret: AuthSecret) -> Self {
-		Self { db, auth_secret }
+	pub fn new(db: DB, auth_secret: AuthSecret, settings_manager: settings::Manager) -> Self {
+		let max_failed_attempts = read_env_var("POLARIS_MAX_FAILED_LOGINS").unwrap_or(5);
+		let base_lockout = read_env_var("POLARIS_LOGIN_LOCKOUT_SECONDS").unwrap_or(30);
+		Self {
+			db,
+			auth_secret,
+			settings_manager,
+			login_throttle: Arc::new(LoginThrottle::new(
+				max_failed_attempts,
+				Duration::from_secs(base_lockout),
+			)),
+		}
 	}
 
 	pub fn create(&self, new_user: &NewUser) -> Result<(), Error> {
@@ -95,6 +238,7 @@ impl Manager {
 			return Err(Error::EmptyUsername);
 		}
 
+		self.check_password_policy(&new_user.password)?;
 		let password_hash = hash_password(&new_user.password)?;
 		let mut connection = self.db.connect()?;
 		let new_user = User {
@@ -117,6 +261,7 @@ impl Manager {
 	}
 
 	pub fn set_password(&self, username: &str, password: &str) -> Result<(), Error> {
+		self.check_password_policy(password)?;
 		let hash = hash_password(password)?;
 		let mut connection = self.db.connect()?;
 		use crate::db::users::dsl::*;
@@ -126,6 +271,14 @@ impl Manager {
 		Ok(())
 	}
 
+	fn check_password_policy(&self, password: &str) -> Result<(), Error> {
+		let policy = self
+			.settings_manager
+			.get_password_policy()
+			.unwrap_or_default();
+		check_password_strength(&policy, password)
+	}
+
 	pub fn set_is_admin(&self, username: &str, is_admin: bool) -> Result<(), Error> {
 		use crate::db::users::dsl::*;
 		let mut connection = self.db.connect()?;
@@ -135,7 +288,26 @@ impl Manager {
 		Ok(())
 	}
 
-	pub fn login(&self, username: &str, password: &str) -> Result<AuthToken, Error> {
+	pub fn login(
+		&self,
+		username: &str,
+		password: &str,
+		client: Option<IpAddr>,
+	) -> Result<AuthToken, Error> {
+		let key = (username.to_owned(), client);
+		self.login_throttle.check(&key, Instant::now())?;
+		let result = self.check_credentials(username, password);
+		match result {
+			Ok(_) => self.login_throttle.record_success(&key),
+			Err(Error::IncorrectUsername) | Err(Error::IncorrectPassword) => {
+				self.login_throttle.record_failure(&key, Instant::now())
+			}
+			Err(_) => (),
+		}
+		result
+	}
+
+	fn check_credentials(&self, username: &str, password: &str) -> Result<AuthToken, Error> {
 		use crate::db::users::dsl::*;
 		let mut connection = self.db.connect()?;
 		match users
@@ -244,33 +416,96 @@ impl Manager {
 		Ok(is_admin != 0)
 	}
 
-	pub fn read_preferences(&self, username: &str) -> Result<Preferences, Error> {
+	// Preferences which are not set for the device fall back to the user preferences
+	pub fn read_preferences(
+		&self,
+		username: &str,
+		device: Option<&str>,
+	) -> Result<Preferences, Error> {
 		use crate::db::users::dsl::*;
 		let mut connection = self.db.connect()?;
-		let (theme_base, theme_accent, read_lastfm_username) = users
-			.select((web_theme_base, web_theme_accent, lastfm_username))
+		let (user_id, theme_base, theme_accent, read_lastfm_username): (
+			i32,
+			Option<String>,
+			Option<String>,
+			Option<String>,
+		) = users
+			.select((id, web_theme_base, web_theme_accent, lastfm_username))
 			.filter(name.eq(username))
 			.get_result(&mut connection)?;
-		Ok(Preferences {
+		let mut preferences = Preferences {
 			web_theme_base: theme_base,
 			web_theme_accent: theme_accent,
 			lastfm_username: read_lastfm_username,
-		})
+		};
+
+		if let Some(device) = device {
+			let overrides: Option<(Option<String>, Option<String>)> = device_preferences::table
+				.select((
+					device_preferences::web_theme_base,
+					device_preferences::web_theme_accent,
+				))
+				.filter(device_preferences::owner.eq(user_id))
+				.filter(device_preferences::device.eq(device))
+				.get_result(&mut connection)
+				.optional()?;
+			if let Some((device_theme_base, device_theme_accent)) = overrides {
+				preferences.web_theme_base = device_theme_base.or(preferences.web_theme_base);
+				preferences.web_theme_accent = device_theme_accent.or(preferences.web_theme_accent);
+			}
+		}
+
+		Ok(preferences)
 	}
 
+	// When writing preferences for a device, fields which are unset or identical to the user
+	// preferences are not stored, so that they keep following the user preferences
 	pub fn write_preferences(
 		&self,
 		username: &str,
+		device: Option<&str>,
 		preferences: &Preferences,
 	) -> Result<(), Error> {
 		use crate::db::users::dsl::*;
 		let mut connection = self.db.connect()?;
-		diesel::update(users.filter(name.eq(username)))
-			.set((
-				web_theme_base.eq(&preferences.web_theme_base),
-				web_theme_accent.eq(&preferences.web_theme_accent),
-			))
-			.execute(&mut connection)?;
+		let Some(device) = device else {
+			diesel::update(users.filter(name.eq(username)))
+				.set((
+					web_theme_base.eq(&preferences.web_theme_base),
+					web_theme_accent.eq(&preferences.web_theme_accent),
+				))
+				.execute(&mut connection)?;
+			return Ok(());
+		};
+
+		let (user_id, theme_base, theme_accent): (i32, Option<String>, Option<String>) = users
+			.select((id, web_theme_base, web_theme_accent))
+			.filter(name.eq(username))
+			.get_result(&mut connection)?;
+		let override_of = |device_value: &Option<String>, user_value: Option<String>| {
+			device_value
+				.clone()
+				.filter(|v| Some(v) != user_value.as_ref())
+		};
+		let device_theme_base = override_of(&preferences.web_theme_base, theme_base);
+		let device_theme_accent = override_of(&preferences.web_theme_accent, theme_accent);
+		connection.transaction::<_, diesel::result::Error, _>(|connection| {
+			diesel::delete(
+				device_preferences::table
+					.filter(device_preferences::owner.eq(user_id))
+					.filter(device_preferences::device.eq(device)),
+			)
+			.execute(&mut *connection)?;
+			diesel::insert_into(device_preferences::table)
+				.values((
+					device_preferences::owner.eq(user_id),
+					device_preferences::device.eq(device),
+					device_preferences::web_theme_base.eq(&device_theme_base),
+					device_preferences::web_theme_accent.eq(&device_theme_accent),
+				))
+				.execute(&mut *connection)?;
+			Ok(())
+		})?;
 		Ok(())
 	}
 
@@ -323,6 +558,26 @@ impl Manager {
 	}
 }
 
+fn check_password_strength(policy: &PasswordPolicy, password: &str) -> Result<(), Error> {
+	if password.chars().count() < policy.min_length {
+		return Err(Error::WeakPassword(format!(
+			"Password must be at least {} characters long",
+			policy.min_length
+		)));
+	}
+	if policy.require_complexity {
+		let has_lowercase = password.chars().any(|c| c.is_lowercase());
+		let has_uppercase = password.chars().any(|c| c.is_uppercase());
+		let has_digit = password.chars().any(|c| c.is_numeric());
+		if !(has_lowercase && has_uppercase && has_digit) {
+			return Err(Error::WeakPassword(
+				"Password must contain lowercase letters, uppercase letters and digits".to_owned(),
+			));
+		}
+	}
+	Ok(())
+}
+
 fn hash_password(password: &str) -> Result<String, Error> {
 	if password.is_empty() {
 		return Err(Error::EmptyPassword);
@@ -397,6 +652,44 @@ mod test {
 		));
 	}
 
+	#[test]
+	fn cannot_create_user_with_weak_password() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		ctx.settings_manager
+			.amend(&settings::NewSettings {
+				password_min_length: Some(20),
+				..Default::default()
+			})
+			.unwrap();
+		let new_user = NewUser {
+			name: TEST_USERNAME.to_owned(),
+			password: TEST_PASSWORD.to_owned(),
+			admin: false,
+		};
+		let Error::WeakPassword(reason) = ctx.user_manager.create(&new_user).unwrap_err() else {
+			panic!("Expected weak password error");
+		};
+		assert_eq!(reason, "Password must be at least 20 characters long");
+	}
+
+	#[test]
+	fn password_strength_checks_are_opt_in() {
+		let policy = PasswordPolicy::default();
+		assert!(check_password_strength(&policy, "a").is_ok());
+	}
+
+	#[test]
+	fn password_strength_checks_complexity() {
+		let policy = PasswordPolicy {
+			min_length: 8,
+			require_complexity: true,
+		};
+		assert!(check_password_strength(&policy, "Sh0rt").is_err());
+		assert!(check_password_strength(&policy, "alllowercase1").is_err());
+		assert!(check_password_strength(&policy, "NoDigitsHere").is_err());
+		assert!(check_password_strength(&policy, "Correct1Horse").is_ok());
+	}
+
 	#[test]
 	fn cannot_create_duplicate_user() {
 		let ctx = test::ContextBuilder::new(test_name!()).build();
@@ -427,13 +720,107 @@ mod test {
 		ctx.user_manager.create(&new_user).unwrap();
 
 		ctx.user_manager
-			.write_preferences(TEST_USERNAME, &new_preferences)
+			.write_preferences(TEST_USERNAME, None, &new_preferences)
 			.unwrap();
 
-		let read_preferences = ctx.user_manager.read_preferences("Walter").unwrap();
+		let read_preferences = ctx.user_manager.read_preferences("Walter", None).unwrap();
 		assert_eq!(new_preferences, read_preferences);
 	}
 
+	#[test]
+	fn device_preferences_fall_back_to_user_preferences() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		let new_user = NewUser {
+			name: TEST_USERNAME.to_owned(),
+			password: TEST_PASSWORD.to_owned(),
+			admin: false,
+		};
+		ctx.user_manager.create(&new_user).unwrap();
+
+		let user_preferences = Preferences {
+			web_theme_base: Some("very-dark-theme".to_owned()),
+			web_theme_accent: Some("#FF0000".to_owned()),
+			lastfm_username: None,
+		};
+		ctx.user_manager
+			.write_preferences(TEST_USERNAME, None, &user_preferences)
+			.unwrap();
+
+		let device_preferences = Preferences {
+			web_theme_accent: Some("#00FF00".to_owned()),
+			..Default::default()
+		};
+		ctx.user_manager
+			.write_preferences(TEST_USERNAME, Some("phone"), &device_preferences)
+			.unwrap();
+
+		let phone = ctx
+			.user_manager
+			.read_preferences(TEST_USERNAME, Some("phone"))
+			.unwrap();
+		assert_eq!(phone.web_theme_base, Some("very-dark-theme".to_owned()));
+		assert_eq!(phone.web_theme_accent, Some("#00FF00".to_owned()));
+
+		let laptop = ctx
+			.user_manager
+			.read_preferences(TEST_USERNAME, Some("laptop"))
+			.unwrap();
+		assert_eq!(laptop, user_preferences);
+
+		let user = ctx
+			.user_manager
+			.read_preferences(TEST_USERNAME, None)
+			.unwrap();
+		assert_eq!(user, user_preferences);
+	}
+
+	#[test]
+	fn device_preferences_only_store_overrides() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		let new_user = NewUser {
+			name: TEST_USERNAME.to_owned(),
+			password: TEST_PASSWORD.to_owned(),
+			admin: false,
+		};
+		ctx.user_manager.create(&new_user).unwrap();
+
+		let user_preferences = Preferences {
+			web_theme_base: Some("very-dark-theme".to_owned()),
+			web_theme_accent: Some("#FF0000".to_owned()),
+			lastfm_username: None,
+		};
+		ctx.user_manager
+			.write_preferences(TEST_USERNAME, None, &user_preferences)
+			.unwrap();
+
+		// Writing back the merged preferences of a device only overrides what was changed
+		let mut phone = ctx
+			.user_manager
+			.read_preferences(TEST_USERNAME, Some("phone"))
+			.unwrap();
+		phone.web_theme_accent = Some("#00FF00".to_owned());
+		ctx.user_manager
+			.write_preferences(TEST_USERNAME, Some("phone"), &phone)
+			.unwrap();
+
+		let user_preferences = Preferences {
+			web_theme_base: Some("light-theme".to_owned()),
+			..user_preferences
+		};
+		ctx.user_manager
+			.write_preferences(TEST_USERNAME, None, &user_preferences)
+			.unwrap();
+
+		let phone = ctx
+			.user_manager
+			.read_preferences(TEST_USERNAME, Some("phone"))
+			.unwrap();
+		assert_eq!(phone.web_theme_base, Some("light-theme".to_owned()));
+		assert_eq!(phone.web_theme_accent, Some("#00FF00".to_owned()));
+	}
+
 	#[test]
 	fn login_rejects_bad_password() {
 		let ctx = test::ContextBuilder::new(test_name!()).build();
@@ -447,7 +834,7 @@ mod test {
 		ctx.user_manager.create(&new_user).unwrap();
 		assert!(matches!(
 			ctx.user_manager
-				.login(TEST_USERNAME, "not the password")
+				.login(TEST_USERNAME, "not the password", None)
 				.unwrap_err(),
 			Error::IncorrectPassword
 		));
@@ -462,7 +849,99 @@ mod test {
 			admin: false,
 		};
 		ctx.user_manager.create(&new_user).unwrap();
-		assert!(ctx.user_manager.login(TEST_USERNAME, TEST_PASSWORD).is_ok())
+		assert!(ctx
+			.user_manager
+			.login(TEST_USERNAME, TEST_PASSWORD, None)
+			.is_ok())
+	}
+
+	#[test]
+	fn login_locks_out_after_repeated_failures() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let new_user = NewUser {
+			name: TEST_USERNAME.to_owned(),
+			password: TEST_PASSWORD.to_owned(),
+			admin: false,
+		};
+		ctx.user_manager.create(&new_user).unwrap();
+
+		for _ in 0..5 {
+			assert!(matches!(
+				ctx.user_manager
+					.login(TEST_USERNAME, "not the password", None)
+					.unwrap_err(),
+				Error::IncorrectPassword
+			));
+		}
+		assert!(matches!(
+			ctx.user_manager
+				.login(TEST_USERNAME, TEST_PASSWORD, None)
+				.unwrap_err(),
+			Error::TooManyLoginAttempts(_)
+		));
+	}
+
+	#[test]
+	fn login_throttle_backs_off_exponentially() {
+		let throttle = LoginThrottle::new(2, Duration::from_secs(10));
+		let key = (TEST_USERNAME.to_owned(), None);
+		let now = Instant::now();
+		throttle.record_failure(&key, now);
+		assert!(throttle.check(&key, now).is_ok());
+
+		throttle.record_failure(&key, now);
+		let Err(Error::TooManyLoginAttempts(first_lockout)) = throttle.check(&key, now) else {
+			panic!("Expected lockout after two failures");
+		};
+		assert!(first_lockout <= Duration::from_secs(10));
+
+		throttle.record_failure(&key, now);
+		let Err(Error::TooManyLoginAttempts(second_lockout)) = throttle.check(&key, now) else {
+			panic!("Expected lockout after three failures");
+		};
+		assert!(second_lockout > Duration::from_secs(10));
+
+		throttle.record_success(&key);
+		assert!(throttle.check(&key, now).is_ok());
+	}
+
+	#[test]
+	fn login_throttle_is_per_client() {
+		let throttle = LoginThrottle::new(1, Duration::from_secs(10));
+		let attacker = (TEST_USERNAME.to_owned(), Some(IpAddr::from([10, 0, 0, 1])));
+		let user = (TEST_USERNAME.to_owned(), Some(IpAddr::from([10, 0, 0, 2])));
+		let now = Instant::now();
+		throttle.record_failure(&attacker, now);
+		assert!(throttle.check(&attacker, now).is_err());
+		assert!(throttle.check(&user, now).is_ok());
+	}
+
+	#[test]
+	fn login_throttle_forgets_old_failures() {
+		let throttle = LoginThrottle::new(2, Duration::from_secs(10));
+		let key = (TEST_USERNAME.to_owned(), None);
+		let now = Instant::now();
+		throttle.record_failure(&key, now);
+		throttle.record_failure(&key, now);
+		assert!(throttle.check(&key, now).is_err());
+
+		// A single failure long after the lockout expired does not lock the client out again
+		let later = now + FAILED_LOGIN_MEMORY + Duration::from_secs(1);
+		assert!(throttle.check(&key, later).is_ok());
+		throttle.record_failure(&key, later);
+		assert!(throttle.check(&key, later).is_ok());
+	}
+
+	#[test]
+	fn login_throttle_evicts_expired_entries() {
+		let throttle = LoginThrottle::new(5, Duration::from_secs(10));
+		let now = Instant::now();
+		for i in 0..100 {
+			throttle.record_failure(&(format!("user{i}"), None), now);
+		}
+		let later = now + FAILED_LOGIN_MEMORY + Duration::from_secs(1);
+		throttle.record_failure(&(TEST_USERNAME.to_owned(), None), later);
+		assert_eq!(throttle.failures.lock().unwrap().len(), 1);
 	}
 
 	#[test]
@@ -496,7 +975,7 @@ mod test {
 		ctx.user_manager.create(&new_user).unwrap();
 		let token = ctx
 			.user_manager
-			.login(TEST_USERNAME, TEST_PASSWORD)
+			.login(TEST_USERNAME, TEST_PASSWORD, None)
 			.unwrap();
 		let authorization = ctx
 			.user_manager
@@ -534,4 +1013,16 @@ mod test {
 			Error::IncorrectAuthorizationScope
 		));
 	}
+
+	#[test]
+	fn preferences_schema_covers_every_field() {
+		let preferences = serde_json::to_value(Preferences::default()).unwrap();
+		let mut expected: Vec<&String> = preferences.as_object().unwrap().keys().collect();
+		expected.sort();
+
+		let schema = Preferences::schema();
+		let mut names: Vec<&String> = schema.iter().map(|f| &f.name).collect();
+		names.sort();
+		assert_eq!(names, expected);
+	}
 }
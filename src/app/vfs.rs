@@ -1,5 +1,6 @@
 use core::ops::Deref;
 use diesel::prelude::*;
+use log::warn;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::path::{self, Path, PathBuf};
@@ -45,6 +46,39 @@ impl From<MountDir> for Mount {
 	}
 }
 
+// Two mounts whose sources point into the same real directories
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MountOverlap {
+	pub name: String,
+	pub nested_name: String,
+}
+
+// Finds mounts whose source is located inside (or is the same as) the source of another mount.
+// Sources are canonicalized when possible so that symlinked directories are detected as well.
+pub fn find_overlaps(mounts: &[Mount]) -> Vec<MountOverlap> {
+	let sources: Vec<PathBuf> = mounts
+		.iter()
+		.map(|m| m.source.canonicalize().unwrap_or_else(|_| m.source.clone()))
+		.collect();
+	let mut overlaps = Vec::new();
+	for (i, outer) in mounts.iter().enumerate() {
+		for (j, inner) in mounts.iter().enumerate() {
+			if i == j || !sources[j].starts_with(&sources[i]) {
+				continue;
+			}
+			// Identical sources only need to be reported once
+			if j < i && sources[i] == sources[j] {
+				continue;
+			}
+			overlaps.push(MountOverlap {
+				name: outer.name.clone(),
+				nested_name: inner.name.clone(),
+			});
+		}
+	}
+	overlaps
+}
+
 #[allow(clippy::upper_case_acronyms)]
 pub struct VFS {
 	mounts: Vec<Mount>,
@@ -55,18 +89,30 @@ impl VFS {
 		VFS { mounts }
 	}
 
+	// When mount sources are nested, the most specific mount is used.
+	// Mounts with identical sources are resolved in declaration order.
 	pub fn real_to_virtual<P: AsRef<Path>>(&self, real_path: P) -> Result<PathBuf, Error> {
-		for mount in &self.mounts {
-			if let Ok(p) = real_path.as_ref().strip_prefix(&mount.source) {
+		let best_match = self
+			.mounts
+			.iter()
+			.rev()
+			.filter_map(|mount| {
+				let p = real_path.as_ref().strip_prefix(&mount.source).ok()?;
+				Some((mount, p))
+			})
+			.max_by_key(|(mount, _)| mount.source.components().count());
+
+		match best_match {
+			Some((mount, p)) => {
 				let mount_path = Path::new(&mount.name);
-				return if p.components().count() == 0 {
+				if p.components().count() == 0 {
 					Ok(mount_path.to_path_buf())
 				} else {
 					Ok(mount_path.join(p))
-				};
+				}
 			}
+			None => Err(Error::CouldNotMapToVirtualPath(real_path.as_ref().into())),
 		}
-		Err(Error::CouldNotMapToVirtualPath(real_path.as_ref().into()))
 	}
 
 	pub fn virtual_to_real<P: AsRef<Path>>(&self, virtual_path: P) -> Result<PathBuf, Error> {
@@ -107,13 +153,15 @@ impl Manager {
 	pub fn mount_dirs(&self) -> Result<Vec<MountDir>, Error> {
 		use self::mount_points::dsl::*;
 		let mut connection = self.db.connect()?;
+		// Mounts are listed in declaration order, which decides between mounts with identical sources
 		let mount_dirs: Vec<MountDir> = mount_points
 			.select((source, name))
+			.order(id)
 			.get_results(&mut connection)?;
 		Ok(mount_dirs)
 	}
 
-	pub fn set_mount_dirs(&self, mount_dirs: &[MountDir]) -> Result<(), Error> {
+	pub fn set_mount_dirs(&self, mount_dirs: &[MountDir]) -> Result<Vec<MountOverlap>, Error> {
 		let mut connection = self.db.connect()?;
 		connection.transaction::<_, diesel::result::Error, _>(|connection| {
 			use self::mount_points::dsl::*;
@@ -123,7 +171,16 @@ impl Manager {
 				.execute(&mut *connection)?; // TODO https://github.com/diesel-rs/diesel/issues/1822
 			Ok(())
 		})?;
-		Ok(())
+
+		let mounts: Vec<Mount> = mount_dirs.iter().cloned().map(|m| m.into()).collect();
+		let overlaps = find_overlaps(&mounts);
+		for overlap in &overlaps {
+			warn!(
+				"Mount `{}` overlaps with mount `{}`",
+				overlap.nested_name, overlap.name
+			);
+		}
+		Ok(overlaps)
 	}
 }
 
@@ -167,6 +224,66 @@ mod test {
 		assert_eq!(converted_path, virtual_path);
 	}
 
+	#[test]
+	fn converts_real_to_virtual_using_most_specific_mount() {
+		let mounts = vec![
+			Mount {
+				name: "root".to_owned(),
+				source: Path::new("test_dir").to_owned(),
+			},
+			Mount {
+				name: "nested".to_owned(),
+				source: ["test_dir", "nested"].iter().collect(),
+			},
+			Mount {
+				name: "nested_copy".to_owned(),
+				source: ["test_dir", "nested"].iter().collect(),
+			},
+		];
+		let real_path: PathBuf = ["test_dir", "nested", "something.png"].iter().collect();
+		let virtual_path: PathBuf = ["nested", "something.png"].iter().collect();
+
+		let vfs = VFS::new(mounts.clone());
+		assert_eq!(vfs.real_to_virtual(&real_path).unwrap(), virtual_path);
+
+		let vfs = VFS::new(mounts.into_iter().rev().collect());
+		let virtual_path: PathBuf = ["nested_copy", "something.png"].iter().collect();
+		assert_eq!(vfs.real_to_virtual(&real_path).unwrap(), virtual_path);
+
+		let real_path: PathBuf = ["test_dir", "nested_sibling", "something.png"]
+			.iter()
+			.collect();
+		let virtual_path: PathBuf = ["root", "nested_sibling", "something.png"].iter().collect();
+		assert_eq!(vfs.real_to_virtual(&real_path).unwrap(), virtual_path);
+	}
+
+	#[test]
+	fn finds_overlapping_mounts() {
+		let mount = |name: &str, source: &[&str]| Mount {
+			name: name.to_owned(),
+			source: source.iter().collect(),
+		};
+		let mounts = vec![
+			mount("music", &["test_dir", "music"]),
+			mount("rock", &["test_dir", "music", "rock"]),
+			mount("music_copy", &["test_dir", "music"]),
+			mount("music2", &["test_dir", "music2"]),
+		];
+		let overlaps = find_overlaps(&mounts);
+		let overlap = |name: &str, nested_name: &str| MountOverlap {
+			name: name.to_owned(),
+			nested_name: nested_name.to_owned(),
+		};
+		assert_eq!(
+			overlaps,
+			vec![
+				overlap("music", "rock"),
+				overlap("music", "music_copy"),
+				overlap("music_copy", "rock"),
+			]
+		);
+	}
+
 	#[test]
 	fn cleans_path_string() {
 		let mut correct_path = path::PathBuf::new();
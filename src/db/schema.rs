@@ -1,3 +1,13 @@
+table! {
+	audit_log (id) {
+		id -> Integer,
+		date -> Integer,
+		actor -> Nullable<Text>,
+		action -> Text,
+		details -> Text,
+	}
+}
+
 table! {
 	ddns_config (id) {
 		id -> Integer,
@@ -7,6 +17,16 @@ table! {
 	}
 }
 
+table! {
+	device_preferences (id) {
+		id -> Integer,
+		owner -> Integer,
+		device -> Text,
+		web_theme_base -> Nullable<Text>,
+		web_theme_accent -> Nullable<Text>,
+	}
+}
+
 table! {
 	directories (id) {
 		id -> Integer,
@@ -17,6 +37,31 @@ table! {
 		album -> Nullable<Text>,
 		artwork -> Nullable<Text>,
 		date_added -> Integer,
+		duration -> Nullable<BigInt>,
+	}
+}
+
+table! {
+	index_changes (id) {
+		id -> Integer,
+		generation -> Integer,
+		path -> Text,
+		previous_fingerprint -> Nullable<BigInt>,
+		fingerprint -> Nullable<BigInt>,
+	}
+}
+
+table! {
+	index_fingerprints (path) {
+		path -> Text,
+		fingerprint -> BigInt,
+	}
+}
+
+table! {
+	index_generations (id) {
+		id -> Integer,
+		date -> Integer,
 	}
 }
 
@@ -26,6 +71,22 @@ table! {
 		auth_secret -> Binary,
 		index_sleep_duration_seconds -> Integer,
 		index_album_art_pattern -> Text,
+		index_album_artist_fallback -> Text,
+		index_keep_unreadable_songs -> Bool,
+		index_path_pattern -> Text,
+		index_exact_durations -> Bool,
+		password_min_length -> Integer,
+		password_require_complexity -> Bool,
+		index_skip_non_utf8_paths -> Bool,
+		case_insensitive_paths -> Bool,
+		index_folder_name_albums -> Bool,
+		index_min_year -> Integer,
+		index_max_year -> Integer,
+		thumbnail_default_size -> Text,
+		index_artwork_preference -> Text,
+		index_compilation_artist_names -> Text,
+		index_tag_settings -> Nullable<Text>,
+		transcode_targets -> Text,
 	}
 }
 
@@ -51,6 +112,26 @@ table! {
 		id -> Integer,
 		owner -> Integer,
 		name -> Text,
+		created_at -> Nullable<Integer>,
+		updated_at -> Integer,
+	}
+}
+
+table! {
+	queue_songs (id) {
+		id -> Integer,
+		queue -> Integer,
+		path -> Text,
+		ordering -> Integer,
+	}
+}
+
+table! {
+	queues (id) {
+		id -> Integer,
+		owner -> Integer,
+		current_index -> Integer,
+		position_ms -> Integer,
 	}
 }
 
@@ -72,6 +153,18 @@ table! {
 		composer -> Nullable<Text>,
 		genre -> Nullable<Text>,
 		label -> Nullable<Text>,
+		start_offset_ms -> Nullable<Integer>,
+		end_offset_ms -> Nullable<Integer>,
+		metadata_error -> Bool,
+		track_total -> Nullable<Integer>,
+		disc_total -> Nullable<Integer>,
+		mtime -> Nullable<BigInt>,
+		track_gain -> Nullable<Float>,
+		track_peak -> Nullable<Float>,
+		album_gain -> Nullable<Float>,
+		album_peak -> Nullable<Float>,
+		duration_decoded -> Bool,
+		audio_path -> Nullable<Text>,
 	}
 }
 
@@ -88,16 +181,27 @@ table! {
 	}
 }
 
+joinable!(device_preferences -> users (owner));
+joinable!(index_changes -> index_generations (generation));
 joinable!(playlist_songs -> playlists (playlist));
 joinable!(playlists -> users (owner));
+joinable!(queue_songs -> queues (queue));
+joinable!(queues -> users (owner));
 
 allow_tables_to_appear_in_same_query!(
+	audit_log,
 	ddns_config,
+	device_preferences,
 	directories,
+	index_changes,
+	index_fingerprints,
+	index_generations,
 	misc_settings,
 	mount_points,
 	playlist_songs,
 	playlists,
+	queue_songs,
+	queues,
 	songs,
 	users,
 );
@@ -1,33 +1,57 @@
 use actix_web::{
 	dev::Service,
+	http::header::{HeaderName, HeaderValue},
 	middleware::{Compress, Logger, NormalizePath},
 	rt::System,
 	web::{self, ServiceConfig},
 	App as ActixApp, HttpServer,
 };
-use log::error;
+use log::{error, warn};
+use std::time::{Duration, Instant};
 
 use crate::app::App;
+use crate::utils::read_env_var;
 
 mod api;
 
 #[cfg(test)]
 pub mod test;
 
+// Set on API responses until the collection has been indexed once, so clients can tell
+// an empty collection apart from one which is still being scanned for the first time
+pub const INDEX_PENDING_HEADER: &str = "x-polaris-index-pending";
+
 pub fn make_config(app: App) -> impl FnOnce(&mut ServiceConfig) + Clone {
 	move |cfg: &mut ServiceConfig| {
+		let index = app.index.clone();
 		cfg.app_data(web::Data::new(app.index))
+			.app_data(web::Data::new(app.audit_manager))
 			.app_data(web::Data::new(app.config_manager))
 			.app_data(web::Data::new(app.ddns_manager))
 			.app_data(web::Data::new(app.lastfm_manager))
 			.app_data(web::Data::new(app.playlist_manager))
 			.app_data(web::Data::new(app.settings_manager))
 			.app_data(web::Data::new(app.thumbnail_manager))
+			.app_data(web::Data::new(app.transcode_manager))
 			.app_data(web::Data::new(app.user_manager))
 			.app_data(web::Data::new(app.vfs_manager))
 			.service(
 				web::scope("/api")
 					.configure(api::make_config())
+					.wrap_fn(move |req, srv| {
+						let index = index.clone();
+						let response_future = srv.call(req);
+						async move {
+							let mut response = response_future.await?;
+							if !index.is_initial_scan_completed() {
+								response.headers_mut().insert(
+									HeaderName::from_static(INDEX_PENDING_HEADER),
+									HeaderValue::from_static("true"),
+								);
+							}
+							Ok(response)
+						}
+					})
 					.wrap(NormalizePath::trim()),
 			)
 			.service(
@@ -43,12 +67,41 @@ pub fn make_config(app: App) -> impl FnOnce(&mut ServiceConfig) + Clone {
 	}
 }
 
+// Requests taking longer than this are logged as warnings, 0 disables this
+fn get_slow_request_threshold() -> Option<Duration> {
+	let threshold_ms = read_env_var("POLARIS_SLOW_REQUEST_THRESHOLD_MS").unwrap_or(1000);
+	match threshold_ms {
+		0 => None,
+		ms => Some(Duration::from_millis(ms)),
+	}
+}
+
 pub fn run(app: App) -> Result<(), std::io::Error> {
 	let address = ("0.0.0.0", app.port);
+	let slow_request_threshold = get_slow_request_threshold();
 	System::new().block_on(
 		HttpServer::new(move || {
 			ActixApp::new()
 				.wrap(Logger::default())
+				.wrap_fn(move |req, srv| {
+					let start = Instant::now();
+					let response_future = srv.call(req);
+					async move {
+						let response = response_future.await?;
+						let elapsed = start.elapsed();
+						if slow_request_threshold.is_some_and(|t| elapsed >= t) {
+							let request = response.request();
+							warn!(
+								"Slow request: {} {} (route: {}) took {}ms",
+								request.method(),
+								request.path(),
+								request.match_pattern().unwrap_or_default(),
+								elapsed.as_millis()
+							);
+						}
+						Ok(response)
+					}
+				})
 				.wrap_fn(|req, srv| {
 					// For some reason, actix logs error as DEBUG level.
 					// This logs them as ERROR level
@@ -1,6 +1,6 @@
 use actix_files::NamedFile;
 use actix_web::body::BoxBody;
-use actix_web::http::header::ContentEncoding;
+use actix_web::http::header::{self, ContentEncoding, EntityTag, HeaderValue};
 use actix_web::{
 	delete,
 	dev::Payload,
@@ -8,32 +8,42 @@ use actix_web::{
 	get,
 	http::StatusCode,
 	post, put,
-	web::{self, Data, Json, JsonConfig, ServiceConfig},
-	FromRequest, HttpRequest, HttpResponse, Responder, ResponseError,
+	web::{self, Bytes, Data, Json, JsonConfig, ServiceConfig},
+	Either, FromRequest, HttpMessage, HttpRequest, HttpResponse, Responder, ResponseError,
 };
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use base64::prelude::*;
 use futures_util::future::err;
+use futures_util::stream;
 use percent_encoding::percent_decode_str;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
 use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::str;
+use std::time::UNIX_EPOCH;
 
 use crate::app::{
-	config, ddns,
+	audit, config, ddns,
 	index::{self, Index},
-	lastfm, playlist, settings, thumbnail, user,
+	lastfm, playlist, settings, thumbnail, transcode, user,
 	vfs::{self, MountDir},
 };
 use crate::service::{dto, error::*};
+use crate::utils::{read_env_var, sniff_mislabeled_audio_format, AUDIO_EXTENSIONS};
 
 pub fn make_config() -> impl FnOnce(&mut ServiceConfig) + Clone {
 	move |cfg: &mut ServiceConfig| {
 		let megabyte = 1024 * 1024;
 		cfg.app_data(JsonConfig::default().limit(4 * megabyte)) // 4MB
 			.service(version)
+			.service(capabilities)
 			.service(initial_setup)
+			.service(setup_status)
 			.service(apply_config)
 			.service(get_settings)
 			.service(put_settings)
@@ -46,23 +56,60 @@ pub fn make_config() -> impl FnOnce(&mut ServiceConfig) + Clone {
 			.service(update_user)
 			.service(delete_user)
 			.service(get_preferences)
+			.service(get_preferences_schema)
 			.service(put_preferences)
 			.service(trigger_index)
+			.service(clean_index)
+			.service(refresh_artwork)
+			.service(get_scan_status)
+			.service(get_scan_history)
+			.service(cancel_scan)
+			.service(get_audit_log)
+			.service(resolve_path)
+			.service(get_raw_tags)
 			.service(login)
 			.service(browse_root)
 			.service(browse)
+			.service(browse_song_directory)
+			.service(album_at)
+			.service(album_credits)
+			.service(album_page)
+			.service(artists)
+			.service(artist_buckets)
+			.service(field_values)
+			.service(artist_songs)
+			.service(similar_artists)
+			.service(genre_songs)
+			.service(stats)
+			.service(years)
+			.service(year_albums)
+			.service(decades)
+			.service(labels)
+			.service(label)
 			.service(flatten_root)
 			.service(flatten)
+			.service(flatten_stream_root)
+			.service(flatten_stream)
+			.service(sync)
+			.service(index_generation)
 			.service(random)
+			.service(random_artists)
+			.service(random_genres)
 			.service(recent)
 			.service(search_root)
 			.service(search)
 			.service(get_audio)
 			.service(get_thumbnail)
+			.service(prewarm_thumbnails)
 			.service(list_playlists)
 			.service(save_playlist)
+			.service(save_playlists)
+			.service(append_playlist)
+			.service(move_playlist_track)
 			.service(read_playlist)
 			.service(delete_playlist)
+			.service(get_queue)
+			.service(put_queue)
 			.service(lastfm_now_playing)
 			.service(lastfm_scrobble)
 			.service(lastfm_link_token)
@@ -76,6 +123,7 @@ impl ResponseError for APIError {
 		match self {
 			APIError::AuthorizationTokenEncoding => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::AdminPermissionRequired => StatusCode::UNAUTHORIZED,
+			APIError::AlbumNotFound => StatusCode::NOT_FOUND,
 			APIError::AudioFileIOError => StatusCode::NOT_FOUND,
 			APIError::AuthenticationRequired => StatusCode::UNAUTHORIZED,
 			APIError::BrancaTokenEncoding => StatusCode::INTERNAL_SERVER_ERROR,
@@ -99,22 +147,51 @@ impl ResponseError for APIError {
 			APIError::OwnAdminPrivilegeRemoval => StatusCode::CONFLICT,
 			APIError::PasswordHashing => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::PlaylistNotFound => StatusCode::NOT_FOUND,
+			APIError::PlaylistTrackIndexOutOfRange(_) => StatusCode::BAD_REQUEST,
+			APIError::ScanInProgress => StatusCode::CONFLICT,
 			APIError::Settings(_) => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::SettingsInvalid(_) => StatusCode::BAD_REQUEST,
 			APIError::SongMetadataNotFound => StatusCode::NOT_FOUND,
+			APIError::SongTagsUnreadable(_, _) => StatusCode::UNPROCESSABLE_ENTITY,
 			APIError::ThumbnailFlacDecoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::ThumbnailFileIOError => StatusCode::NOT_FOUND,
 			APIError::ThumbnailId3Decoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::ThumbnailImageDecoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::ThumbnailMp4Decoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::ThumbnailPadColorInvalid => StatusCode::BAD_REQUEST,
+			APIError::ThumbnailServiceBusy => StatusCode::SERVICE_UNAVAILABLE,
 			APIError::TomlDeserialization(_) => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::TooManyLoginAttempts(_) => StatusCode::TOO_MANY_REQUESTS,
+			APIError::TranscodeTargetNotAllowed => StatusCode::BAD_REQUEST,
+			APIError::TranscodingServiceBusy => StatusCode::SERVICE_UNAVAILABLE,
+			APIError::TranscodingUnavailable(_, _) => StatusCode::SERVICE_UNAVAILABLE,
 			APIError::UnsupportedThumbnailFormat(_) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::UserNotFound => StatusCode::NOT_FOUND,
 			APIError::VFSPathNotFound => StatusCode::NOT_FOUND,
+			APIError::WeakPassword(_) => StatusCode::BAD_REQUEST,
 		}
 	}
 
 	fn error_response(&self) -> HttpResponse<BoxBody> {
-		HttpResponse::new(self.status_code())
+		let mut response = HttpResponse::new(self.status_code());
+		match self {
+			APIError::ThumbnailServiceBusy | APIError::TranscodingServiceBusy => {
+				response
+					.headers_mut()
+					.insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+			}
+			APIError::TooManyLoginAttempts(retry_after) => {
+				let seconds = retry_after.as_secs_f64().ceil() as u64;
+				response
+					.headers_mut()
+					.insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+			}
+			APIError::WeakPassword(reason) => {
+				return HttpResponse::build(self.status_code()).body(reason.clone());
+			}
+			_ => (),
+		}
+		response
 	}
 }
 
@@ -172,6 +249,13 @@ struct AdminRights {
 	auth: Option<Auth>,
 }
 
+impl AdminRights {
+	// Missing while no user exists yet
+	fn username(&self) -> Option<String> {
+		self.auth.as_ref().map(|a| a.username.clone())
+	}
+}
+
 impl FromRequest for AdminRights {
 	type Error = actix_web::Error;
 	type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
@@ -205,6 +289,54 @@ impl FromRequest for AdminRights {
 	}
 }
 
+// Shapes songs in responses according to `dto::SongFields`
+struct SongFormat {
+	fields: dto::SongFields,
+	// Set when real paths were requested by an admin
+	vfs: Option<vfs::VFS>,
+}
+
+impl SongFormat {
+	fn select_songs(&self, songs: Vec<index::Song>) -> Vec<serde_json::Value> {
+		self.fields.select_songs(songs, self.vfs.as_ref())
+	}
+
+	fn select_files(&self, files: Vec<index::CollectionFile>) -> Vec<serde_json::Value> {
+		self.fields.select_files(files, self.vfs.as_ref())
+	}
+}
+
+impl FromRequest for SongFormat {
+	type Error = actix_web::Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+	fn from_request(request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+		let fields = match web::Query::<dto::SongFields>::from_query(request.query_string()) {
+			Ok(q) => q.into_inner(),
+			Err(e) => return Box::pin(err(e.into())),
+		};
+
+		if fields.include_real_path != Some(true) {
+			return Box::pin(async move { Ok(SongFormat { fields, vfs: None }) });
+		}
+
+		let vfs_manager = match request.app_data::<Data<vfs::Manager>>() {
+			Some(m) => m.clone(),
+			None => return Box::pin(err(ErrorInternalServerError(APIError::Internal))),
+		};
+		let admin_rights_future = AdminRights::from_request(request, payload);
+
+		Box::pin(async move {
+			admin_rights_future.await?;
+			let vfs = block(move || vfs_manager.get_vfs()).await?;
+			Ok(SongFormat {
+				fields,
+				vfs: Some(vfs),
+			})
+		})
+	}
+}
+
 struct MediaFile {
 	named_file: NamedFile,
 }
@@ -228,6 +360,48 @@ impl Responder for MediaFile {
 	}
 }
 
+// Files served as-is get their ETag from `NamedFile`. Transcoded audio only changes when the
+// source file, the transcoded portion of it or the transcode options do, so its ETag is derived
+// from all of them.
+fn transcode_etag(
+	audio_path: &Path,
+	segment: Option<&index::CueSegment>,
+	options: &transcode::Options,
+) -> Option<EntityTag> {
+	let metadata = fs::metadata(audio_path).ok()?;
+	let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+	let segment = segment.map(|s| (s.start_ms, s.end_ms));
+	let mut hasher = DefaultHasher::new();
+	(metadata.len(), mtime, segment, options).hash(&mut hasher);
+	Some(EntityTag::new_strong(format!("{:x}", hasher.finish())))
+}
+
+// Forwarding headers can be forged by clients, so they are only used when the server is known to
+// run behind a reverse proxy. Otherwise, every client of a proxy shares the proxy's address.
+fn login_client_address(request: &HttpRequest) -> Option<IpAddr> {
+	let trust_proxy_headers: bool = read_env_var("POLARIS_TRUST_PROXY_HEADERS").unwrap_or(false);
+	if !trust_proxy_headers {
+		return request.peer_addr().map(|a| a.ip());
+	}
+	let connection_info = request.connection_info();
+	let address = connection_info.realip_remote_addr()?;
+	address
+		.parse::<IpAddr>()
+		.ok()
+		.or_else(|| address.parse::<SocketAddr>().ok().map(|a| a.ip()))
+}
+
+fn if_none_match(request: &HttpRequest, etag: &EntityTag) -> bool {
+	match request.get_header::<header::IfNoneMatch>() {
+		Some(header::IfNoneMatch::Any) => true,
+		Some(header::IfNoneMatch::Items(items)) => items.iter().any(|i| i.weak_eq(etag)),
+		None => false,
+	}
+}
+
+const FLATTEN_STREAM_CHUNK_SIZE: i64 = 500; // Songs read from the index at a time
+const TRANSCODE_CHUNK_SIZE: usize = 64 * 1024; // Bytes read from ffmpeg at a time
+
 async fn block<F, I, E>(f: F) -> Result<I, APIError>
 where
 	F: FnOnce() -> Result<I, E> + Send + 'static,
@@ -249,6 +423,44 @@ async fn version() -> Json<dto::Version> {
 	Json(current_version)
 }
 
+// Each feature is listed next to the check it depends on, so clients are only told about what
+// this build and environment can actually do
+fn supported_features() -> Vec<String> {
+	let features = [
+		("cue_sheets", true),
+		("ddns", true),
+		("lastfm", true),
+		("playlists", true),
+		("thumbnails", true),
+		("thumbnail_fit", true),
+		("thumbnail_webp", thumbnail::Format::WebP.is_encodable()),
+		("transcoding", transcode::is_available()),
+	];
+	features
+		.iter()
+		.filter(|(_, supported)| *supported)
+		.map(|(feature, _)| feature.to_string())
+		.collect()
+}
+
+#[get("/capabilities")]
+async fn capabilities() -> Result<Json<dto::Capabilities>, APIError> {
+	let features = actix_web::web::block(supported_features)
+		.await
+		.map_err(|_| APIError::Internal)?;
+	Ok(Json(dto::Capabilities {
+		features,
+		thumbnail_formats: thumbnail::IMAGE_FORMATS
+			.iter()
+			.map(|f| f.to_string())
+			.collect(),
+		audio_extensions: AUDIO_EXTENSIONS
+			.iter()
+			.map(|(e, _)| e.to_string())
+			.collect(),
+	}))
+}
+
 #[get("/initial_setup")]
 async fn initial_setup(
 	user_manager: Data<user::Manager>,
@@ -264,13 +476,39 @@ async fn initial_setup(
 	Ok(Json(initial_setup))
 }
 
+#[get("/setup_status")]
+async fn setup_status(
+	user_manager: Data<user::Manager>,
+	vfs_manager: Data<vfs::Manager>,
+	index: Data<Index>,
+) -> Result<Json<dto::SetupStatus>, APIError> {
+	let setup_status = block(move || -> Result<dto::SetupStatus, APIError> {
+		let users = user_manager.list()?;
+		let mount_dirs = vfs_manager.mount_dirs()?;
+		Ok(dto::SetupStatus {
+			admin_created: users.iter().any(|u| u.is_admin()),
+			mount_dirs_configured: !mount_dirs.is_empty(),
+			initial_scan_completed: index.is_initial_scan_completed(),
+		})
+	})
+	.await?;
+	Ok(Json(setup_status))
+}
+
 #[put("/config")]
 async fn apply_config(
-	_admin_rights: AdminRights,
+	admin_rights: AdminRights,
+	audit_manager: Data<audit::Manager>,
 	config_manager: Data<config::Manager>,
 	config: Json<dto::Config>,
 ) -> Result<HttpResponse, APIError> {
-	block(move || config_manager.apply(&config.to_owned().into())).await?;
+	let actor = admin_rights.username();
+	block(move || -> Result<(), APIError> {
+		config_manager.apply(&config.to_owned().into())?;
+		audit_manager.record(actor.as_deref(), audit::Action::ApplyConfig, "");
+		Ok(())
+	})
+	.await?;
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
@@ -285,11 +523,19 @@ async fn get_settings(
 
 #[put("/settings")]
 async fn put_settings(
-	_admin_rights: AdminRights,
+	admin_rights: AdminRights,
+	audit_manager: Data<audit::Manager>,
 	settings_manager: Data<settings::Manager>,
 	new_settings: Json<dto::NewSettings>,
 ) -> Result<HttpResponse, APIError> {
-	block(move || settings_manager.amend(&new_settings.to_owned().into())).await?;
+	let actor = admin_rights.username();
+	block(move || -> Result<(), APIError> {
+		settings_manager.amend(&new_settings.to_owned().into())?;
+		let details = serde_json::to_string(&new_settings.into_inner()).unwrap_or_default();
+		audit_manager.record(actor.as_deref(), audit::Action::UpdateSettings, &details);
+		Ok(())
+	})
+	.await?;
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
@@ -305,13 +551,25 @@ async fn list_mount_dirs(
 
 #[put("/mount_dirs")]
 async fn put_mount_dirs(
-	_admin_rights: AdminRights,
+	admin_rights: AdminRights,
+	audit_manager: Data<audit::Manager>,
 	vfs_manager: Data<vfs::Manager>,
 	new_mount_dirs: Json<Vec<dto::MountDir>>,
-) -> Result<HttpResponse, APIError> {
+) -> Result<Json<Vec<dto::MountDirOverlap>>, APIError> {
+	let actor = admin_rights.username();
 	let new_mount_dirs: Vec<MountDir> = new_mount_dirs.iter().cloned().map(|m| m.into()).collect();
-	block(move || vfs_manager.set_mount_dirs(&new_mount_dirs)).await?;
-	Ok(HttpResponse::new(StatusCode::OK))
+	let overlaps = block(move || -> Result<Vec<vfs::MountOverlap>, APIError> {
+		let overlaps = vfs_manager.set_mount_dirs(&new_mount_dirs)?;
+		let details = new_mount_dirs
+			.iter()
+			.map(|m| format!("{} => {}", m.name, m.source))
+			.collect::<Vec<_>>()
+			.join(", ");
+		audit_manager.record(actor.as_deref(), audit::Action::UpdateMountDirs, &details);
+		Ok(overlaps)
+	})
+	.await?;
+	Ok(Json(overlaps.into_iter().map(|o| o.into()).collect()))
 }
 
 #[get("/ddns")]
@@ -325,11 +583,22 @@ async fn get_ddns_config(
 
 #[put("/ddns")]
 async fn put_ddns_config(
-	_admin_rights: AdminRights,
+	admin_rights: AdminRights,
+	audit_manager: Data<audit::Manager>,
 	ddns_manager: Data<ddns::Manager>,
 	new_ddns_config: Json<dto::DDNSConfig>,
 ) -> Result<HttpResponse, APIError> {
-	block(move || ddns_manager.set_config(&new_ddns_config.to_owned().into())).await?;
+	let actor = admin_rights.username();
+	block(move || -> Result<(), APIError> {
+		ddns_manager.set_config(&new_ddns_config.to_owned().into())?;
+		audit_manager.record(
+			actor.as_deref(),
+			audit::Action::UpdateDDNSConfig,
+			&new_ddns_config.host,
+		);
+		Ok(())
+	})
+	.await?;
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
@@ -346,17 +615,25 @@ async fn list_users(
 #[post("/user")]
 async fn create_user(
 	user_manager: Data<user::Manager>,
-	_admin_rights: AdminRights,
+	audit_manager: Data<audit::Manager>,
+	admin_rights: AdminRights,
 	new_user: Json<dto::NewUser>,
 ) -> Result<HttpResponse, APIError> {
-	let new_user = new_user.to_owned().into();
-	block(move || user_manager.create(&new_user)).await?;
+	let actor = admin_rights.username();
+	let new_user: user::NewUser = new_user.to_owned().into();
+	block(move || -> Result<(), APIError> {
+		user_manager.create(&new_user)?;
+		audit_manager.record(actor.as_deref(), audit::Action::CreateUser, &new_user.name);
+		Ok(())
+	})
+	.await?;
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
 #[put("/user/{name}")]
 async fn update_user(
 	user_manager: Data<user::Manager>,
+	audit_manager: Data<audit::Manager>,
 	admin_rights: AdminRights,
 	name: web::Path<String>,
 	user_update: Json<dto::UserUpdate>,
@@ -367,13 +644,19 @@ async fn update_user(
 		}
 	}
 
+	let actor = admin_rights.username();
 	block(move || -> Result<(), APIError> {
+		let mut changes = Vec::new();
 		if let Some(password) = &user_update.new_password {
 			user_manager.set_password(&name, password)?;
+			changes.push("password".to_owned());
 		}
 		if let Some(is_admin) = &user_update.new_is_admin {
 			user_manager.set_is_admin(&name, *is_admin)?;
+			changes.push(format!("is_admin = {}", is_admin));
 		}
+		let details = format!("{} ({})", name, changes.join(", "));
+		audit_manager.record(actor.as_deref(), audit::Action::UpdateUser, &details);
 		Ok(())
 	})
 	.await?;
@@ -383,6 +666,7 @@ async fn update_user(
 #[delete("/user/{name}")]
 async fn delete_user(
 	user_manager: Data<user::Manager>,
+	audit_manager: Data<audit::Manager>,
 	admin_rights: AdminRights,
 	name: web::Path<String>,
 ) -> Result<HttpResponse, APIError> {
@@ -391,7 +675,13 @@ async fn delete_user(
 			return Err(APIError::DeletingOwnAccount);
 		}
 	}
-	block(move || user_manager.delete(&name)).await?;
+	let actor = admin_rights.username();
+	block(move || -> Result<(), APIError> {
+		user_manager.delete(&name)?;
+		audit_manager.record(actor.as_deref(), audit::Action::DeleteUser, &name);
+		Ok(())
+	})
+	.await?;
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
@@ -399,39 +689,186 @@ async fn delete_user(
 async fn get_preferences(
 	user_manager: Data<user::Manager>,
 	auth: Auth,
+	options: web::Query<dto::PreferencesOptions>,
 ) -> Result<Json<user::Preferences>, APIError> {
-	let preferences = block(move || user_manager.read_preferences(&auth.username)).await?;
+	let preferences =
+		block(move || user_manager.read_preferences(&auth.username, options.device())).await?;
 	Ok(Json(preferences))
 }
 
+#[get("/preferences/schema")]
+async fn get_preferences_schema(_auth: Auth) -> Json<Vec<user::PreferenceField>> {
+	Json(user::Preferences::schema())
+}
+
 #[put("/preferences")]
 async fn put_preferences(
 	user_manager: Data<user::Manager>,
 	auth: Auth,
+	options: web::Query<dto::PreferencesOptions>,
 	preferences: Json<user::Preferences>,
 ) -> Result<HttpResponse, APIError> {
-	block(move || user_manager.write_preferences(&auth.username, &preferences)).await?;
+	block(move || user_manager.write_preferences(&auth.username, options.device(), &preferences))
+		.await?;
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
 #[post("/trigger_index")]
 async fn trigger_index(
+	index: Data<Index>,
+	audit_manager: Data<audit::Manager>,
+	admin_rights: AdminRights,
+) -> Result<Json<dto::TriggerIndexResult>, APIError> {
+	let already_running = index.trigger_reindex();
+	let actor = admin_rights.username();
+	block(move || -> Result<(), APIError> {
+		audit_manager.record(actor.as_deref(), audit::Action::TriggerIndex, "");
+		Ok(())
+	})
+	.await?;
+	Ok(Json(dto::TriggerIndexResult {
+		already_running,
+		status: index.get_scan_status(),
+	}))
+}
+
+#[post("/index/clean")]
+async fn clean_index(
+	index: Data<Index>,
+	audit_manager: Data<audit::Manager>,
+	admin_rights: AdminRights,
+) -> Result<Json<index::CleanReport>, APIError> {
+	let actor = admin_rights.username();
+	let report = block(move || -> Result<index::CleanReport, APIError> {
+		let report = index.clean()?;
+		let details = format!(
+			"{} directories and {} songs removed",
+			report.directories_removed, report.songs_removed
+		);
+		audit_manager.record(actor.as_deref(), audit::Action::CleanIndex, &details);
+		Ok(report)
+	})
+	.await?;
+	Ok(Json(report))
+}
+
+#[get("/scans")]
+async fn get_scan_status(
 	index: Data<Index>,
 	_admin_rights: AdminRights,
-) -> Result<HttpResponse, APIError> {
-	index.trigger_reindex();
-	Ok(HttpResponse::new(StatusCode::OK))
+) -> Result<Json<index::ScanStatus>, APIError> {
+	Ok(Json(index.get_scan_status()))
+}
+
+#[get("/index/history")]
+async fn get_scan_history(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+) -> Result<Json<Vec<index::ScanRecord>>, APIError> {
+	Ok(Json(index.get_scan_history()))
+}
+
+#[post("/scans/cancel")]
+async fn cancel_scan(
+	index: Data<Index>,
+	audit_manager: Data<audit::Manager>,
+	admin_rights: AdminRights,
+) -> Result<Json<index::ScanStatus>, APIError> {
+	let status = index.cancel_scan();
+	if status.cancel_requested {
+		let actor = admin_rights.username();
+		block(move || -> Result<(), APIError> {
+			audit_manager.record(actor.as_deref(), audit::Action::CancelScan, "");
+			Ok(())
+		})
+		.await?;
+	}
+	Ok(Json(status))
+}
+
+#[post("/admin/refresh_artwork")]
+async fn refresh_artwork(
+	index: Data<Index>,
+	thumbnails_manager: Data<thumbnail::Manager>,
+	audit_manager: Data<audit::Manager>,
+	admin_rights: AdminRights,
+	options: web::Query<dto::RefreshArtworkOptions>,
+) -> Result<Json<dto::RefreshArtworkResult>, APIError> {
+	let actor = admin_rights.username();
+	let invalidated = block(move || -> Result<usize, APIError> {
+		let invalidated = if options.all.unwrap_or(false) {
+			thumbnails_manager.invalidate_all_thumbnails()?
+		} else {
+			let artwork_paths = index.get_artwork_paths()?;
+			thumbnails_manager.invalidate_thumbnails(artwork_paths)?
+		};
+		let details = format!("{} thumbnails invalidated", invalidated);
+		audit_manager.record(actor.as_deref(), audit::Action::RefreshArtwork, &details);
+		Ok(invalidated)
+	})
+	.await?;
+	Ok(Json(dto::RefreshArtworkResult { invalidated }))
+}
+
+#[get("/admin/audit")]
+async fn get_audit_log(
+	audit_manager: Data<audit::Manager>,
+	_admin_rights: AdminRights,
+	options: web::Query<dto::AuditLogOptions>,
+) -> Result<Json<Vec<dto::AuditLogEntry>>, APIError> {
+	let limit = options.limit.unwrap_or(100);
+	let entries = block(move || audit_manager.list(limit)).await?;
+	Ok(Json(entries.into_iter().map(|e| e.into()).collect()))
+}
+
+#[get("/admin/resolve")]
+async fn resolve_path(
+	vfs_manager: Data<vfs::Manager>,
+	_admin_rights: AdminRights,
+	options: web::Query<dto::ResolvePathOptions>,
+) -> Result<Json<dto::ResolvedPath>, APIError> {
+	let resolved_path = block(move || -> Result<dto::ResolvedPath, APIError> {
+		let vfs = vfs_manager.get_vfs()?;
+		let real_path = vfs.virtual_to_real(Path::new(&options.path))?;
+		Ok(dto::ResolvedPath {
+			exists: real_path.exists(),
+			real_path: real_path.to_string_lossy().into_owned(),
+		})
+	})
+	.await?;
+	Ok(Json(resolved_path))
+}
+
+#[get("/song/{path:.*}/raw_tags")]
+async fn get_raw_tags(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+	path: web::Path<String>,
+) -> Result<Json<Vec<dto::RawTag>>, APIError> {
+	let tags = block(move || {
+		let path = percent_decode_str(&path).decode_utf8_lossy();
+		index.get_raw_tags(Path::new(path.as_ref()))
+	})
+	.await?;
+	Ok(Json(
+		tags.into_iter()
+			.map(|(key, value)| dto::RawTag { key, value })
+			.collect(),
+	))
 }
 
 #[post("/auth")]
 async fn login(
 	user_manager: Data<user::Manager>,
 	credentials: Json<dto::Credentials>,
+	request: HttpRequest,
 ) -> Result<HttpResponse, APIError> {
 	let username = credentials.username.clone();
+	let client = login_client_address(&request);
 	let (user::AuthToken(token), is_admin) =
 		block(move || -> Result<(user::AuthToken, bool), APIError> {
-			let auth_token = user_manager.login(&credentials.username, &credentials.password)?;
+			let auth_token =
+				user_manager.login(&credentials.username, &credentials.password, client)?;
 			let is_admin = user_manager.is_admin(&credentials.username)?;
 			Ok((auth_token, is_admin))
 		})
@@ -449,9 +886,10 @@ async fn login(
 async fn browse_root(
 	index: Data<Index>,
 	_auth: Auth,
-) -> Result<Json<Vec<index::CollectionFile>>, APIError> {
+	fields: SongFormat,
+) -> Result<Json<Vec<serde_json::Value>>, APIError> {
 	let result = block(move || index.browse(Path::new(""))).await?;
-	Ok(Json(result))
+	Ok(Json(fields.select_files(result)))
 }
 
 #[get("/browse/{path:.*}")]
@@ -459,19 +897,214 @@ async fn browse(
 	index: Data<Index>,
 	_auth: Auth,
 	path: web::Path<String>,
-) -> Result<Json<Vec<index::CollectionFile>>, APIError> {
+	fields: SongFormat,
+) -> Result<Json<Vec<serde_json::Value>>, APIError> {
 	let result = block(move || {
 		let path = percent_decode_str(&path).decode_utf8_lossy();
 		index.browse(Path::new(path.as_ref()))
 	})
 	.await?;
+	Ok(Json(fields.select_files(result)))
+}
+
+#[get("/song/{path:.*}/directory")]
+async fn browse_song_directory(
+	index: Data<Index>,
+	_auth: Auth,
+	path: web::Path<String>,
+	fields: SongFormat,
+) -> Result<Json<Vec<serde_json::Value>>, APIError> {
+	let result = block(move || {
+		let path = percent_decode_str(&path).decode_utf8_lossy();
+		let song = index.get_song(Path::new(path.as_ref()))?;
+		let song_path = Path::new(&song.path);
+		index.browse(song_path.parent().unwrap_or_else(|| Path::new("")))
+	})
+	.await?;
+	Ok(Json(fields.select_files(result)))
+}
+
+#[get("/album_at/{path:.*}")]
+async fn album_at(
+	index: Data<Index>,
+	_auth: Auth,
+	path: web::Path<String>,
+) -> Result<Json<index::Directory>, APIError> {
+	let result = block(move || {
+		let path = percent_decode_str(&path).decode_utf8_lossy();
+		index.get_album_at(Path::new(path.as_ref()))
+	})
+	.await?;
+	Ok(Json(result))
+}
+
+#[get("/album_credits/{path:.*}")]
+async fn album_credits(
+	index: Data<Index>,
+	_auth: Auth,
+	path: web::Path<String>,
+) -> Result<Json<index::AlbumCredits>, APIError> {
+	let result = block(move || {
+		let path = percent_decode_str(&path).decode_utf8_lossy();
+		index.get_album_credits(Path::new(path.as_ref()))
+	})
+	.await?;
+	Ok(Json(result))
+}
+
+#[get("/album_page/{path:.*}")]
+async fn album_page(
+	index: Data<Index>,
+	_auth: Auth,
+	path: web::Path<String>,
+) -> Result<Json<index::AlbumPage>, APIError> {
+	let result = block(move || {
+		let path = percent_decode_str(&path).decode_utf8_lossy();
+		index.get_album_page(Path::new(path.as_ref()))
+	})
+	.await?;
+	Ok(Json(result))
+}
+
+#[get("/artists")]
+async fn artists(
+	index: Data<Index>,
+	_auth: Auth,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<String>>, APIError> {
+	let result = block(move || index.get_artists()).await?;
+	Ok(Json(page.paginate(result)))
+}
+
+#[get("/artists/index")]
+async fn artist_buckets(
+	index: Data<Index>,
+	_auth: Auth,
+) -> Result<Json<Vec<index::ArtistBucket>>, APIError> {
+	let result = block(move || index.get_artist_buckets()).await?;
+	Ok(Json(result))
+}
+
+#[get("/field_values/{field}")]
+async fn field_values(
+	index: Data<Index>,
+	_auth: Auth,
+	field: web::Path<dto::SongField>,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<index::FieldValue>>, APIError> {
+	let field = field.into_inner().into();
+	let result = block(move || index.get_field_values(field)).await?;
+	Ok(Json(page.paginate(result)))
+}
+
+#[get("/artist/{name}/songs")]
+async fn artist_songs(
+	index: Data<Index>,
+	_auth: Auth,
+	name: web::Path<String>,
+	options: web::Query<dto::ArtistSongsOptions>,
+	fields: SongFormat,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<serde_json::Value>>, APIError> {
+	let sort = options.sort.map(Into::into).unwrap_or_default();
+	let songs = block(move || index.get_artist_songs(&name, sort)).await?;
+	Ok(Json(page.paginate(fields.select_songs(songs))))
+}
+
+#[get("/artist/{name}/similar")]
+async fn similar_artists(
+	index: Data<Index>,
+	_auth: Auth,
+	name: web::Path<String>,
+	options: web::Query<dto::SimilarArtistsOptions>,
+) -> Result<Json<Vec<String>>, APIError> {
+	let count = options.count.unwrap_or(20);
+	let result = block(move || index.get_similar_artists(&name, count)).await?;
+	Ok(Json(result))
+}
+
+#[get("/genres/songs")]
+async fn genre_songs(
+	index: Data<Index>,
+	_auth: Auth,
+	options: web::Query<dto::GenreSongsOptions>,
+	fields: SongFormat,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<serde_json::Value>>, APIError> {
+	let genres = options.genres();
+	let sort = options.sort.map(Into::into).unwrap_or_default();
+	let songs = block(move || index.get_genre_songs(&genres, sort)).await?;
+	Ok(Json(page.paginate(fields.select_songs(songs))))
+}
+
+#[get("/stats")]
+async fn stats(index: Data<Index>, _auth: Auth) -> Result<Json<index::Statistics>, APIError> {
+	let result = block(move || index.get_statistics()).await?;
+	Ok(Json(result))
+}
+
+#[get("/years")]
+async fn years(
+	index: Data<Index>,
+	_auth: Auth,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<index::Year>>, APIError> {
+	let result = block(move || index.get_years()).await?;
+	Ok(Json(page.paginate(result)))
+}
+
+#[get("/year/{year}/albums")]
+async fn year_albums(
+	index: Data<Index>,
+	_auth: Auth,
+	year: web::Path<i32>,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<index::Directory>>, APIError> {
+	let year = year.into_inner();
+	let result = block(move || index.get_year_albums(year)).await?;
+	Ok(Json(page.paginate(result)))
+}
+
+#[get("/decades")]
+async fn decades(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::Decade>>, APIError> {
+	let result = block(move || index.get_decades()).await?;
+	Ok(Json(result))
+}
+
+#[get("/labels")]
+async fn labels(
+	index: Data<Index>,
+	_auth: Auth,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<String>>, APIError> {
+	let result = block(move || index.get_labels()).await?;
+	Ok(Json(page.paginate(result)))
+}
+
+#[get("/label/{name}")]
+async fn label(
+	index: Data<Index>,
+	_auth: Auth,
+	name: web::Path<String>,
+) -> Result<Json<index::Label>, APIError> {
+	let result = block(move || index.get_label(&name)).await?;
 	Ok(Json(result))
 }
 
 #[get("/flatten")]
-async fn flatten_root(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::Song>>, APIError> {
-	let songs = block(move || index.flatten(Path::new(""))).await?;
-	Ok(Json(songs))
+async fn flatten_root(
+	index: Data<Index>,
+	_auth: Auth,
+	options: web::Query<dto::FlattenOptions>,
+	fields: SongFormat,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<serde_json::Value>>, APIError> {
+	let sort = options
+		.sort
+		.map(Into::into)
+		.unwrap_or(index::SongSort::Path);
+	let songs = block(move || index.flatten_sorted(Path::new(""), sort)).await?;
+	Ok(Json(page.paginate(fields.select_songs(songs))))
 }
 
 #[get("/flatten/{path:.*}")]
@@ -479,13 +1112,135 @@ async fn flatten(
 	index: Data<Index>,
 	_auth: Auth,
 	path: web::Path<String>,
-) -> Result<Json<Vec<index::Song>>, APIError> {
+	options: web::Query<dto::FlattenOptions>,
+	fields: SongFormat,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<serde_json::Value>>, APIError> {
+	let sort = options
+		.sort
+		.map(Into::into)
+		.unwrap_or(index::SongSort::Path);
 	let songs = block(move || {
 		let path = percent_decode_str(&path).decode_utf8_lossy();
-		index.flatten(Path::new(path.as_ref()))
+		index.flatten_sorted(Path::new(path.as_ref()), sort)
 	})
 	.await?;
-	Ok(Json(songs))
+	Ok(Json(page.paginate(fields.select_songs(songs))))
+}
+
+struct FlattenStream {
+	index: Data<Index>,
+	path: PathBuf,
+	fields: SongFormat,
+	songs: Option<Vec<index::Song>>,
+	next: Option<String>,
+}
+
+#[get("/flatten_stream")]
+async fn flatten_stream_root(
+	index: Data<Index>,
+	_auth: Auth,
+	fields: SongFormat,
+) -> Result<HttpResponse, APIError> {
+	stream_flattened_songs(index, PathBuf::new(), fields).await
+}
+
+#[get("/flatten_stream/{path:.*}")]
+async fn flatten_stream(
+	index: Data<Index>,
+	_auth: Auth,
+	path: web::Path<String>,
+	fields: SongFormat,
+) -> Result<HttpResponse, APIError> {
+	let path = PathBuf::from(percent_decode_str(&path).decode_utf8_lossy().as_ref());
+	stream_flattened_songs(index, path, fields).await
+}
+
+// Songs are written as newline-delimited JSON, in the same order as `/flatten`
+async fn stream_flattened_songs(
+	index: Data<Index>,
+	path: PathBuf,
+	fields: SongFormat,
+) -> Result<HttpResponse, APIError> {
+	// The first chunk is read upfront so that invalid paths are reported with a proper status code
+	let (songs, next) = {
+		let (index, path) = (index.clone(), path.clone());
+		block(move || index.flatten_page(path, None, FLATTEN_STREAM_CHUNK_SIZE)).await?
+	};
+	let state = FlattenStream {
+		index,
+		path,
+		fields,
+		songs: Some(songs),
+		next,
+	};
+
+	let body = stream::try_unfold(state, |mut state| async move {
+		let songs = match state.songs.take() {
+			Some(songs) => songs,
+			None => {
+				let Some(after) = state.next.take() else {
+					return Ok(None);
+				};
+				let (index, path) = (state.index.clone(), state.path.clone());
+				let (songs, next) =
+					block(move || index.flatten_page(path, Some(after), FLATTEN_STREAM_CHUNK_SIZE))
+						.await?;
+				state.next = next;
+				songs
+			}
+		};
+		let mut chunk = Vec::new();
+		for song in state.fields.select_songs(songs) {
+			serde_json::to_writer(&mut chunk, &song).map_err(|_| APIError::Internal)?;
+			chunk.push(b'\n');
+		}
+		Ok::<_, APIError>(Some((Bytes::from(chunk), state)))
+	});
+
+	Ok(HttpResponse::Ok()
+		.content_type("application/x-ndjson")
+		.streaming(body))
+}
+
+// The changes listed for a given `since` only depend on the latest generation, which
+// clients can revalidate against with `If-None-Match`
+#[get("/sync")]
+async fn sync(
+	index: Data<Index>,
+	_auth: Auth,
+	options: web::Query<dto::SyncOptions>,
+	request: HttpRequest,
+) -> Result<HttpResponse, APIError> {
+	let generation = {
+		let index = index.clone();
+		block(move || index.get_generation()).await?
+	};
+	// Responses depend on the generation clients sync from, as well as on the latest one
+	let since = options
+		.since
+		.map_or_else(|| "none".to_owned(), |s| s.to_string());
+	let etag = generation.map(|g| EntityTag::new_strong(format!("{since}-{g}")));
+	if let Some(etag) = etag.as_ref().filter(|e| if_none_match(&request, e)) {
+		return Ok(HttpResponse::NotModified()
+			.insert_header((header::ETAG, etag.to_string()))
+			.finish());
+	}
+	let changes: index::SyncChanges = block(move || index.get_changes_since(options.since)).await?;
+	let mut response = HttpResponse::Ok();
+	if let Some(etag) = etag {
+		response.insert_header((header::ETAG, etag.to_string()));
+	}
+	Ok(response.json(changes))
+}
+
+#[get("/index/generation")]
+async fn index_generation(
+	index: Data<Index>,
+	_auth: Auth,
+) -> Result<Json<index::GenerationInfo>, APIError> {
+	let info = block(move || index.get_generation_info()).await?;
+	Ok(Json(info))
 }
 
 #[get("/random")]
@@ -494,19 +1249,48 @@ async fn random(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::Direc
 	Ok(Json(result))
 }
 
-#[get("/recent")]
-async fn recent(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::Directory>>, APIError> {
-	let result = block(move || index.get_recent_albums(20)).await?;
+#[get("/artists/random")]
+async fn random_artists(
+	index: Data<Index>,
+	_auth: Auth,
+	options: web::Query<dto::RandomOptions>,
+) -> Result<Json<Vec<String>>, APIError> {
+	let count = options.count.unwrap_or(20);
+	let result = block(move || index.get_random_artists(count, options.seed)).await?;
+	Ok(Json(result))
+}
+
+#[get("/genres/random")]
+async fn random_genres(
+	index: Data<Index>,
+	_auth: Auth,
+	options: web::Query<dto::RandomOptions>,
+) -> Result<Json<Vec<String>>, APIError> {
+	let count = options.count.unwrap_or(20);
+	let result = block(move || index.get_random_genres(count, options.seed)).await?;
 	Ok(Json(result))
 }
 
+#[get("/recent")]
+async fn recent(
+	index: Data<Index>,
+	_auth: Auth,
+	options: web::Query<dto::RecentOptions>,
+	page: web::Query<dto::PageOptions>,
+) -> Result<Json<dto::Listing<index::Directory>>, APIError> {
+	let exclude_singles = options.exclude_singles.unwrap_or_default();
+	let result = block(move || index.get_recent_albums(20, exclude_singles)).await?;
+	Ok(Json(page.paginate(result)))
+}
+
 #[get("/search")]
 async fn search_root(
 	index: Data<Index>,
 	_auth: Auth,
-) -> Result<Json<Vec<index::CollectionFile>>, APIError> {
+	fields: SongFormat,
+) -> Result<Json<Vec<serde_json::Value>>, APIError> {
 	let result = block(move || index.search("")).await?;
-	Ok(Json(result))
+	Ok(Json(fields.select_files(result)))
 }
 
 #[get("/search/{query:.*}")]
@@ -514,45 +1298,154 @@ async fn search(
 	index: Data<Index>,
 	_auth: Auth,
 	query: web::Path<String>,
-) -> Result<Json<Vec<index::CollectionFile>>, APIError> {
+	fields: SongFormat,
+) -> Result<Json<Vec<serde_json::Value>>, APIError> {
 	let result = block(move || index.search(&query)).await?;
-	Ok(Json(result))
+	Ok(Json(fields.select_files(result)))
 }
 
 #[get("/audio/{path:.*}")]
+#[allow(clippy::too_many_arguments)]
 async fn get_audio(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	transcode_manager: Data<transcode::Manager>,
 	vfs_manager: Data<vfs::Manager>,
 	_auth: Auth,
 	path: web::Path<String>,
-) -> Result<MediaFile, APIError> {
-	let audio_path = block(move || {
-		let vfs = vfs_manager.get_vfs()?;
-		let path = percent_decode_str(&path).decode_utf8_lossy();
-		vfs.virtual_to_real(Path::new(path.as_ref()))
-	})
-	.await?;
+	options: web::Query<dto::AudioOptions>,
+	request: HttpRequest,
+) -> Result<Either<MediaFile, HttpResponse>, APIError> {
+	let requested_options = options.transcode_options();
+	let seek_ms = options.seek_ms();
+	let (audio_path, segment, transcode_options, sniffed_format, etag) =
+		block(move || -> Result<_, APIError> {
+			if let Some(requested_options) = requested_options {
+				if !settings_manager
+					.get_transcode_targets()?
+					.contains(&requested_options)
+				{
+					return Err(APIError::TranscodeTargetNotAllowed);
+				}
+			}
+			let vfs = vfs_manager.get_vfs()?;
+			let path = percent_decode_str(&path).decode_utf8_lossy();
+			let virtual_path = Path::new(path.as_ref());
+			let mut real_path = vfs.virtual_to_real(virtual_path)?;
+			// Use the casing of the indexed song when the requested one does not exist on disk
+			let mut song = None;
+			if settings_manager.get_case_insensitive_paths()? && !real_path.exists() {
+				if let Ok(s) = index.get_song(virtual_path) {
+					real_path = vfs.virtual_to_real(Path::new(&s.path))?;
+					song = Some(s);
+				}
+			}
+			// Cue sheet tracks are cut out of their audio file, losslessly unless a codec was requested
+			let segment = match (index::is_cue_track_path(&real_path), song) {
+				(true, Some(song)) => song.cue_segment(),
+				(true, None) => index.get_song(virtual_path)?.cue_segment(),
+				(false, _) => None,
+			};
+			let transcode_options = match segment {
+				Some(_) => Some(requested_options.unwrap_or_else(transcode::Options::lossless)),
+				None => requested_options,
+			};
+			// Seeking within transcoded audio starts ffmpeg further into the file
+			let segment = match (transcode_options, seek_ms) {
+				(Some(_), Some(seek_ms)) => {
+					let segment = segment.unwrap_or_else(|| index::CueSegment {
+						audio_path: real_path.clone(),
+						start_ms: 0,
+						end_ms: None,
+					});
+					let start_ms = segment.start_ms.saturating_add(seek_ms);
+					Some(index::CueSegment {
+						start_ms: segment.end_ms.map_or(start_ms, |e| start_ms.min(e)),
+						..segment
+					})
+				}
+				_ => segment,
+			};
+			let audio_path = segment
+				.as_ref()
+				.map(|s| s.audio_path.clone())
+				.unwrap_or(real_path);
+			// Content type is derived from the file extension, unless the file contents disagree
+			let sniffed_format = sniff_mislabeled_audio_format(&audio_path);
+			let etag =
+				transcode_options.and_then(|o| transcode_etag(&audio_path, segment.as_ref(), &o));
+			Ok((audio_path, segment, transcode_options, sniffed_format, etag))
+		})
+		.await?;
 
-	let named_file = NamedFile::open(audio_path).map_err(|_| APIError::AudioFileIOError)?;
-	Ok(MediaFile::new(named_file))
+	if let Some(transcode_options) = transcode_options {
+		if let Some(etag) = etag.as_ref().filter(|e| if_none_match(&request, e)) {
+			return Ok(Either::Right(
+				HttpResponse::NotModified()
+					.insert_header((header::ETAG, etag.to_string()))
+					.finish(),
+			));
+		}
+		let transcode = block(move || {
+			transcode_manager.transcode(&audio_path, segment.as_ref(), &transcode_options)
+		})
+		.await?;
+		// The length of transcoded audio is unknown upfront, so range requests are not supported
+		let body = stream::try_unfold(transcode, |mut transcode| async move {
+			let (transcode, chunk) = block(move || -> Result<_, APIError> {
+				let mut chunk = vec![0; TRANSCODE_CHUNK_SIZE];
+				let read = transcode.read(&mut chunk).map_err(|_| APIError::Internal)?;
+				chunk.truncate(read);
+				Ok((transcode, chunk))
+			})
+			.await?;
+			if chunk.is_empty() {
+				return Ok(None);
+			}
+			Ok::<_, APIError>(Some((Bytes::from(chunk), transcode)))
+		});
+		let mut response = HttpResponse::Ok();
+		response
+			.content_type(transcode_options.codec.mime_type())
+			.insert_header((header::ACCEPT_RANGES, "none"));
+		if let Some(etag) = etag {
+			response.insert_header((header::ETAG, etag.to_string()));
+		}
+		return Ok(Either::Right(response.streaming(body)));
+	}
+
+	let mut named_file = NamedFile::open(audio_path).map_err(|_| APIError::AudioFileIOError)?;
+	if let Some(mime) = sniffed_format.and_then(|f| f.mime_type().parse().ok()) {
+		named_file = named_file.set_content_type(mime);
+	}
+	Ok(Either::Left(MediaFile::new(named_file)))
 }
 
 #[get("/thumbnail/{path:.*}")]
 async fn get_thumbnail(
 	vfs_manager: Data<vfs::Manager>,
+	settings_manager: Data<settings::Manager>,
 	thumbnails_manager: Data<thumbnail::Manager>,
 	_auth: Auth,
 	path: web::Path<String>,
 	options_input: web::Query<dto::ThumbnailOptions>,
 ) -> Result<MediaFile, APIError> {
-	let options = thumbnail::Options::from(options_input.0);
-
+	let mut options_input = options_input.into_inner();
 	let thumbnail_path = block(move || -> Result<PathBuf, APIError> {
+		if options_input.size.is_none() {
+			options_input.size = Some(settings_manager.get_thumbnail_default_size()?.into());
+		}
+		let is_original = options_input.is_original();
+		let options = thumbnail::Options::try_from(options_input)?;
+
 		let vfs = vfs_manager.get_vfs()?;
 		let path = percent_decode_str(&path).decode_utf8_lossy();
 		let image_path = vfs.virtual_to_real(Path::new(path.as_ref()))?;
-		thumbnails_manager
-			.get_thumbnail(&image_path, &options)
-			.map_err(|e| e.into())
+		let thumbnail_path = match is_original {
+			true => thumbnails_manager.get_original_artwork(&image_path)?,
+			false => thumbnails_manager.get_thumbnail(&image_path, &options)?,
+		};
+		Ok(thumbnail_path)
 	})
 	.await?;
 
@@ -561,16 +1454,45 @@ async fn get_thumbnail(
 	Ok(MediaFile::new(named_file))
 }
 
+#[post("/thumbnails/prewarm")]
+async fn prewarm_thumbnails(
+	vfs_manager: Data<vfs::Manager>,
+	settings_manager: Data<settings::Manager>,
+	thumbnails_manager: Data<thumbnail::Manager>,
+	_auth: Auth,
+	input: Json<dto::PrewarmThumbnailsInput>,
+) -> Result<Json<dto::PrewarmThumbnailsResult>, APIError> {
+	let dto::PrewarmThumbnailsInput { paths, mut options } = input.into_inner();
+	let report = block(move || -> Result<thumbnail::PrewarmReport, APIError> {
+		if options.size.is_none() {
+			options.size = Some(settings_manager.get_thumbnail_default_size()?.into());
+		}
+		let is_original = options.is_original();
+		let options = thumbnail::Options::try_from(options)?;
+
+		let vfs = vfs_manager.get_vfs()?;
+		let image_paths: Vec<PathBuf> = paths
+			.iter()
+			.filter_map(|p| vfs.virtual_to_real(Path::new(p)).ok())
+			.collect();
+		let mut report =
+			thumbnails_manager.prewarm_thumbnails(&image_paths, (!is_original).then_some(&options));
+		// Paths outside of the mount points count as failures
+		report.failed += paths.len() - image_paths.len();
+		Ok(report)
+	})
+	.await?;
+	Ok(Json(report.into()))
+}
+
 #[get("/playlists")]
 async fn list_playlists(
 	playlist_manager: Data<playlist::Manager>,
 	auth: Auth,
 ) -> Result<Json<Vec<dto::ListPlaylistsEntry>>, APIError> {
-	let playlist_names = block(move || playlist_manager.list_playlists(&auth.username)).await?;
-	let playlists: Vec<dto::ListPlaylistsEntry> = playlist_names
-		.into_iter()
-		.map(|p| dto::ListPlaylistsEntry { name: p })
-		.collect();
+	let playlist_headers = block(move || playlist_manager.list_playlists(&auth.username)).await?;
+	let playlists: Vec<dto::ListPlaylistsEntry> =
+		playlist_headers.into_iter().map(|p| p.into()).collect();
 
 	Ok(Json(playlists))
 }
@@ -581,8 +1503,52 @@ async fn save_playlist(
 	auth: Auth,
 	name: web::Path<String>,
 	playlist: Json<dto::SavePlaylistInput>,
+) -> Result<Json<dto::SavePlaylistResult>, APIError> {
+	let dedupe = playlist.dedupe.unwrap_or(false);
+	let duplicates_removed = block(move || {
+		playlist_manager.save_playlist(&name, &auth.username, &playlist.tracks, dedupe)
+	})
+	.await?;
+	Ok(Json(dto::SavePlaylistResult { duplicates_removed }))
+}
+
+#[post("/playlists/bulk")]
+async fn save_playlists(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	playlists: Json<Vec<dto::BulkSavePlaylistInput>>,
+) -> Result<Json<Vec<dto::BulkSavePlaylistResult>>, APIError> {
+	let playlists: Vec<(String, Vec<String>)> = playlists
+		.into_inner()
+		.into_iter()
+		.map(|p| (p.name, p.tracks))
+		.collect();
+	let reports =
+		block(move || playlist_manager.save_playlists(&auth.username, &playlists)).await?;
+	Ok(Json(reports.into_iter().map(|r| r.into()).collect()))
+}
+
+#[post("/playlist/{name}/append")]
+async fn append_playlist(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+	playlist: Json<dto::AppendPlaylistInput>,
+) -> Result<Json<dto::AppendPlaylistResult>, APIError> {
+	let tracks_added =
+		block(move || playlist_manager.append_to_playlist(&name, &auth.username, &playlist.tracks))
+			.await?;
+	Ok(Json(dto::AppendPlaylistResult { tracks_added }))
+}
+
+#[post("/playlist/{name}/move")]
+async fn move_playlist_track(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+	input: Json<dto::MovePlaylistTrackInput>,
 ) -> Result<HttpResponse, APIError> {
-	block(move || playlist_manager.save_playlist(&name, &auth.username, &playlist.tracks)).await?;
+	block(move || playlist_manager.move_track(&name, &auth.username, input.from, input.to)).await?;
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
@@ -591,9 +1557,42 @@ async fn read_playlist(
 	playlist_manager: Data<playlist::Manager>,
 	auth: Auth,
 	name: web::Path<String>,
-) -> Result<Json<Vec<index::Song>>, APIError> {
+	fields: SongFormat,
+) -> Result<Json<Vec<serde_json::Value>>, APIError> {
 	let songs = block(move || playlist_manager.read_playlist(&name, &auth.username)).await?;
-	Ok(Json(songs))
+	Ok(Json(fields.select_songs(songs)))
+}
+
+#[get("/queue")]
+async fn get_queue(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	fields: SongFormat,
+) -> Result<Json<dto::Queue>, APIError> {
+	let queue = block(move || playlist_manager.read_queue(&auth.username)).await?;
+	Ok(Json(dto::Queue {
+		tracks: fields.select_songs(queue.songs),
+		current_index: queue.current_index,
+		position_ms: queue.position_ms,
+	}))
+}
+
+#[put("/queue")]
+async fn put_queue(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	queue: Json<dto::SaveQueueInput>,
+) -> Result<HttpResponse, APIError> {
+	block(move || {
+		playlist_manager.save_queue(
+			&auth.username,
+			&queue.tracks,
+			queue.current_index,
+			queue.position_ms,
+		)
+	})
+	.await?;
+	Ok(HttpResponse::new(StatusCode::OK))
 }
 
 #[delete("/playlist/{name}")]
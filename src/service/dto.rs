@@ -1,10 +1,12 @@
 use serde::{Deserialize, Serialize};
 
-use crate::app::{config, ddns, settings, thumbnail, user, vfs};
-use std::convert::From;
+use crate::app::{audit, config, ddns, index, playlist, settings, thumbnail, transcode, user, vfs};
+use crate::service::error::APIError;
+use std::convert::{From, TryFrom};
+use std::path::Path;
 
 pub const API_MAJOR_VERSION: i32 = 7;
-pub const API_MINOR_VERSION: i32 = 0;
+pub const API_MINOR_VERSION: i32 = 1;
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Version {
@@ -12,11 +14,25 @@ pub struct Version {
 	pub minor: i32,
 }
 
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Capabilities {
+	pub features: Vec<String>,
+	pub thumbnail_formats: Vec<String>,
+	pub audio_extensions: Vec<String>,
+}
+
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct InitialSetup {
 	pub has_any_users: bool,
 }
 
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SetupStatus {
+	pub admin_created: bool,
+	pub mount_dirs_configured: bool,
+	pub initial_scan_completed: bool,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Credentials {
 	pub username: String,
@@ -35,21 +51,180 @@ pub struct AuthQueryParameters {
 	pub auth_token: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct ThumbnailOptions {
 	pub size: Option<ThumbnailSize>,
 	pub pad: Option<bool>,
+	pub pad_color: Option<String>, // Hex color code, eg. `1a1a1a` or `#1a1a1a`
+	pub format: Option<ThumbnailFormat>,
+	pub fit: Option<ThumbnailFit>, // Takes precedence over `pad`
 }
 
-impl From<ThumbnailOptions> for thumbnail::Options {
-	fn from(dto: ThumbnailOptions) -> Self {
+impl TryFrom<ThumbnailOptions> for thumbnail::Options {
+	type Error = APIError;
+
+	fn try_from(dto: ThumbnailOptions) -> Result<Self, Self::Error> {
 		let mut options = thumbnail::Options::default();
 		options.max_dimension = dto.size.map_or(options.max_dimension, Into::into);
-		options.pad_to_square = dto.pad.unwrap_or(options.pad_to_square);
-		options
+		options.fit = match (dto.fit, dto.pad) {
+			(Some(fit), _) => fit.into(),
+			(None, Some(true)) => thumbnail::Fit::Pad,
+			(None, Some(false)) => thumbnail::Fit::Contain,
+			(None, None) => options.fit,
+		};
+		options.format = dto.format.map_or(options.format, Into::into);
+		if let Some(pad_color) = dto.pad_color {
+			options.pad_color =
+				parse_hex_color(&pad_color).ok_or(APIError::ThumbnailPadColorInvalid)?;
+		}
+		Ok(options)
+	}
+}
+
+impl ThumbnailOptions {
+	// Native size artwork is served without re-encoding when it is explicitly not padded, since
+	// thumbnails are padded to a square by default
+	pub fn is_original(&self) -> bool {
+		let is_contained = match (self.fit, self.pad) {
+			(Some(fit), _) => matches!(fit, ThumbnailFit::Contain),
+			(None, pad) => pad == Some(false),
+		};
+		matches!(self.size, Some(ThumbnailSize::Native)) && self.format.is_none() && is_contained
 	}
 }
 
+// Thumbnails are generated with the same options as `/thumbnail`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PrewarmThumbnailsInput {
+	pub paths: Vec<String>,
+	#[serde(flatten)]
+	pub options: ThumbnailOptions,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrewarmThumbnailsResult {
+	pub already_cached: usize,
+	pub generated: usize,
+	pub failed: usize,
+}
+
+impl From<thumbnail::PrewarmReport> for PrewarmThumbnailsResult {
+	fn from(r: thumbnail::PrewarmReport) -> Self {
+		Self {
+			already_cached: r.already_cached,
+			generated: r.generated,
+			failed: r.failed,
+		}
+	}
+}
+
+fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+	let color = color.strip_prefix('#').unwrap_or(color);
+	if color.len() != 6 || !color.is_ascii() {
+		return None;
+	}
+	let channel = |i: usize| u8::from_str_radix(&color[i..i + 2], 16).ok();
+	Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+// Comma-separated list of song fields to include in responses, eg. `path,title,duration`.
+// All fields are included when omitted.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SongFields {
+	pub fields: Option<String>,
+	// Adds the location of songs on the server's filesystem. Only available to admins.
+	pub include_real_path: Option<bool>,
+}
+
+impl SongFields {
+	// Real paths are included when a `vfs` is passed in
+	pub fn select_songs(
+		&self,
+		songs: Vec<index::Song>,
+		vfs: Option<&vfs::VFS>,
+	) -> Vec<serde_json::Value> {
+		songs.into_iter().map(|s| self.select(s, vfs)).collect()
+	}
+
+	pub fn select_files(
+		&self,
+		files: Vec<index::CollectionFile>,
+		vfs: Option<&vfs::VFS>,
+	) -> Vec<serde_json::Value> {
+		files
+			.into_iter()
+			.map(|f| match f {
+				index::CollectionFile::Song(s) => {
+					serde_json::json!({ "Song": self.select(s, vfs) })
+				}
+				f => serde_json::to_value(f).unwrap_or_default(),
+			})
+			.collect()
+	}
+
+	fn select(&self, song: index::Song, vfs: Option<&vfs::VFS>) -> serde_json::Value {
+		let real_path = vfs.and_then(|v| v.virtual_to_real(Path::new(&song.path)).ok());
+		let mut value = serde_json::to_value(song).unwrap_or_default();
+		if let serde_json::Value::Object(map) = &mut value {
+			if let Some(fields) = &self.fields {
+				map.retain(|key, _| fields.split(',').any(|f| f.trim() == key));
+			}
+			if let Some(real_path) = real_path {
+				map.insert(
+					"real_path".to_owned(),
+					real_path.to_string_lossy().into_owned().into(),
+				);
+			}
+		}
+		value
+	}
+}
+
+// Optional paging of list responses. Responses remain a bare array unless `envelope` is set,
+// in which case they also report the total number of items before paging.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct PageOptions {
+	pub envelope: Option<bool>,
+	pub offset: Option<usize>,
+	pub count: Option<usize>,
+}
+
+impl PageOptions {
+	pub fn paginate<T>(&self, items: Vec<T>) -> Listing<T> {
+		let total = items.len();
+		let offset = self.offset.unwrap_or_default().min(total);
+		let items: Vec<T> = items
+			.into_iter()
+			.skip(offset)
+			.take(self.count.unwrap_or(usize::MAX))
+			.collect();
+		match self.envelope.unwrap_or_default() {
+			true => Listing::Page(Page {
+				count: items.len(),
+				items,
+				total,
+				offset,
+			}),
+			false => Listing::Items(items),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page<T> {
+	pub items: Vec<T>,
+	pub total: usize,
+	pub offset: usize,
+	pub count: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Listing<T> {
+	Items(Vec<T>),
+	Page(Page<T>),
+}
+
 #[derive(Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ThumbnailSize {
@@ -69,14 +244,324 @@ impl Into<Option<u32>> for ThumbnailSize {
 	}
 }
 
+impl From<settings::ThumbnailSize> for ThumbnailSize {
+	fn from(s: settings::ThumbnailSize) -> Self {
+		match s {
+			settings::ThumbnailSize::Small => Self::Small,
+			settings::ThumbnailSize::Large => Self::Large,
+			settings::ThumbnailSize::Native => Self::Native,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbnailFormat {
+	Jpeg,
+	Png,
+	Webp,
+}
+
+impl From<ThumbnailFormat> for thumbnail::Format {
+	fn from(f: ThumbnailFormat) -> Self {
+		match f {
+			ThumbnailFormat::Jpeg => Self::Jpeg,
+			ThumbnailFormat::Png => Self::Png,
+			ThumbnailFormat::Webp => Self::WebP,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbnailFit {
+	Contain,
+	Cover,
+	Pad,
+}
+
+impl From<ThumbnailFit> for thumbnail::Fit {
+	fn from(f: ThumbnailFit) -> Self {
+		match f {
+			ThumbnailFit::Contain => Self::Contain,
+			ThumbnailFit::Cover => Self::Cover,
+			ThumbnailFit::Pad => Self::Pad,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SongSort {
+	Album,
+	Title,
+	Year,
+	Path,
+	Track,
+	DateAdded,
+}
+
+impl From<SongSort> for index::SongSort {
+	fn from(s: SongSort) -> Self {
+		match s {
+			SongSort::Album => Self::Album,
+			SongSort::Title => Self::Title,
+			SongSort::Year => Self::Year,
+			SongSort::Path => Self::Path,
+			SongSort::Track => Self::Track,
+			SongSort::DateAdded => Self::DateAdded,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SongField {
+	Artist,
+	Album,
+	Genre,
+	Label,
+	Year,
+}
+
+impl From<SongField> for index::SongField {
+	fn from(f: SongField) -> Self {
+		match f {
+			SongField::Artist => Self::Artist,
+			SongField::Album => Self::Album,
+			SongField::Genre => Self::Genre,
+			SongField::Label => Self::Label,
+			SongField::Year => Self::Year,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ArtistSongsOptions {
+	pub sort: Option<SongSort>,
+}
+
+// Songs are sorted by path unless `sort` is set
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct FlattenOptions {
+	pub sort: Option<SongSort>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct SimilarArtistsOptions {
+	pub count: Option<usize>,
+}
+
+// `genres` is a comma-separated list of genres
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct GenreSongsOptions {
+	pub genres: Option<String>,
+	pub sort: Option<SongSort>,
+}
+
+impl GenreSongsOptions {
+	pub fn genres(&self) -> Vec<String> {
+		self.genres
+			.iter()
+			.flat_map(|g| g.split(','))
+			.map(|g| g.trim().to_owned())
+			.filter(|g| !g.is_empty())
+			.collect()
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct RandomOptions {
+	pub count: Option<usize>,
+	pub seed: Option<u64>,
+}
+
+// Audio is served as-is unless `transcode` names a supported codec, eg. `mp3` or `opus`.
+// Bitrates are in kbps.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AudioOptions {
+	pub transcode: Option<String>,
+	pub bitrate: Option<String>,
+	// In seconds, only applies to transcoded audio
+	pub seek: Option<String>,
+}
+
+impl AudioOptions {
+	pub fn transcode_options(&self) -> Option<transcode::Options> {
+		let codec = self.transcode.as_deref()?.parse().ok()?;
+		let bitrate = self.bitrate.as_deref().and_then(|b| b.parse().ok());
+		Some(transcode::Options::new(codec, bitrate))
+	}
+
+	pub fn seek_ms(&self) -> Option<u32> {
+		let seek: f64 = self.seek.as_deref()?.parse().ok()?;
+		(seek.is_finite() && seek > 0.0).then(|| (seek * 1000.0).round() as u32)
+	}
+}
+
+// Preferences are read and written for the whole user when `device` is omitted
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PreferencesOptions {
+	pub device: Option<String>,
+}
+
+impl PreferencesOptions {
+	pub fn device(&self) -> Option<&str> {
+		self.device.as_deref().filter(|d| !d.is_empty())
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct RecentOptions {
+	pub exclude_singles: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct SyncOptions {
+	pub since: Option<i32>,
+}
+
+// `already_running` is set when the request was merged into an update which was already
+// running or queued, rather than starting a new one
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TriggerIndexResult {
+	pub already_running: bool,
+	pub status: index::ScanStatus,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct RefreshArtworkOptions {
+	pub all: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefreshArtworkResult {
+	pub invalidated: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct AuditLogOptions {
+	pub limit: Option<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+	pub date: i32,
+	pub actor: Option<String>,
+	pub action: String,
+	pub details: String,
+}
+
+impl From<audit::Entry> for AuditLogEntry {
+	fn from(e: audit::Entry) -> Self {
+		Self {
+			date: e.date,
+			actor: e.actor,
+			action: e.action,
+			details: e.details,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawTag {
+	pub key: String,
+	pub value: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ResolvePathOptions {
+	pub path: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolvedPath {
+	pub real_path: String,
+	pub exists: bool,
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ListPlaylistsEntry {
 	pub name: String,
+	pub created_at: Option<i32>,
+	pub updated_at: i32,
+}
+
+impl From<playlist::PlaylistHeader> for ListPlaylistsEntry {
+	fn from(p: playlist::PlaylistHeader) -> Self {
+		Self {
+			name: p.name,
+			created_at: p.created_at,
+			updated_at: p.updated_at,
+		}
+	}
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SavePlaylistInput {
 	pub tracks: Vec<String>,
+	pub dedupe: Option<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavePlaylistResult {
+	pub duplicates_removed: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AppendPlaylistInput {
+	pub tracks: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppendPlaylistResult {
+	pub tracks_added: usize,
+}
+
+// Indices of the track to move, and of the position it moves to
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct MovePlaylistTrackInput {
+	pub from: usize,
+	pub to: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveQueueInput {
+	pub tracks: Vec<String>,
+	pub current_index: u32,
+	pub position_ms: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Queue {
+	pub tracks: Vec<serde_json::Value>,
+	pub current_index: u32,
+	pub position_ms: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BulkSavePlaylistInput {
+	pub name: String,
+	pub tracks: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkSavePlaylistResult {
+	pub name: String,
+	pub saved: bool,
+	pub tracks_saved: usize,
+	pub tracks_skipped: usize,
+}
+
+impl From<playlist::SaveReport> for BulkSavePlaylistResult {
+	fn from(r: playlist::SaveReport) -> Self {
+		Self {
+			name: r.name,
+			saved: r.saved,
+			tracks_saved: r.tracks_saved,
+			tracks_skipped: r.tracks_skipped,
+		}
+	}
 }
 
 #[derive(Serialize, Deserialize)]
@@ -180,6 +665,21 @@ impl From<vfs::MountDir> for MountDir {
 	}
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MountDirOverlap {
+	pub name: String,
+	pub nested_name: String,
+}
+
+impl From<vfs::MountOverlap> for MountDirOverlap {
+	fn from(o: vfs::MountOverlap) -> Self {
+		Self {
+			name: o.name,
+			nested_name: o.nested_name,
+		}
+	}
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Config {
 	pub settings: Option<NewSettings>,
@@ -204,6 +704,21 @@ impl From<Config> for config::Config {
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NewSettings {
 	pub album_art_pattern: Option<String>,
+	pub album_artist_fallback: Option<String>,
+	pub keep_unreadable_songs: Option<bool>,
+	pub path_pattern: Option<String>,
+	pub exact_durations: Option<bool>,
+	pub skip_non_utf8_paths: Option<bool>,
+	pub password_min_length: Option<i32>,
+	pub password_require_complexity: Option<bool>,
+	pub case_insensitive_paths: Option<bool>,
+	pub folder_name_albums: Option<bool>,
+	pub min_year: Option<i32>,
+	pub max_year: Option<i32>,
+	pub thumbnail_default_size: Option<String>,
+	pub artwork_preference: Option<String>,
+	pub compilation_artist_names: Option<Vec<String>>,
+	pub transcode_targets: Option<Vec<String>>,
 	pub reindex_every_n_seconds: Option<i32>,
 }
 
@@ -211,6 +726,21 @@ impl From<NewSettings> for settings::NewSettings {
 	fn from(s: NewSettings) -> Self {
 		Self {
 			album_art_pattern: s.album_art_pattern,
+			album_artist_fallback: s.album_artist_fallback,
+			keep_unreadable_songs: s.keep_unreadable_songs,
+			path_pattern: s.path_pattern,
+			exact_durations: s.exact_durations,
+			skip_non_utf8_paths: s.skip_non_utf8_paths,
+			password_min_length: s.password_min_length,
+			password_require_complexity: s.password_require_complexity,
+			case_insensitive_paths: s.case_insensitive_paths,
+			folder_name_albums: s.folder_name_albums,
+			min_year: s.min_year,
+			max_year: s.max_year,
+			thumbnail_default_size: s.thumbnail_default_size,
+			artwork_preference: s.artwork_preference,
+			compilation_artist_names: s.compilation_artist_names,
+			transcode_targets: s.transcode_targets,
 			reindex_every_n_seconds: s.reindex_every_n_seconds,
 		}
 	}
@@ -219,13 +749,45 @@ impl From<NewSettings> for settings::NewSettings {
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Settings {
 	pub album_art_pattern: String,
+	pub album_artist_fallback: String,
+	pub keep_unreadable_songs: bool,
+	pub path_pattern: String,
+	pub exact_durations: bool,
+	pub skip_non_utf8_paths: bool,
+	pub password_min_length: i32,
+	pub password_require_complexity: bool,
+	pub case_insensitive_paths: bool,
+	pub folder_name_albums: bool,
+	pub min_year: i32,
+	pub max_year: i32,
+	pub thumbnail_default_size: String,
+	pub artwork_preference: String,
+	pub compilation_artist_names: Vec<String>,
+	pub transcode_targets: Vec<String>,
 	pub reindex_every_n_seconds: i32,
 }
 
 impl From<settings::Settings> for Settings {
 	fn from(s: settings::Settings) -> Self {
+		let compilation_artist_names = s.compilation_artist_names();
+		let transcode_targets = s.transcode_targets();
 		Self {
 			album_art_pattern: s.index_album_art_pattern,
+			album_artist_fallback: s.index_album_artist_fallback,
+			keep_unreadable_songs: s.index_keep_unreadable_songs,
+			path_pattern: s.index_path_pattern,
+			exact_durations: s.index_exact_durations,
+			skip_non_utf8_paths: s.index_skip_non_utf8_paths,
+			password_min_length: s.password_min_length,
+			password_require_complexity: s.password_require_complexity,
+			case_insensitive_paths: s.case_insensitive_paths,
+			folder_name_albums: s.index_folder_name_albums,
+			min_year: s.index_min_year,
+			max_year: s.index_max_year,
+			thumbnail_default_size: s.thumbnail_default_size,
+			artwork_preference: s.index_artwork_preference,
+			compilation_artist_names,
+			transcode_targets,
 			reindex_every_n_seconds: s.index_sleep_duration_seconds,
 		}
 	}
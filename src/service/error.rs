@@ -1,8 +1,10 @@
 use std::path::PathBuf;
 use thiserror::Error;
 
-use crate::app::index::QueryError;
-use crate::app::{config, ddns, lastfm, playlist, settings, thumbnail, user, vfs};
+use crate::app::index::{QueryError, UpdateError};
+use crate::app::{
+	audit, config, ddns, lastfm, playlist, settings, thumbnail, transcode, user, vfs,
+};
 use crate::db;
 
 #[derive(Error, Debug)]
@@ -11,6 +13,8 @@ pub enum APIError {
 	AuthorizationTokenEncoding,
 	#[error("Administrator permission is required")]
 	AdminPermissionRequired,
+	#[error("Album not found")]
+	AlbumNotFound,
 	#[error("Audio file could not be opened")]
 	AudioFileIOError,
 	#[error("Authentication is required")]
@@ -53,10 +57,18 @@ pub enum APIError {
 	PasswordHashing,
 	#[error("Playlist not found")]
 	PlaylistNotFound,
+	#[error("Playlist has no track at index {0}")]
+	PlaylistTrackIndexOutOfRange(usize),
+	#[error("An index update is already in progress")]
+	ScanInProgress,
 	#[error("Settings error:\n\n{0}")]
 	Settings(settings::Error),
+	#[error("Invalid settings:\n\n{0}")]
+	SettingsInvalid(settings::Error),
 	#[error("Song not found")]
 	SongMetadataNotFound,
+	#[error("Could not read tags from `{0}`:\n\n{1}")]
+	SongTagsUnreadable(PathBuf, String),
 	#[error("Could not decode thumbnail from flac file `{0}`:\n\n{1}")]
 	ThumbnailFlacDecoding(PathBuf, metaflac::Error),
 	#[error("Thumbnail file could not be opened")]
@@ -67,6 +79,18 @@ pub enum APIError {
 	ThumbnailImageDecoding(PathBuf, image::error::ImageError),
 	#[error("Could not decode thumbnail from mp4 file `{0}`:\n\n{1}")]
 	ThumbnailMp4Decoding(PathBuf, mp4ameta::Error),
+	#[error("Thumbnail padding color must be a hex color code")]
+	ThumbnailPadColorInvalid,
+	#[error("Too many thumbnails are being generated, try again later")]
+	ThumbnailServiceBusy,
+	#[error("Transcoding to this codec and bitrate is not allowed")]
+	TranscodeTargetNotAllowed,
+	#[error("Too many songs are being transcoded, try again later")]
+	TranscodingServiceBusy,
+	#[error("Could not start transcoding `{0}`:\n\n{1}")]
+	TranscodingUnavailable(PathBuf, std::io::Error),
+	#[error("Too many failed login attempts")]
+	TooManyLoginAttempts(std::time::Duration),
 	#[error("Toml deserialization error:\n\n{0}")]
 	TomlDeserialization(toml::de::Error),
 	#[error("Unsupported thumbnail format: `{0}`")]
@@ -75,6 +99,8 @@ pub enum APIError {
 	UserNotFound,
 	#[error("Path not found in virtual filesystem")]
 	VFSPathNotFound,
+	#[error("{0}")]
+	WeakPassword(String),
 }
 
 impl From<config::Error> for APIError {
@@ -96,6 +122,7 @@ impl From<playlist::Error> for APIError {
 			playlist::Error::Database(e) => APIError::Database(e),
 			playlist::Error::DatabaseConnection(e) => e.into(),
 			playlist::Error::PlaylistNotFound => APIError::PlaylistNotFound,
+			playlist::Error::TrackIndexOutOfRange(i) => APIError::PlaylistTrackIndexOutOfRange(i),
 			playlist::Error::UserNotFound => APIError::UserNotFound,
 			playlist::Error::Vfs(e) => e.into(),
 		}
@@ -107,12 +134,26 @@ impl From<QueryError> for APIError {
 		match error {
 			QueryError::Database(e) => APIError::Database(e),
 			QueryError::DatabaseConnection(e) => e.into(),
+			QueryError::AlbumNotFound(_) => APIError::AlbumNotFound,
 			QueryError::SongNotFound(_) => APIError::SongMetadataNotFound,
+			QueryError::TagsUnreadable(p, e) => APIError::SongTagsUnreadable(p, e.to_string()),
 			QueryError::Vfs(e) => e.into(),
 		}
 	}
 }
 
+impl From<UpdateError> for APIError {
+	fn from(error: UpdateError) -> APIError {
+		match error {
+			UpdateError::IndexClean(_) => APIError::Internal,
+			UpdateError::Database(e) => APIError::Database(e),
+			UpdateError::DatabaseConnection(e) => e.into(),
+			UpdateError::ScanInProgress => APIError::ScanInProgress,
+			UpdateError::Vfs(e) => e.into(),
+		}
+	}
+}
+
 impl From<settings::Error> for APIError {
 	fn from(error: settings::Error) -> APIError {
 		match error {
@@ -121,6 +162,12 @@ impl From<settings::Error> for APIError {
 			settings::Error::AuthenticationSecretInvalid => APIError::Settings(error),
 			settings::Error::MiscSettingsNotFound => APIError::Settings(error),
 			settings::Error::IndexAlbumArtPatternInvalid => APIError::Settings(error),
+			settings::Error::IndexAlbumArtistFallbackInvalid => APIError::SettingsInvalid(error),
+			settings::Error::IndexArtworkPreferenceInvalid => APIError::SettingsInvalid(error),
+			settings::Error::IndexPathPatternInvalid => APIError::SettingsInvalid(error),
+			settings::Error::IndexSleepDurationInvalid => APIError::SettingsInvalid(error),
+			settings::Error::ThumbnailDefaultSizeInvalid => APIError::SettingsInvalid(error),
+			settings::Error::TranscodeTargetInvalid => APIError::SettingsInvalid(error),
 			settings::Error::Database(e) => APIError::Database(e),
 		}
 	}
@@ -141,6 +188,17 @@ impl From<user::Error> for APIError {
 			user::Error::InvalidAuthToken => APIError::IncorrectCredentials,
 			user::Error::MissingLastFMSessionKey => APIError::IncorrectCredentials,
 			user::Error::PasswordHashing => APIError::PasswordHashing,
+			user::Error::TooManyLoginAttempts(d) => APIError::TooManyLoginAttempts(d),
+			user::Error::WeakPassword(s) => APIError::WeakPassword(s),
+		}
+	}
+}
+
+impl From<transcode::Error> for APIError {
+	fn from(error: transcode::Error) -> APIError {
+		match error {
+			transcode::Error::Spawn(p, e) => APIError::TranscodingUnavailable(p, e),
+			transcode::Error::TooManyRequests => APIError::TranscodingServiceBusy,
 		}
 	}
 }
@@ -156,6 +214,15 @@ impl From<vfs::Error> for APIError {
 	}
 }
 
+impl From<audit::Error> for APIError {
+	fn from(error: audit::Error) -> APIError {
+		match error {
+			audit::Error::Database(e) => APIError::Database(e),
+			audit::Error::DatabaseConnection(e) => e.into(),
+		}
+	}
+}
+
 impl From<ddns::Error> for APIError {
 	fn from(error: ddns::Error) -> APIError {
 		match error {
@@ -200,6 +267,7 @@ impl From<thumbnail::Error> for APIError {
 			thumbnail::Error::Metaflac(p, e) => APIError::ThumbnailFlacDecoding(p, e),
 			thumbnail::Error::Mp4aMeta(p, e) => APIError::ThumbnailMp4Decoding(p, e),
 			thumbnail::Error::UnsupportedFormat(f) => APIError::UnsupportedThumbnailFormat(f),
+			thumbnail::Error::TooManyRequests => APIError::ThumbnailServiceBusy,
 		}
 	}
 }
@@ -108,6 +108,15 @@ pub trait TestService {
 			}
 			std::thread::sleep(Duration::from_secs(1));
 		}
+
+		loop {
+			let setup_status_request = protocol::setup_status();
+			let response = self.fetch_json::<_, dto::SetupStatus>(&setup_status_request);
+			if response.body().initial_scan_completed {
+				break;
+			}
+			std::thread::sleep(Duration::from_secs(1));
+		}
 	}
 }
 
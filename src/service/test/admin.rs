@@ -1,8 +1,9 @@
 use http::StatusCode;
+use std::path::PathBuf;
 
-use crate::app::index;
+use crate::app::{index, transcode};
 use crate::service::dto;
-use crate::service::test::{protocol, ServiceType, TestService};
+use crate::service::test::{constants::*, protocol, ServiceType, TestService};
 use crate::test_name;
 
 #[test]
@@ -13,6 +14,23 @@ fn returns_api_version() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn returns_capabilities() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::capabilities();
+	let response = service.fetch_json::<_, dto::Capabilities>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let capabilities = response.body();
+	assert!(capabilities.features.contains(&"lastfm".to_owned()));
+	assert_eq!(
+		capabilities.features.contains(&"transcoding".to_owned()),
+		transcode::is_available()
+	);
+	assert!(!capabilities.features.contains(&"thumbnail_webp".to_owned()));
+	assert!(capabilities.thumbnail_formats.contains(&"webp".to_owned()));
+	assert!(capabilities.audio_extensions.contains(&"flac".to_owned()));
+}
+
 #[test]
 fn initial_setup_golden_path() {
 	let mut service = ServiceType::new(&test_name!());
@@ -42,6 +60,39 @@ fn initial_setup_golden_path() {
 	}
 }
 
+#[test]
+fn setup_status_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::setup_status();
+	{
+		let response = service.fetch_json::<_, dto::SetupStatus>(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.body(),
+			&dto::SetupStatus {
+				admin_created: false,
+				mount_dirs_configured: false,
+				initial_scan_completed: false,
+			}
+		);
+	}
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	{
+		let response = service.fetch_json::<_, dto::SetupStatus>(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.body(),
+			&dto::SetupStatus {
+				admin_created: true,
+				mount_dirs_configured: true,
+				initial_scan_completed: true,
+			}
+		);
+	}
+}
+
 #[test]
 fn trigger_index_golden_path() {
 	let mut service = ServiceType::new(&test_name!());
@@ -61,6 +112,22 @@ fn trigger_index_golden_path() {
 	assert_eq!(entries.len(), 3);
 }
 
+#[test]
+fn trigger_index_reports_running_update() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::trigger_index();
+	let response = service.fetch_json::<_, dto::TriggerIndexResult>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(!response.body().already_running);
+
+	let response = service.fetch_json::<_, dto::TriggerIndexResult>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().already_running);
+}
+
 #[test]
 fn trigger_index_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
@@ -79,3 +146,239 @@ fn trigger_index_requires_admin() {
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
+
+#[test]
+fn clean_index_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::clean_index();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn clean_index_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::clean_index();
+	let response = service.fetch_json::<_, index::CleanReport>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body(), &index::CleanReport::default());
+}
+
+#[test]
+fn refresh_artwork_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+	let request = protocol::thumbnail(&path, None, None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::refresh_artwork(false);
+	let response = service.fetch_json::<_, dto::RefreshArtworkResult>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().invalidated, 0);
+
+	let request = protocol::refresh_artwork(true);
+	let response = service.fetch_json::<_, dto::RefreshArtworkResult>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().invalidated, 1);
+}
+
+#[test]
+fn refresh_artwork_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::refresh_artwork(true);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn scan_status_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::scan_status();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+	let request = protocol::cancel_scan();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn scan_status_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::scan_status();
+	let response = service.fetch_json::<_, index::ScanStatus>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let status = response.body();
+	assert!(!status.running);
+	assert_eq!(status.songs_found, 13);
+
+	let request = protocol::cancel_scan();
+	let response = service.fetch_json::<_, index::ScanStatus>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let status = response.body();
+	assert!(!status.running);
+	assert!(!status.cancel_requested);
+}
+
+#[test]
+fn scan_history_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::scan_history();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn scan_history_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::scan_history();
+	let response = service.fetch_json::<_, Vec<index::ScanRecord>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+
+	service.index();
+
+	let response = service.fetch_json::<_, Vec<index::ScanRecord>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let history = response.body();
+	assert_eq!(history.len(), 1);
+	assert_eq!(history[0].songs_found, 13);
+	assert!(!history[0].cancelled);
+}
+
+#[test]
+fn resolve_path_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::resolve_path(&PathBuf::from(TEST_MOUNT_NAME));
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn resolve_path_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis"].iter().collect();
+	let request = protocol::resolve_path(&path);
+	let response = service.fetch_json::<_, dto::ResolvedPath>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let resolved = response.body();
+	assert!(resolved.exists);
+	assert!(PathBuf::from(&resolved.real_path)
+		.ends_with([TEST_MOUNT_SOURCE, "Khemmis"].iter().collect::<PathBuf>()));
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Nonexistent"].iter().collect();
+	let request = protocol::resolve_path(&path);
+	let response = service.fetch_json::<_, dto::ResolvedPath>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(!response.body().exists);
+
+	let request = protocol::resolve_path(&PathBuf::from("not_a_mount"));
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn raw_tags_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::raw_tags(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn raw_tags_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::raw_tags(&path);
+	let response = service.fetch_json::<_, Vec<dto::RawTag>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().contains(&dto::RawTag {
+		key: "TIT2".to_owned(),
+		value: "Candlelight".to_owned(),
+	}));
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+	let request = protocol::raw_tags(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn audit_log_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::audit_log();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn audit_log_records_admin_actions() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::create_user(dto::NewUser {
+		name: "Walter".into(),
+		password: "secret".into(),
+		admin: false,
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::trigger_index();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::audit_log();
+	let response = service.fetch_json::<_, Vec<dto::AuditLogEntry>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries[0].action, "trigger_index");
+	assert_eq!(entries[0].actor.as_deref(), Some(TEST_USERNAME_ADMIN));
+	assert_eq!(entries[1].action, "create_user");
+	assert_eq!(entries[1].details, "Walter");
+}
@@ -25,6 +25,23 @@ fn login_rejects_bad_password() {
 	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
+#[test]
+fn login_rejects_repeated_failures() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+
+	let request = protocol::login(TEST_USERNAME, "garbage");
+	for _ in 0..5 {
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+	}
+
+	let request = protocol::login(TEST_USERNAME, TEST_PASSWORD);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+	assert!(response.headers().contains_key(http::header::RETRY_AFTER));
+}
+
 #[test]
 fn login_golden_path() {
 	let mut service = ServiceType::new(&test_name!());
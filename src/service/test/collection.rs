@@ -1,8 +1,10 @@
-use http::StatusCode;
+use http::{header, StatusCode};
 use std::path::{Path, PathBuf};
 
 use crate::app::index;
+use crate::service::dto;
 use crate::service::test::{add_trailing_slash, constants::*, protocol, ServiceType, TestService};
+use crate::service::INDEX_PENDING_HEADER;
 use crate::test_name;
 
 #[test]
@@ -56,14 +58,577 @@ fn browse_bad_directory() {
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[test]
+fn browse_song_directory_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::browse_song_directory(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn browse_song_directory_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::browse_song_directory(&path);
+	let response = service.fetch_json::<_, Vec<index::CollectionFile>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 5);
+	assert!(entries.iter().any(|e| match e {
+		index::CollectionFile::Song(s) => Path::new(&s.path) == path,
+		_ => false,
+	}));
+}
+
+#[test]
+fn browse_song_directory_case_insensitive_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::put_settings(dto::NewSettings {
+		case_insensitive_paths: Some(true),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "KHEMMIS", "hunted", "02 - CANDLELIGHT.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::browse_song_directory(&path);
+	let response = service.fetch_json::<_, Vec<index::CollectionFile>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 5);
+}
+
+#[test]
+fn browse_song_directory_bad_song() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "06 - Not A Song.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::browse_song_directory(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn album_at_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::album_at(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn album_at_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::album_at(&path);
+	let response = service.fetch_json::<_, index::Directory>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let album = response.body();
+	assert_eq!(Path::new(&album.path), path);
+	assert_eq!(album.album, Some("Hunted".to_owned()));
+}
+
+#[test]
+fn album_at_bad_directory() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME].iter().collect();
+	let request = protocol::album_at(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn album_credits_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::album_credits(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn album_credits_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::album_credits(&path);
+	let response = service.fetch_json::<_, index::AlbumCredits>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().performers, vec!["Khemmis".to_owned()]);
+}
+
+#[test]
+fn album_credits_bad_directory() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME].iter().collect();
+	let request = protocol::album_credits(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn album_page_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::album_page(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn album_page_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::album_page(&path);
+	let response = service.fetch_json::<_, index::AlbumPage>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let page = response.body();
+	assert_eq!(page.album.album, Some("Hunted".to_owned()));
+	assert_eq!(page.songs.len(), 5);
+	assert_eq!(page.credits.performers, vec!["Khemmis".to_owned()]);
+	assert!(page.similar_albums.is_empty());
+}
+
+#[test]
+fn artist_songs_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::artist_songs("Khemmis", None);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn artist_songs_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::artist_songs("Khemmis", Some(dto::SongSort::Title));
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let songs = response.body();
+	assert_eq!(songs.len(), 5);
+	assert_eq!(songs[1].title, Some("Beyond The Door".to_owned()));
+}
+
+#[test]
+fn similar_artists_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::similar_artists("Khemmis", 5);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn similar_artists_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::similar_artists("Khemmis", 5);
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+}
+
+#[test]
+fn genre_songs_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::genre_songs(&["Metal"]);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn genre_songs_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::genre_songs(&["Metal", "Electronic", "Metal"]);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let songs = response.body();
+	assert_eq!(songs.len(), 13);
+	assert_eq!(songs[0].title, Some("Above The Water".to_owned()));
+}
+
+#[test]
+fn responses_flag_collection_pending_initial_scan() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::browse(Path::new(""));
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(INDEX_PENDING_HEADER).unwrap(),
+		"true"
+	);
+
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let is_pending = |service: &mut ServiceType| {
+		let response = service.fetch(&protocol::browse(Path::new("")));
+		response.headers().contains_key(INDEX_PENDING_HEADER)
+	};
+	let mut attempts = 0;
+	while is_pending(&mut service) {
+		attempts += 1;
+		assert!(attempts < 30);
+		std::thread::sleep(std::time::Duration::from_secs(1));
+	}
+}
+
+#[test]
+fn index_generation_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::index_generation();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn index_generation_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::index_generation();
+	let response = service.fetch_json::<_, index::GenerationInfo>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().generation, None);
+
+	service.index();
+	let response = service.fetch_json::<_, index::GenerationInfo>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().generation.is_some());
+	assert!(response.body().date.is_some());
+}
+
+#[test]
+fn sync_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::sync(None);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn sync_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::sync(None);
+	let response = service.fetch_json::<_, index::SyncChanges>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let changes = response.body();
+	assert!(changes.full_sync);
+	assert_eq!(changes.added.len(), 13);
+
+	let request = protocol::sync(changes.generation);
+	let response = service.fetch_json::<_, index::SyncChanges>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let changes = response.body();
+	assert!(!changes.full_sync);
+	assert!(changes.added.is_empty());
+}
+
+#[test]
+fn sync_not_modified() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::sync(None);
+	let response = service.fetch_json::<_, index::SyncChanges>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let etag = response.headers().get(header::ETAG).unwrap().clone();
+	let generation = response.body().generation;
+
+	let mut request = protocol::sync(None);
+	request
+		.headers_mut()
+		.append(header::IF_NONE_MATCH, etag.clone());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+	// Syncing from another generation yields a different response
+	let mut request = protocol::sync(generation);
+	request.headers_mut().append(header::IF_NONE_MATCH, etag);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
 #[test]
 fn flatten_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
-	let request = protocol::flatten(&PathBuf::new());
+	let request = protocol::flatten(&PathBuf::new());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn flatten_stream_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::flatten_stream(&PathBuf::new());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn stats_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::stats();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn stats_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::stats();
+	let response = service.fetch_json::<_, index::Statistics>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().num_songs, 13);
+	assert_eq!(response.body().num_albums, 3);
+}
+
+#[test]
+fn years_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::years();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn years_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::years();
+	let response = service.fetch_json::<_, Vec<index::Year>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 1);
+	assert_eq!(response.body()[0].year, 2016);
+}
+
+#[test]
+fn years_can_paginate() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::years_page(1, 5);
+	let response = service.fetch_json::<_, Vec<index::Year>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+}
+
+#[test]
+fn year_albums_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::year_albums(2016);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn year_albums_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::year_albums(2016);
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 3);
+}
+
+#[test]
+fn year_albums_can_wrap_page_in_envelope() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::year_albums_page(2016, 1, 1, true);
+	let response = service.fetch_json::<_, dto::Page<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let page = response.body();
+	assert_eq!(page.items.len(), 1);
+	assert_eq!(page.total, 3);
+	assert_eq!(page.offset, 1);
+	assert_eq!(page.count, 1);
+}
+
+#[test]
+fn decades_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::decades();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn decades_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::decades();
+	let response = service.fetch_json::<_, Vec<index::Decade>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&vec![index::Decade {
+			decade: 2010,
+			num_albums: 3
+		}]
+	);
+}
+
+#[test]
+fn labels_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::labels();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn labels_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::labels();
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn labels_can_paginate() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::labels();
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	let labels = response.body().clone();
+
+	let request = protocol::labels_page(1, 5);
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body().as_slice(),
+		labels.get(1..).unwrap_or_default()
+	);
+}
+
+#[test]
+fn label_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::label("Svart Records");
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
+#[test]
+fn label_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::label("Svart Records");
+	let response = service.fetch_json::<_, index::Label>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().albums.is_empty());
+}
+
 #[test]
 fn flatten_root() {
 	let mut service = ServiceType::new(&test_name!());
@@ -79,6 +644,55 @@ fn flatten_root() {
 	assert_eq!(entries.len(), 13);
 }
 
+#[test]
+fn flatten_can_paginate() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::flatten_page(&PathBuf::new(), 10, 5, false);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 3);
+}
+
+#[test]
+fn flatten_can_sort_songs() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis"].iter().collect();
+	let request = protocol::flatten_sorted(&path, dto::SongSort::Title);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let songs = response.body();
+	assert_eq!(songs.len(), 5);
+	assert_eq!(songs[1].title, Some("Beyond The Door".to_owned()));
+}
+
+#[test]
+fn flatten_can_wrap_page_in_envelope() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::flatten_page(&PathBuf::new(), 2, 5, true);
+	let response = service.fetch_json::<_, dto::Page<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let page = response.body();
+	assert_eq!(page.items.len(), 5);
+	assert_eq!(page.total, 13);
+	assert_eq!(page.offset, 2);
+	assert_eq!(page.count, 5);
+}
+
 #[test]
 fn flatten_directory() {
 	let mut service = ServiceType::new(&test_name!());
@@ -94,6 +708,58 @@ fn flatten_directory() {
 	assert_eq!(entries.len(), 13);
 }
 
+#[test]
+fn flatten_selected_fields() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::flatten_with_fields(Path::new(TEST_MOUNT_NAME), "path,title");
+	let response = service.fetch_json::<_, Vec<serde_json::Value>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 13);
+	for entry in entries {
+		let keys: Vec<&String> = entry.as_object().unwrap().keys().collect();
+		assert_eq!(keys, vec!["path", "title"]);
+	}
+}
+
+#[test]
+fn flatten_real_paths_require_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::flatten_with_real_paths(Path::new(TEST_MOUNT_NAME));
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn flatten_can_include_real_paths() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::flatten_with_real_paths(Path::new(TEST_MOUNT_NAME));
+	let response = service.fetch_json::<_, Vec<serde_json::Value>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 13);
+	for entry in entries {
+		let keys: Vec<&String> = entry.as_object().unwrap().keys().collect();
+		assert_eq!(keys, vec!["path", "real_path"]);
+		let real_path = entry["real_path"].as_str().unwrap();
+		assert!(real_path.contains("small-collection"));
+	}
+}
+
 #[test]
 fn flatten_bad_directory() {
 	let mut service = ServiceType::new(&test_name!());
@@ -106,6 +772,218 @@ fn flatten_bad_directory() {
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[test]
+fn flatten_stream_matches_flatten() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::flatten(Path::new(TEST_MOUNT_NAME));
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	let expected = response.into_body();
+
+	let request = protocol::flatten_stream(Path::new(TEST_MOUNT_NAME));
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(http::header::CONTENT_TYPE).unwrap(),
+		"application/x-ndjson"
+	);
+	let songs: Vec<index::Song> = std::str::from_utf8(response.body())
+		.unwrap()
+		.lines()
+		.map(|line| serde_json::from_str(line).unwrap())
+		.collect();
+	assert_eq!(songs, expected);
+}
+
+#[test]
+fn flatten_stream_bad_directory() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let path: PathBuf = ["not_my_collection"].iter().collect();
+	let request = protocol::flatten_stream(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn artists_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::artists();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn artists_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::artists();
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&vec!["Khemmis".to_owned(), "Tobokegao".to_owned()]
+	);
+}
+
+#[test]
+fn artists_can_paginate() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::artists_page(1, 5);
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body(), &vec!["Tobokegao".to_owned()]);
+}
+
+#[test]
+fn artist_buckets_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::artist_buckets();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn artist_buckets_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::artist_buckets();
+	let response = service.fetch_json::<_, Vec<index::ArtistBucket>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let letters: Vec<char> = response.body().iter().map(|b| b.letter).collect();
+	assert_eq!(letters, vec!['K', 'T']);
+}
+
+#[test]
+fn field_values_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::field_values("artist");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn field_values_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::field_values("artist");
+	let response = service.fetch_json::<_, Vec<index::FieldValue>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&vec![
+			index::FieldValue {
+				value: "Khemmis".to_owned(),
+				num_songs: 5,
+			},
+			index::FieldValue {
+				value: "Tobokegao".to_owned(),
+				num_songs: 8,
+			},
+		]
+	);
+}
+
+#[test]
+fn field_values_can_paginate() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::field_values_page("artist", 1, 5);
+	let response = service.fetch_json::<_, Vec<index::FieldValue>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let values: Vec<&str> = response.body().iter().map(|v| v.value.as_str()).collect();
+	assert_eq!(values, vec!["Tobokegao"]);
+}
+
+#[test]
+fn field_values_bad_field() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::field_values("mood");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn random_artists_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::random_artists(5, 0);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn random_artists_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::random_artists(1, 0);
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 1);
+
+	let request = protocol::random_artists(5, 0);
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 2);
+}
+
+#[test]
+fn random_genres_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::random_genres(5, 0);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn random_genres_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::random_genres(5, 0);
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let seeded = response.body().clone();
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.body(), &seeded);
+}
+
 #[test]
 fn random_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
@@ -129,6 +1007,23 @@ fn random_golden_path() {
 	assert_eq!(entries.len(), 3);
 }
 
+#[test]
+fn random_is_not_paged() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let mut request = protocol::random();
+	*request.uri_mut() = "/api/random?envelope=true&offset=1&count=1"
+		.parse()
+		.unwrap();
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 3);
+}
+
 #[test]
 fn random_with_trailing_slash() {
 	let mut service = ServiceType::new(&test_name!());
@@ -168,6 +1063,21 @@ fn recent_golden_path() {
 	assert_eq!(entries.len(), 3);
 }
 
+#[test]
+fn recent_can_exclude_singles() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::recent_albums_only();
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 2);
+}
+
 #[test]
 fn recent_with_trailing_slash() {
 	let mut service = ServiceType::new(&test_name!());
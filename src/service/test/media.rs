@@ -1,7 +1,7 @@
 use http::{header, HeaderValue, StatusCode};
 use std::path::PathBuf;
 
-use crate::service::dto::ThumbnailSize;
+use crate::service::dto::{self, ThumbnailSize};
 use crate::service::test::{constants::*, protocol, ServiceType, TestService};
 use crate::test_name;
 
@@ -40,6 +40,33 @@ fn audio_golden_path() {
 	);
 }
 
+#[test]
+fn audio_case_insensitive_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "KHEMMIS", "hunted", "02 - CANDLELIGHT.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::audio(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::put_settings(dto::NewSettings {
+		case_insensitive_paths: Some(true),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::audio(&path);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 24_142);
+}
+
 #[test]
 fn audio_does_not_encode_content() {
 	let mut service = ServiceType::new(&test_name!());
@@ -97,6 +124,123 @@ fn audio_partial_content() {
 	);
 }
 
+#[test]
+fn audio_not_modified() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::audio(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+	let mut request = protocol::audio(&path);
+	request.headers_mut().append(header::IF_NONE_MATCH, etag);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[test]
+fn audio_transcoded_not_modified() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	// Answered without starting ffmpeg
+	let mut request = protocol::audio_transcoded(&path, "opus", 96);
+	request
+		.headers_mut()
+		.append(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+	let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+	let mut request = protocol::audio_transcoded(&path, "opus", 96);
+	request
+		.headers_mut()
+		.append(header::IF_NONE_MATCH, etag.clone());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+	// Other options make for a different ETag
+	let mut request = protocol::audio_transcoded(&path, "mp3", 128);
+	request
+		.headers_mut()
+		.append(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+	assert_ne!(response.headers().get(header::ETAG), Some(&etag));
+
+	// So does seeking
+	let mut request = protocol::audio_transcoded(&path, "opus", 96);
+	let uri = format!("{}&seek=12.5", request.uri());
+	*request.uri_mut() = uri.parse().unwrap();
+	request
+		.headers_mut()
+		.append(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+	assert_ne!(response.headers().get(header::ETAG), Some(&etag));
+}
+
+#[test]
+fn audio_rejects_transcode_targets_not_allowed() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::audio_transcoded(&path, "mp3", 96);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+	let request = protocol::audio_transcoded(&path, "opus", 320);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn audio_unknown_transcode_codec_serves_original_file() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let mut request = protocol::audio_transcoded(&path, "wma", 128);
+	let headers = request.headers_mut();
+	headers.append(
+		header::RANGE,
+		HeaderValue::from_str("bytes=100-299").unwrap(),
+	);
+
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	assert_eq!(response.body().len(), 200);
+}
+
 #[test]
 fn audio_bad_path_returns_not_found() {
 	let mut service = ServiceType::new(&test_name!());
@@ -144,6 +288,97 @@ fn thumbnail_golden_path() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn thumbnail_not_modified() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+
+	let request = protocol::thumbnail(&path, None, None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+	let mut request = protocol::thumbnail(&path, None, None);
+	request
+		.headers_mut()
+		.append(header::IF_NONE_MATCH, etag.clone());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+	let mut request = protocol::thumbnail(&path, Some(ThumbnailSize::Large), None);
+	request.headers_mut().append(header::IF_NONE_MATCH, etag);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn prewarm_thumbnails_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::prewarm_thumbnails(dto::PrewarmThumbnailsInput {
+		paths: Vec::new(),
+		options: dto::ThumbnailOptions::default(),
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn prewarm_thumbnails_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+	let paths = vec![
+		path.to_string_lossy().into_owned(),
+		[TEST_MOUNT_NAME, "Khemmis", "Missing.jpg"]
+			.iter()
+			.collect::<PathBuf>()
+			.to_string_lossy()
+			.into_owned(),
+		"not_my_collection/Folder.jpg".to_owned(),
+	];
+
+	let request = protocol::prewarm_thumbnails(dto::PrewarmThumbnailsInput {
+		paths: paths.clone(),
+		options: dto::ThumbnailOptions::default(),
+	});
+	let response = service.fetch_json::<_, dto::PrewarmThumbnailsResult>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&dto::PrewarmThumbnailsResult {
+			already_cached: 0,
+			generated: 1,
+			failed: 2,
+		}
+	);
+
+	// The prewarmed thumbnail is the one served by default
+	let request = protocol::prewarm_thumbnails(dto::PrewarmThumbnailsInput {
+		paths,
+		options: dto::ThumbnailOptions {
+			size: Some(ThumbnailSize::Small),
+			pad: Some(true),
+			..Default::default()
+		},
+	});
+	let response = service.fetch_json::<_, dto::PrewarmThumbnailsResult>(&request);
+	assert_eq!(response.body().already_cached, 1);
+	assert_eq!(response.body().generated, 0);
+}
+
 #[test]
 fn thumbnail_bad_path_returns_not_found() {
 	let mut service = ServiceType::new(&test_name!());
@@ -159,29 +394,196 @@ fn thumbnail_bad_path_returns_not_found() {
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[test]
+fn thumbnail_pad_color() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+
+	let request = protocol::thumbnail_with_pad_color(&path, "#1a1a1a");
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::thumbnail_with_pad_color(&path, "dark");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn thumbnail_format() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+
+	let request = protocol::thumbnail_with_format(&path, "png");
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(header::CONTENT_TYPE).unwrap(),
+		"image/png"
+	);
+
+	// WebP encoding is not available, so these thumbnails are encoded as JPEG instead
+	let request = protocol::thumbnail_with_format(&path, "webp");
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(header::CONTENT_TYPE).unwrap(),
+		"image/jpeg"
+	);
+
+	let request = protocol::thumbnail_with_format(&path, "gif");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+#[cfg(not(tarpaulin))]
+fn thumbnail_fit() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Tobokegao", "Picnic", "Folder.png"]
+		.iter()
+		.collect();
+
+	let dimensions = |service: &mut ServiceType, fit: &str| {
+		let request = protocol::thumbnail_with_fit(&path, ThumbnailSize::Native, fit);
+		let response = service.fetch_bytes(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		image::load_from_memory(response.body())
+			.unwrap()
+			.to_rgb8()
+			.dimensions()
+	};
+	assert_eq!(dimensions(&mut service, "contain"), (1423, 1411));
+	assert_eq!(dimensions(&mut service, "cover"), (1411, 1411));
+	assert_eq!(dimensions(&mut service, "pad"), (1423, 1423));
+
+	let request = protocol::thumbnail_with_fit(&path, ThumbnailSize::Small, "stretch");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[test]
 fn thumbnail_size_default() {
-	thumbnail_size(&test_name!(), None, None, 400);
+	thumbnail_size(&test_name!(), None, None, (400, 400));
 }
 
 #[test]
 fn thumbnail_size_small() {
-	thumbnail_size(&test_name!(), Some(ThumbnailSize::Small), None, 400);
+	thumbnail_size(&test_name!(), Some(ThumbnailSize::Small), None, (400, 400));
 }
 
 #[test]
 #[cfg(not(tarpaulin))]
 fn thumbnail_size_large() {
-	thumbnail_size(&test_name!(), Some(ThumbnailSize::Large), None, 1200);
+	thumbnail_size(
+		&test_name!(),
+		Some(ThumbnailSize::Large),
+		None,
+		(1200, 1200),
+	);
 }
 
 #[test]
 #[cfg(not(tarpaulin))]
 fn thumbnail_size_native() {
-	thumbnail_size(&test_name!(), Some(ThumbnailSize::Native), None, 1423);
+	thumbnail_size(
+		&test_name!(),
+		Some(ThumbnailSize::Native),
+		None,
+		(1423, 1423),
+	);
+}
+
+#[test]
+#[cfg(not(tarpaulin))]
+fn thumbnail_size_native_padded() {
+	thumbnail_size(
+		&test_name!(),
+		Some(ThumbnailSize::Native),
+		Some(true),
+		(1423, 1423),
+	);
+}
+
+#[test]
+#[cfg(not(tarpaulin))]
+fn thumbnail_size_configured_default() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::put_settings(dto::NewSettings {
+		thumbnail_default_size: Some("large".to_owned()),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Tobokegao", "Picnic", "Folder.png"]
+		.iter()
+		.collect();
+
+	let request = protocol::thumbnail(&path, None, None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let thumbnail = image::load_from_memory(response.body()).unwrap().to_rgb8();
+	assert_eq!(thumbnail.dimensions(), (1200, 1200));
+
+	let request = protocol::thumbnail(&path, Some(ThumbnailSize::Small), None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let thumbnail = image::load_from_memory(response.body()).unwrap().to_rgb8();
+	assert_eq!(thumbnail.dimensions(), (400, 400));
+}
+
+#[test]
+fn thumbnail_native_serves_original_file() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Tobokegao", "Picnic", "Folder.png"]
+		.iter()
+		.collect();
+
+	let request = protocol::thumbnail(&path, Some(ThumbnailSize::Native), Some(false));
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let original = std::fs::read("test-data/small-collection/Tobokegao/Picnic/Folder.png").unwrap();
+	assert_eq!(response.body(), &original);
+	assert_eq!(
+		response.headers().get(header::CONTENT_TYPE).unwrap(),
+		"image/png"
+	);
 }
 
-fn thumbnail_size(name: &str, size: Option<ThumbnailSize>, pad: Option<bool>, expected: u32) {
+fn thumbnail_size(
+	name: &str,
+	size: Option<ThumbnailSize>,
+	pad: Option<bool>,
+	expected: (u32, u32),
+) {
 	let mut service = ServiceType::new(name);
 	service.complete_initial_setup();
 	service.login_admin();
@@ -196,6 +598,5 @@ fn thumbnail_size(name: &str, size: Option<ThumbnailSize>, pad: Option<bool>, ex
 	let response = service.fetch_bytes(&request);
 	assert_eq!(response.status(), StatusCode::OK);
 	let thumbnail = image::load_from_memory(response.body()).unwrap().to_rgb8();
-	assert_eq!(thumbnail.width(), expected);
-	assert_eq!(thumbnail.height(), expected);
+	assert_eq!(thumbnail.dimensions(), expected);
 }
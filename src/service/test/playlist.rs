@@ -1,4 +1,5 @@
 use http::StatusCode;
+use std::path::PathBuf;
 
 use crate::app::index;
 use crate::service::dto;
@@ -23,10 +24,35 @@ fn list_playlists_golden_path() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn list_playlists_includes_dates() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: Vec::new(),
+		dedupe: None,
+	};
+	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::playlists();
+	let response = service.fetch_json::<_, Vec<dto::ListPlaylistsEntry>>(&request);
+	let playlists = response.body();
+	assert_eq!(playlists.len(), 1);
+	assert!(playlists[0].updated_at > 0);
+	assert_eq!(playlists[0].created_at, Some(playlists[0].updated_at));
+}
+
 #[test]
 fn save_playlist_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
-	let my_playlist = dto::SavePlaylistInput { tracks: Vec::new() };
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: Vec::new(),
+		dedupe: None,
+	};
 	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
@@ -38,12 +64,31 @@ fn save_playlist_golden_path() {
 	service.complete_initial_setup();
 	service.login();
 
-	let my_playlist = dto::SavePlaylistInput { tracks: Vec::new() };
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: Vec::new(),
+		dedupe: None,
+	};
 	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn save_playlist_dedupe() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: vec!["a.mp3".to_owned(), "b.mp3".to_owned(), "a.mp3".to_owned()],
+		dedupe: Some(true),
+	};
+	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+	let response = service.fetch_json::<_, dto::SavePlaylistResult>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().duplicates_removed, 1);
+}
+
 #[test]
 fn save_playlist_large() {
 	let mut service = ServiceType::new(&test_name!());
@@ -53,12 +98,54 @@ fn save_playlist_large() {
 	let tracks = (0..100_000)
 		.map(|_| "My Super Cool Song".to_string())
 		.collect();
-	let my_playlist = dto::SavePlaylistInput { tracks };
+	let my_playlist = dto::SavePlaylistInput {
+		tracks,
+		dedupe: None,
+	};
 	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn save_playlists_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::save_playlists(Vec::new());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn save_playlists_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::save_playlists(vec![
+		dto::BulkSavePlaylistInput {
+			name: TEST_PLAYLIST_NAME.to_owned(),
+			tracks: vec!["not_my_collection/song.mp3".to_owned()],
+		},
+		dto::BulkSavePlaylistInput {
+			name: "".to_owned(),
+			tracks: Vec::new(),
+		},
+	]);
+	let response = service.fetch_json::<_, Vec<dto::BulkSavePlaylistResult>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let results = response.body();
+	assert_eq!(results.len(), 2);
+	assert!(results[0].saved);
+	assert_eq!(results[0].tracks_skipped, 1);
+	assert!(!results[1].saved);
+
+	let request = protocol::playlists();
+	let response = service.fetch_json::<_, Vec<dto::ListPlaylistsEntry>>(&request);
+	let playlists = response.body();
+	assert_eq!(playlists.len(), 1);
+	assert_eq!(playlists[0].name, TEST_PLAYLIST_NAME);
+}
+
 #[test]
 fn get_playlist_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
@@ -74,7 +161,10 @@ fn get_playlist_golden_path() {
 	service.login();
 
 	{
-		let my_playlist = dto::SavePlaylistInput { tracks: Vec::new() };
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: Vec::new(),
+			dedupe: None,
+		};
 		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 		let response = service.fetch(&request);
 		assert_eq!(response.status(), StatusCode::OK);
@@ -96,6 +186,164 @@ fn get_playlist_bad_name_returns_not_found() {
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[test]
+fn append_playlist_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::append_playlist(
+		TEST_PLAYLIST_NAME,
+		dto::AppendPlaylistInput { tracks: Vec::new() },
+	);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn append_playlist_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let first: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let second: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "05 - Hunted.mp3"]
+		.iter()
+		.collect();
+
+	{
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: vec![first.to_string_lossy().into_owned()],
+			dedupe: None,
+		};
+		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::append_playlist(
+		TEST_PLAYLIST_NAME,
+		dto::AppendPlaylistInput {
+			tracks: vec![second.to_string_lossy().into_owned()],
+		},
+	);
+	let response = service.fetch_json::<_, dto::AppendPlaylistResult>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().tracks_added, 1);
+
+	let request = protocol::read_playlist(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let paths: Vec<PathBuf> = response
+		.body()
+		.iter()
+		.map(|s| PathBuf::from(&s.path))
+		.collect();
+	assert_eq!(paths, vec![first, second]);
+}
+
+#[test]
+fn append_playlist_bad_name_returns_not_found() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::append_playlist(
+		TEST_PLAYLIST_NAME,
+		dto::AppendPlaylistInput { tracks: Vec::new() },
+	);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::read_playlist(TEST_PLAYLIST_NAME);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn move_playlist_track_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::move_playlist_track(TEST_PLAYLIST_NAME, 0, 1);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn move_playlist_track_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let first: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let second: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "05 - Hunted.mp3"]
+		.iter()
+		.collect();
+
+	{
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: vec![
+				first.to_string_lossy().into_owned(),
+				second.to_string_lossy().into_owned(),
+			],
+			dedupe: None,
+		};
+		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::move_playlist_track(TEST_PLAYLIST_NAME, 1, 0);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::read_playlist(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let paths: Vec<PathBuf> = response
+		.body()
+		.iter()
+		.map(|s| PathBuf::from(&s.path))
+		.collect();
+	assert_eq!(paths, vec![second, first]);
+}
+
+#[test]
+fn move_playlist_track_bad_index_returns_bad_request() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	{
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: Vec::new(),
+			dedupe: None,
+		};
+		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::move_playlist_track(TEST_PLAYLIST_NAME, 0, 3);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn move_playlist_track_bad_name_returns_not_found() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::move_playlist_track(TEST_PLAYLIST_NAME, 0, 0);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
 #[test]
 fn delete_playlist_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
@@ -111,7 +359,10 @@ fn delete_playlist_golden_path() {
 	service.login();
 
 	{
-		let my_playlist = dto::SavePlaylistInput { tracks: Vec::new() };
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: Vec::new(),
+			dedupe: None,
+		};
 		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 		let response = service.fetch(&request);
 		assert_eq!(response.status(), StatusCode::OK);
@@ -132,3 +383,52 @@ fn delete_playlist_bad_name_returns_not_found() {
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
+
+#[test]
+fn get_queue_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::queue();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn save_queue_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::save_queue(dto::SaveQueueInput {
+		tracks: Vec::new(),
+		current_index: 0,
+		position_ms: 0,
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn queue_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::save_queue(dto::SaveQueueInput {
+		tracks: vec![path.to_string_lossy().into_owned()],
+		current_index: 0,
+		position_ms: 42_000,
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::queue();
+	let response = service.fetch_json::<_, dto::Queue>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let queue = response.body();
+	assert_eq!(queue.tracks.len(), 1);
+	assert_eq!(queue.tracks[0]["path"], path.to_string_lossy().as_ref());
+	assert_eq!(queue.current_index, 0);
+	assert_eq!(queue.position_ms, 42_000);
+}
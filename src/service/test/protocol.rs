@@ -29,6 +29,14 @@ pub fn version() -> Request<()> {
 		.unwrap()
 }
 
+pub fn capabilities() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/capabilities")
+		.body(())
+		.unwrap()
+}
+
 pub fn initial_setup() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -37,6 +45,14 @@ pub fn initial_setup() -> Request<()> {
 		.unwrap()
 }
 
+pub fn setup_status() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/setup_status")
+		.body(())
+		.unwrap()
+}
+
 pub fn login(username: &str, password: &str) -> Request<dto::Credentials> {
 	let credentials = dto::Credentials {
 		username: username.into(),
@@ -73,6 +89,14 @@ pub fn put_settings(settings: dto::NewSettings) -> Request<dto::NewSettings> {
 		.unwrap()
 }
 
+pub fn put_mount_dirs(mount_dirs: Vec<dto::MountDir>) -> Request<Vec<dto::MountDir>> {
+	Request::builder()
+		.method(Method::PUT)
+		.uri("/api/mount_dirs")
+		.body(mount_dirs)
+		.unwrap()
+}
+
 pub fn get_ddns_config() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -129,6 +153,35 @@ pub fn get_preferences() -> Request<()> {
 		.unwrap()
 }
 
+pub fn get_device_preferences(device: &str) -> Request<()> {
+	let endpoint = format!("/api/preferences?device={}", url_encode(device));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn put_device_preferences(
+	device: &str,
+	preferences: user::Preferences,
+) -> Request<user::Preferences> {
+	let endpoint = format!("/api/preferences?device={}", url_encode(device));
+	Request::builder()
+		.method(Method::PUT)
+		.uri(&endpoint)
+		.body(preferences)
+		.unwrap()
+}
+
+pub fn get_preferences_schema() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/preferences/schema")
+		.body(())
+		.unwrap()
+}
+
 pub fn put_preferences(preferences: user::Preferences) -> Request<user::Preferences> {
 	Request::builder()
 		.method(Method::PUT)
@@ -145,6 +198,65 @@ pub fn trigger_index() -> Request<()> {
 		.unwrap()
 }
 
+pub fn clean_index() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/index/clean")
+		.body(())
+		.unwrap()
+}
+
+pub fn refresh_artwork(all: bool) -> Request<()> {
+	let endpoint = format!("/api/admin/refresh_artwork?all={}", all);
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn scan_status() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/scans")
+		.body(())
+		.unwrap()
+}
+
+pub fn scan_history() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/index/history")
+		.body(())
+		.unwrap()
+}
+
+pub fn cancel_scan() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/scans/cancel")
+		.body(())
+		.unwrap()
+}
+
+pub fn audit_log() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/admin/audit")
+		.body(())
+		.unwrap()
+}
+
+pub fn resolve_path(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/admin/resolve?path={}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn browse(path: &Path) -> Request<()> {
 	let path = path.to_string_lossy();
 	let endpoint = format!("/api/browse/{}", url_encode(path.as_ref()));
@@ -155,6 +267,233 @@ pub fn browse(path: &Path) -> Request<()> {
 		.unwrap()
 }
 
+pub fn album_at(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/album_at/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn album_credits(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/album_credits/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn album_page(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/album_page/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn artists() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/artists")
+		.body(())
+		.unwrap()
+}
+
+pub fn artists_page(offset: usize, count: usize) -> Request<()> {
+	let endpoint = format!("/api/artists?offset={}&count={}", offset, count);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn artist_buckets() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/artists/index")
+		.body(())
+		.unwrap()
+}
+
+pub fn field_values(field: &str) -> Request<()> {
+	let endpoint = format!("/api/field_values/{}", url_encode(field));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn field_values_page(field: &str, offset: usize, count: usize) -> Request<()> {
+	let endpoint = format!(
+		"/api/field_values/{}?offset={}&count={}",
+		url_encode(field),
+		offset,
+		count
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn random_artists(count: usize, seed: u64) -> Request<()> {
+	let endpoint = format!("/api/artists/random?count={}&seed={}", count, seed);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn random_genres(count: usize, seed: u64) -> Request<()> {
+	let endpoint = format!("/api/genres/random?count={}&seed={}", count, seed);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn raw_tags(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/song/{}/raw_tags", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn similar_artists(name: &str, count: usize) -> Request<()> {
+	let endpoint = format!("/api/artist/{}/similar?count={}", url_encode(name), count);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn artist_songs(name: &str, sort: Option<dto::SongSort>) -> Request<()> {
+	let mut endpoint = format!("/api/artist/{}/songs", url_encode(name));
+	if let Some(sort) = sort {
+		endpoint.push_str(&format!("?sort={}", song_sort_name(sort)));
+	}
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn genre_songs(genres: &[&str]) -> Request<()> {
+	let genres: Vec<String> = genres.iter().map(|g| url_encode(g)).collect();
+	let endpoint = format!("/api/genres/songs?genres={}", genres.join(","));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn browse_song_directory(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/song/{}/directory", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn stats() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/stats")
+		.body(())
+		.unwrap()
+}
+
+pub fn years() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/years")
+		.body(())
+		.unwrap()
+}
+
+pub fn years_page(offset: usize, count: usize) -> Request<()> {
+	let endpoint = format!("/api/years?offset={}&count={}", offset, count);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn year_albums(year: i32) -> Request<()> {
+	let endpoint = format!("/api/year/{}/albums", year);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn year_albums_page(year: i32, offset: usize, count: usize, envelope: bool) -> Request<()> {
+	let endpoint = format!(
+		"/api/year/{}/albums?offset={}&count={}&envelope={}",
+		year, offset, count, envelope
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn decades() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/decades")
+		.body(())
+		.unwrap()
+}
+
+pub fn labels() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/labels")
+		.body(())
+		.unwrap()
+}
+
+pub fn labels_page(offset: usize, count: usize) -> Request<()> {
+	let endpoint = format!("/api/labels?offset={}&count={}", offset, count);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn label(name: &str) -> Request<()> {
+	let endpoint = format!("/api/label/{}", url_encode(name));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn flatten(path: &Path) -> Request<()> {
 	let path = path.to_string_lossy();
 	let endpoint = format!("/api/flatten/{}", url_encode(path.as_ref()));
@@ -165,6 +504,93 @@ pub fn flatten(path: &Path) -> Request<()> {
 		.unwrap()
 }
 
+pub fn flatten_sorted(path: &Path, sort: dto::SongSort) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/flatten/{}?sort={}",
+		url_encode(path.as_ref()),
+		song_sort_name(sort)
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn flatten_stream(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/flatten_stream/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn flatten_page(path: &Path, offset: usize, count: usize, envelope: bool) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/flatten/{}?offset={}&count={}&envelope={}",
+		url_encode(path.as_ref()),
+		offset,
+		count,
+		envelope
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn flatten_with_fields(path: &Path, fields: &str) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/flatten/{}?fields={}",
+		url_encode(path.as_ref()),
+		url_encode(fields)
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn flatten_with_real_paths(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/flatten/{}?fields=path&include_real_path=true",
+		url_encode(path.as_ref())
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn index_generation() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/index/generation")
+		.body(())
+		.unwrap()
+}
+
+pub fn sync(since: Option<i32>) -> Request<()> {
+	let endpoint = match since {
+		Some(since) => format!("/api/sync?since={}", since),
+		None => "/api/sync".to_owned(),
+	};
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn random() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -181,6 +607,14 @@ pub fn recent() -> Request<()> {
 		.unwrap()
 }
 
+pub fn recent_albums_only() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/recent?exclude_singles=true")
+		.body(())
+		.unwrap()
+}
+
 pub fn search(query: &str) -> Request<()> {
 	let endpoint = format!("/api/search/{}", url_encode(query));
 	Request::builder()
@@ -200,6 +634,21 @@ pub fn audio(path: &Path) -> Request<()> {
 		.unwrap()
 }
 
+pub fn audio_transcoded(path: &Path, codec: &str, bitrate: u32) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/audio/{}?transcode={}&bitrate={}",
+		url_encode(path.as_ref()),
+		url_encode(codec),
+		bitrate
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn thumbnail(path: &Path, size: Option<ThumbnailSize>, pad: Option<bool>) -> Request<()> {
 	let path = path.to_string_lossy();
 	let mut params = String::new();
@@ -233,6 +682,54 @@ pub fn thumbnail(path: &Path, size: Option<ThumbnailSize>, pad: Option<bool>) ->
 		.unwrap()
 }
 
+pub fn thumbnail_with_pad_color(path: &Path, pad_color: &str) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/thumbnail/{}?pad_color={}",
+		url_encode(path.as_ref()),
+		url_encode(pad_color)
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn thumbnail_with_format(path: &Path, format: &str) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/thumbnail/{}?format={}",
+		url_encode(path.as_ref()),
+		url_encode(format)
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn thumbnail_with_fit(path: &Path, size: ThumbnailSize, fit: &str) -> Request<()> {
+	let path = path.to_string_lossy();
+	let size = match size {
+		ThumbnailSize::Small => "small",
+		ThumbnailSize::Large => "large",
+		ThumbnailSize::Native => "native",
+	};
+	let endpoint = format!(
+		"/api/thumbnail/{}?size={}&fit={}",
+		url_encode(path.as_ref()),
+		size,
+		url_encode(fit)
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn playlists() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -253,6 +750,51 @@ pub fn save_playlist(
 		.unwrap()
 }
 
+pub fn save_playlists(
+	playlists: Vec<dto::BulkSavePlaylistInput>,
+) -> Request<Vec<dto::BulkSavePlaylistInput>> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/playlists/bulk")
+		.body(playlists)
+		.unwrap()
+}
+
+pub fn prewarm_thumbnails(
+	input: dto::PrewarmThumbnailsInput,
+) -> Request<dto::PrewarmThumbnailsInput> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/thumbnails/prewarm")
+		.body(input)
+		.unwrap()
+}
+
+pub fn append_playlist(
+	name: &str,
+	tracks: dto::AppendPlaylistInput,
+) -> Request<dto::AppendPlaylistInput> {
+	let endpoint = format!("/api/playlist/{}/append", url_encode(name));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(tracks)
+		.unwrap()
+}
+
+pub fn move_playlist_track(
+	name: &str,
+	from: usize,
+	to: usize,
+) -> Request<dto::MovePlaylistTrackInput> {
+	let endpoint = format!("/api/playlist/{}/move", url_encode(name));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(dto::MovePlaylistTrackInput { from, to })
+		.unwrap()
+}
+
 pub fn read_playlist(name: &str) -> Request<()> {
 	let endpoint = format!("/api/playlist/{}", url_encode(name));
 	Request::builder()
@@ -271,6 +813,22 @@ pub fn delete_playlist(name: &str) -> Request<()> {
 		.unwrap()
 }
 
+pub fn queue() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/queue")
+		.body(())
+		.unwrap()
+}
+
+pub fn save_queue(queue: dto::SaveQueueInput) -> Request<dto::SaveQueueInput> {
+	Request::builder()
+		.method(Method::PUT)
+		.uri("/api/queue")
+		.body(queue)
+		.unwrap()
+}
+
 pub fn lastfm_link_token() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -302,3 +860,14 @@ pub fn lastfm_scrobble(path: &Path) -> Request<()> {
 fn url_encode(input: &str) -> String {
 	percent_encode(input.as_bytes(), NON_ALPHANUMERIC).to_string()
 }
+
+fn song_sort_name(sort: dto::SongSort) -> &'static str {
+	match sort {
+		dto::SongSort::Album => "album",
+		dto::SongSort::Title => "title",
+		dto::SongSort::Year => "year",
+		dto::SongSort::Path => "path",
+		dto::SongSort::Track => "track",
+		dto::SongSort::DateAdded => "date_added",
+	}
+}
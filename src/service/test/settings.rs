@@ -63,6 +63,24 @@ fn put_settings_golden_path() {
 
 	let request = protocol::put_settings(dto::NewSettings {
 		album_art_pattern: Some("test_pattern".to_owned()),
+		album_artist_fallback: Some("directory_name".to_owned()),
+		keep_unreadable_songs: Some(true),
+		path_pattern: Some("{artist}/{album}/{track} - {title}".to_owned()),
+		exact_durations: Some(true),
+		skip_non_utf8_paths: Some(true),
+		password_min_length: Some(8),
+		password_require_complexity: Some(false),
+		case_insensitive_paths: Some(true),
+		folder_name_albums: Some(true),
+		min_year: Some(1900),
+		max_year: Some(2100),
+		thumbnail_default_size: Some("large".to_owned()),
+		artwork_preference: Some("embedded".to_owned()),
+		compilation_artist_names: Some(vec![
+			"Various Artists".to_owned(),
+			" Verschiedene Interpreten ".to_owned(),
+		]),
+		transcode_targets: Some(vec!["mp3:160".to_owned(), " opus:64".to_owned()]),
 		reindex_every_n_seconds: Some(31),
 	});
 	let response = service.fetch(&request);
@@ -75,7 +93,91 @@ fn put_settings_golden_path() {
 		settings,
 		&Settings {
 			album_art_pattern: "test_pattern".to_owned(),
+			album_artist_fallback: "directory_name".to_owned(),
+			keep_unreadable_songs: true,
+			path_pattern: "{artist}/{album}/{track} - {title}".to_owned(),
+			exact_durations: true,
+			skip_non_utf8_paths: true,
+			password_min_length: 8,
+			password_require_complexity: false,
+			case_insensitive_paths: true,
+			folder_name_albums: true,
+			min_year: 1900,
+			max_year: 2100,
+			thumbnail_default_size: "large".to_owned(),
+			artwork_preference: "embedded".to_owned(),
+			compilation_artist_names: vec![
+				"Various Artists".to_owned(),
+				"Verschiedene Interpreten".to_owned(),
+			],
+			transcode_targets: vec!["mp3:160".to_owned(), "opus:64".to_owned()],
 			reindex_every_n_seconds: 31,
 		},
 	);
 }
+
+#[test]
+fn put_settings_rejects_invalid_values() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let invalid_settings = [
+		dto::NewSettings {
+			album_artist_fallback: Some("nobody".to_owned()),
+			..Default::default()
+		},
+		dto::NewSettings {
+			artwork_preference: Some("painted".to_owned()),
+			..Default::default()
+		},
+		dto::NewSettings {
+			path_pattern: Some("{artist".to_owned()),
+			..Default::default()
+		},
+		dto::NewSettings {
+			reindex_every_n_seconds: Some(-1),
+			..Default::default()
+		},
+		dto::NewSettings {
+			thumbnail_default_size: Some("huge".to_owned()),
+			..Default::default()
+		},
+		dto::NewSettings {
+			transcode_targets: Some(vec!["wma:128".to_owned()]),
+			..Default::default()
+		},
+	];
+	for settings in invalid_settings {
+		let request = protocol::put_settings(settings);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+	}
+}
+
+#[test]
+fn put_mount_dirs_reports_overlaps() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::put_mount_dirs(vec![
+		dto::MountDir {
+			name: "root".to_owned(),
+			source: "test-data/small-collection".to_owned(),
+		},
+		dto::MountDir {
+			name: "khemmis".to_owned(),
+			source: "test-data/small-collection/Khemmis".to_owned(),
+		},
+	]);
+	let response = service.fetch_json::<_, Vec<dto::MountDirOverlap>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&vec![dto::MountDirOverlap {
+			name: "root".to_owned(),
+			nested_name: "khemmis".to_owned(),
+		}]
+	);
+}
@@ -48,6 +48,28 @@ fn create_user_requires_admin() {
 	assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
+#[test]
+fn create_user_rejects_weak_password() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::put_settings(dto::NewSettings {
+		password_min_length: Some(12),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::create_user(dto::NewUser {
+		name: "Walter".into(),
+		password: "secret".into(),
+		admin: false,
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[test]
 fn create_user_golden_path() {
 	let mut service = ServiceType::new(&test_name!());
@@ -160,6 +182,31 @@ fn get_preferences_golden_path() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn get_preferences_schema_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::get_preferences_schema();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn get_preferences_schema_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::get_preferences_schema();
+	let response = service.fetch_json::<_, Vec<user::PreferenceField>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let fields = response.body();
+	let theme_base = fields.iter().find(|f| f.name == "web_theme_base").unwrap();
+	assert_eq!(theme_base.value_type, user::PreferenceType::String);
+	assert!(!theme_base.read_only);
+	let lastfm_username = fields.iter().find(|f| f.name == "lastfm_username").unwrap();
+	assert!(lastfm_username.read_only);
+}
+
 #[test]
 fn put_preferences_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
@@ -178,3 +225,40 @@ fn put_preferences_golden_path() {
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::OK);
 }
+
+#[test]
+fn device_preferences_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let user_preferences = user::Preferences {
+		web_theme_base: Some("very-dark-theme".to_owned()),
+		..Default::default()
+	};
+	let request = protocol::put_preferences(user_preferences);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let device_preferences = user::Preferences {
+		web_theme_accent: Some("#00FF00".to_owned()),
+		..Default::default()
+	};
+	let request = protocol::put_device_preferences("phone", device_preferences);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::get_device_preferences("phone");
+	let response = service.fetch_json::<_, user::Preferences>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let preferences = response.body();
+	assert_eq!(
+		preferences.web_theme_base,
+		Some("very-dark-theme".to_owned())
+	);
+	assert_eq!(preferences.web_theme_accent, Some("#00FF00".to_owned()));
+
+	let request = protocol::get_preferences();
+	let response = service.fetch_json::<_, user::Preferences>(&request);
+	assert_eq!(response.body().web_theme_accent, None);
+}
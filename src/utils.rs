@@ -1,4 +1,7 @@
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
+use std::str::FromStr;
 
 #[macro_export]
 macro_rules! match_ignore_case {
@@ -13,7 +16,7 @@ macro_rules! match_ignore_case {
 pub use crate::match_ignore_case;
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AudioFormat {
 	AIFF,
 	APE,
@@ -27,6 +30,26 @@ pub enum AudioFormat {
 	M4B,
 }
 
+pub const AUDIO_EXTENSIONS: &[(&str, AudioFormat)] = &[
+	("aif", AudioFormat::AIFF),
+	("aiff", AudioFormat::AIFF),
+	("ape", AudioFormat::APE),
+	("flac", AudioFormat::FLAC),
+	("mp3", AudioFormat::MP3),
+	("m4a", AudioFormat::MP4),
+	("mpc", AudioFormat::MPC),
+	("ogg", AudioFormat::OGG),
+	("opus", AudioFormat::OPUS),
+	("wav", AudioFormat::WAVE),
+	("m4b", AudioFormat::M4B),
+];
+
+pub fn read_env_var<T: FromStr>(key: &str) -> Option<T> {
+	std::env::var_os(key)
+		.map(|v| v.to_string_lossy().to_string())
+		.and_then(|v| T::from_str(&v).ok())
+}
+
 pub fn get_audio_format(path: &Path) -> Option<AudioFormat> {
 	let extension = match path.extension() {
 		Some(e) => e,
@@ -36,19 +59,76 @@ pub fn get_audio_format(path: &Path) -> Option<AudioFormat> {
 		Some(e) => e,
 		_ => return None,
 	};
-	match extension.to_lowercase().as_str() {
-		"aif" => Some(AudioFormat::AIFF),
-		"aiff" => Some(AudioFormat::AIFF),
-		"ape" => Some(AudioFormat::APE),
-		"flac" => Some(AudioFormat::FLAC),
-		"mp3" => Some(AudioFormat::MP3),
-		"m4a" => Some(AudioFormat::MP4),
-		"mpc" => Some(AudioFormat::MPC),
-		"ogg" => Some(AudioFormat::OGG),
-		"opus" => Some(AudioFormat::OPUS),
-		"wav" => Some(AudioFormat::WAVE),
-		"m4b" => Some(AudioFormat::M4B),
-		_ => None,
+	AUDIO_EXTENSIONS
+		.iter()
+		.find(|(e, _)| e.eq_ignore_ascii_case(extension))
+		.map(|(_, format)| *format)
+}
+
+impl AudioFormat {
+	pub fn mime_type(&self) -> &'static str {
+		match self {
+			AudioFormat::AIFF => "audio/aiff",
+			AudioFormat::APE => "audio/x-ape",
+			AudioFormat::FLAC => "audio/flac",
+			AudioFormat::MP3 => "audio/mpeg",
+			AudioFormat::MP4 | AudioFormat::M4B => "audio/mp4",
+			AudioFormat::MPC => "audio/x-musepack",
+			AudioFormat::OGG => "audio/ogg",
+			AudioFormat::OPUS => "audio/opus",
+			AudioFormat::WAVE => "audio/wav",
+		}
+	}
+}
+
+// Format of an audio file according to its first bytes, when its extension is missing or
+// disagrees with them. Returns `None` when the extension can be trusted (or nothing was recognized).
+pub fn sniff_mislabeled_audio_format(path: &Path) -> Option<AudioFormat> {
+	let sniffed = sniff_audio_format(path)?;
+	match get_audio_format(path) {
+		Some(format) if format.mime_type() == sniffed.mime_type() => None,
+		_ => Some(sniffed),
+	}
+}
+
+// Identifies audio files from their first bytes
+pub fn sniff_audio_format(path: &Path) -> Option<AudioFormat> {
+	let mut header = Vec::with_capacity(36);
+	File::open(path)
+		.ok()?
+		.take(36)
+		.read_to_end(&mut header)
+		.ok()?;
+	let bytes = |range: std::ops::Range<usize>| header.get(range);
+
+	if bytes(0..4) == Some(b"fLaC") {
+		Some(AudioFormat::FLAC)
+	} else if bytes(0..3) == Some(b"ID3")
+		|| matches!(bytes(0..2), Some(&[0xFF, b]) if b & 0xE0 == 0xE0)
+	{
+		// ID3v2 tag, or MPEG frame sync
+		Some(AudioFormat::MP3)
+	} else if bytes(0..4) == Some(b"OggS") {
+		match bytes(28..36) {
+			Some(b"OpusHead") => Some(AudioFormat::OPUS),
+			_ => Some(AudioFormat::OGG),
+		}
+	} else if bytes(0..4) == Some(b"RIFF") && bytes(8..12) == Some(b"WAVE") {
+		Some(AudioFormat::WAVE)
+	} else if bytes(0..4) == Some(b"FORM") && matches!(bytes(8..12), Some(b"AIFF") | Some(b"AIFC"))
+	{
+		Some(AudioFormat::AIFF)
+	} else if bytes(4..8) == Some(b"ftyp") {
+		match bytes(8..12) {
+			Some(b"M4B ") => Some(AudioFormat::M4B),
+			_ => Some(AudioFormat::MP4),
+		}
+	} else if bytes(0..4) == Some(b"MAC ") {
+		Some(AudioFormat::APE)
+	} else if bytes(0..4) == Some(b"MPCK") || bytes(0..3) == Some(b"MP+") {
+		Some(AudioFormat::MPC)
+	} else {
+		None
 	}
 }
 
@@ -72,3 +152,42 @@ fn can_guess_audio_format() {
 		Some(AudioFormat::WAVE)
 	);
 }
+
+#[test]
+fn can_sniff_audio_format() {
+	let sniff = |name: &str| sniff_audio_format(&Path::new("test-data/formats").join(name));
+	assert_eq!(sniff("sample.aif"), Some(AudioFormat::AIFF));
+	assert_eq!(sniff("sample.ape"), Some(AudioFormat::APE));
+	assert_eq!(sniff("sample.flac"), Some(AudioFormat::FLAC));
+	assert_eq!(sniff("sample.m4a"), Some(AudioFormat::MP4));
+	assert_eq!(sniff("sample.mp3"), Some(AudioFormat::MP3));
+	assert_eq!(sniff("sample.ogg"), Some(AudioFormat::OGG));
+	assert_eq!(sniff("sample.opus"), Some(AudioFormat::OPUS));
+	assert_eq!(sniff("sample.wav"), Some(AudioFormat::WAVE));
+	assert_eq!(
+		sniff_audio_format(Path::new("test-data/artwork/Folder.png")),
+		None
+	);
+}
+
+#[test]
+fn can_sniff_mislabeled_audio_format() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+	let mislabeled = test_directory.join("sample.flac");
+	std::fs::copy("test-data/formats/sample.mp3", &mislabeled).unwrap();
+	let unlabeled = test_directory.join("sample");
+	std::fs::copy("test-data/formats/sample.ogg", &unlabeled).unwrap();
+
+	assert_eq!(
+		sniff_mislabeled_audio_format(&mislabeled),
+		Some(AudioFormat::MP3)
+	);
+	assert_eq!(
+		sniff_mislabeled_audio_format(&unlabeled),
+		Some(AudioFormat::OGG)
+	);
+	assert_eq!(
+		sniff_mislabeled_audio_format(Path::new("test-data/formats/sample.flac")),
+		None
+	);
+}
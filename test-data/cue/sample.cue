@@ -0,0 +1,12 @@
+REM GENRE "TEST GENRE"
+REM DATE 2016
+PERFORMER "TEST CUE ARTIST"
+TITLE "TEST CUE ALBUM"
+FILE "sample.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "First Track"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Second Track"
+    PERFORMER "TEST GUEST ARTIST"
+    INDEX 01 00:00:30